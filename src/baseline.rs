@@ -1,8 +1,9 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
 use std::fs;
 
 use crate::state::Baseline;
-use crate::{AppliedMigration, Migration};
+use crate::verify;
+use crate::{AppliedMigration, MigrateError, Migration};
 
 /// Compare two version strings. Returns true if v1 <= v2.
 pub fn version_lte(v1: &str, v2: &str) -> bool {
@@ -41,6 +42,21 @@ pub fn delete_baselined_migrations(
                 });
             }
 
+            if let Some(verify_script) =
+                verify::script_path(migration).filter(|script| script.is_file())
+            {
+                fs::remove_file(&verify_script).with_context(|| {
+                    format!(
+                        "Failed to delete verify script: {}",
+                        verify_script.display()
+                    )
+                })?;
+                deleted.push(DeletedItem {
+                    path: verify_script.display().to_string(),
+                    is_directory: false,
+                });
+            }
+
             // Delete associated asset directory if it exists
             // The directory shares the migration ID as its name (e.g., "1f700-init/")
             if let Some(parent) = migration.file_path.parent() {
@@ -75,17 +91,21 @@ pub fn validate_baseline(
     // Check if the version matches any migration
     let matching_migration = available.iter().find(|m| m.version == version);
     if matching_migration.is_none() {
-        bail!("No migration found with version '{}'", version);
+        return Err(MigrateError::InvalidBaseline(format!(
+            "No migration found with version '{}'",
+            version
+        ))
+        .into());
     }
 
     // Cannot move baseline backward
     if let Some(existing) = existing_baseline {
         if version < existing.version.as_str() {
-            bail!(
+            return Err(MigrateError::InvalidBaseline(format!(
                 "Cannot move baseline backward from '{}' to '{}'",
-                existing.version,
-                version
-            );
+                existing.version, version
+            ))
+            .into());
         }
     }
 
@@ -96,10 +116,11 @@ pub fn validate_baseline(
     for migration in available {
         if version_lte(&migration.version, version) && !applied_ids.contains(migration.id.as_str())
         {
-            bail!(
+            return Err(MigrateError::InvalidBaseline(format!(
                 "Cannot baseline: migration '{}' has not been applied",
                 migration.id
-            );
+            ))
+            .into());
         }
     }
 
@@ -126,6 +147,7 @@ mod tests {
             id: "1f700-first".to_string(),
             version: "1f700".to_string(),
             file_path: PathBuf::from("1f700-first.sh"),
+            ..Default::default()
         }];
         let applied = vec![];
 
@@ -144,17 +166,16 @@ mod tests {
                 id: "1f700-first".to_string(),
                 version: "1f700".to_string(),
                 file_path: PathBuf::from("1f700-first.sh"),
+                ..Default::default()
             },
             Migration {
                 id: "1f710-second".to_string(),
                 version: "1f710".to_string(),
                 file_path: PathBuf::from("1f710-second.sh"),
+                ..Default::default()
             },
         ];
-        let applied = vec![AppliedMigration {
-            id: "1f710-second".to_string(),
-            applied_at: Utc::now(),
-        }];
+        let applied = vec![AppliedMigration::new("1f710-second", Utc::now())];
 
         // Try to baseline at 1f710, but 1f700 hasn't been applied
         let result = validate_baseline("1f710", &available, &applied, None);
@@ -172,22 +193,18 @@ mod tests {
                 id: "1f700-first".to_string(),
                 version: "1f700".to_string(),
                 file_path: PathBuf::from("1f700-first.sh"),
+                ..Default::default()
             },
             Migration {
                 id: "1f710-second".to_string(),
                 version: "1f710".to_string(),
                 file_path: PathBuf::from("1f710-second.sh"),
+                ..Default::default()
             },
         ];
         let applied = vec![
-            AppliedMigration {
-                id: "1f700-first".to_string(),
-                applied_at: Utc::now(),
-            },
-            AppliedMigration {
-                id: "1f710-second".to_string(),
-                applied_at: Utc::now(),
-            },
+            AppliedMigration::new("1f700-first", Utc::now()),
+            AppliedMigration::new("1f710-second", Utc::now()),
         ];
 
         let existing = Baseline {
@@ -208,22 +225,18 @@ mod tests {
                 id: "1f700-first".to_string(),
                 version: "1f700".to_string(),
                 file_path: PathBuf::from("1f700-first.sh"),
+                ..Default::default()
             },
             Migration {
                 id: "1f710-second".to_string(),
                 version: "1f710".to_string(),
                 file_path: PathBuf::from("1f710-second.sh"),
+                ..Default::default()
             },
         ];
         let applied = vec![
-            AppliedMigration {
-                id: "1f700-first".to_string(),
-                applied_at: Utc::now(),
-            },
-            AppliedMigration {
-                id: "1f710-second".to_string(),
-                applied_at: Utc::now(),
-            },
+            AppliedMigration::new("1f700-first", Utc::now()),
+            AppliedMigration::new("1f710-second", Utc::now()),
         ];
 
         let result = validate_baseline("1f710", &available, &applied, None);
@@ -256,11 +269,13 @@ mod tests {
                 id: "1f700-first".to_string(),
                 version: "1f700".to_string(),
                 file_path: migration_file.clone(),
+                ..Default::default()
             },
             Migration {
                 id: "1f710-second".to_string(),
                 version: "1f710".to_string(),
                 file_path: migration_file2.clone(),
+                ..Default::default()
             },
         ];
 
@@ -297,6 +312,7 @@ mod tests {
             id: "1f700-first".to_string(),
             version: "1f700".to_string(),
             file_path: migration_file.clone(),
+            ..Default::default()
         }];
 
         let deleted = delete_baselined_migrations("1f700", &available).unwrap();
@@ -1,45 +1,27 @@
 use anyhow::Result;
-use chrono::Utc;
-use std::path::Path;
 
-use crate::baseline::{delete_baselined_migrations, validate_baseline, DeletedItem};
-use crate::loader::discover_migrations;
-use crate::state::{append_baseline, read_history, Baseline};
+use crate::baseline::DeletedItem;
+use crate::Migrator;
 
 /// Create a baseline at the specified version
 pub fn run(
-    project_root: &Path,
-    migrations_dir: &Path,
+    migrator: Migrator,
     version: &str,
     summary: Option<&str>,
     dry_run: bool,
     keep: bool,
 ) -> Result<()> {
-    let migrations_path = if migrations_dir.is_absolute() {
-        migrations_dir.to_path_buf()
-    } else {
-        project_root.join(migrations_dir)
-    };
+    let migrator = migrator.dry_run(dry_run).keep_files(keep);
 
-    if !migrations_path.exists() {
+    if !migrator.migrations_path().exists() {
         println!(
             "No migrations directory found at: {}",
-            migrations_path.display()
+            migrator.migrations_path().display()
         );
         return Ok(());
     }
 
-    let available = discover_migrations(&migrations_path)?;
-    let state = read_history(&migrations_path)?;
-
-    // Validate the baseline
-    validate_baseline(version, &available, &state.applied, state.baseline.as_ref())?;
-
-    // Find migrations that would be deleted
-    let to_delete: Vec<_> = available
-        .iter()
-        .filter(|m| m.version.as_str() <= version)
-        .collect();
+    let report = migrator.baseline(version, summary)?;
 
     if dry_run {
         println!("Dry run - no changes will be made");
@@ -53,29 +35,19 @@ pub fn run(
     );
     println!();
 
-    if !to_delete.is_empty() && !keep {
+    if !report.covered.is_empty() && !keep {
         println!("{}:", if dry_run { "Would delete" } else { "Deleting" });
-        for migration in &to_delete {
-            let asset_dir_exists = migration
-                .file_path
-                .parent()
-                .map(|p| p.join(&migration.id).is_dir())
-                .unwrap_or(false);
-            if asset_dir_exists {
-                println!("  - {} (file + {}/)", migration.id, migration.id);
+        for covered in &report.covered {
+            let id = &covered.migration.id;
+            if covered.has_asset_dir {
+                println!("  - {} (file + {}/)", id, id);
             } else {
-                println!("  - {}", migration.id);
+                println!("  - {}", id);
             }
         }
         println!();
     } else if keep {
-        let has_any_asset_dir = to_delete.iter().any(|m| {
-            m.file_path
-                .parent()
-                .map(|p| p.join(&m.id).is_dir())
-                .unwrap_or(false)
-        });
-        if has_any_asset_dir {
+        if report.covered.iter().any(|c| c.has_asset_dir) {
             println!("Keeping migration files and asset directories (--keep flag)");
         } else {
             println!("Keeping migration files (--keep flag)");
@@ -87,27 +59,15 @@ pub fn run(
         return Ok(());
     }
 
-    // Create the baseline
-    let baseline = Baseline {
-        version: version.to_string(),
-        created: Utc::now(),
-        summary: summary.map(|s| s.to_string()),
-    };
-
-    append_baseline(&migrations_path, &baseline)?;
     println!("Added baseline to history file");
 
-    // Delete old migration files and asset directories unless --keep was specified
-    if !keep && !to_delete.is_empty() {
-        let deleted = delete_baselined_migrations(version, &available)?;
-        let (files, dirs): (Vec<&DeletedItem>, Vec<&DeletedItem>) =
-            deleted.iter().partition(|d| !d.is_directory);
-        if !files.is_empty() {
-            println!("Deleted {} migration file(s)", files.len());
-        }
-        if !dirs.is_empty() {
-            println!("Deleted {} asset directory(ies)", dirs.len());
-        }
+    let (files, dirs): (Vec<&DeletedItem>, Vec<&DeletedItem>) =
+        report.deleted.iter().partition(|d| !d.is_directory);
+    if !files.is_empty() {
+        println!("Deleted {} migration file(s)", files.len());
+    }
+    if !dirs.is_empty() {
+        println!("Deleted {} asset directory(ies)", dirs.len());
     }
 
     println!();
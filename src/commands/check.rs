@@ -0,0 +1,96 @@
+use anyhow::Result;
+
+use crate::dag;
+use crate::migrator::Status;
+use crate::{MigrateError, Migrator};
+
+/// Fail if any migrations are pending (for CI)
+pub fn run(migrator: &Migrator) -> Result<()> {
+    if !migrator.migrations_path().exists() {
+        println!(
+            "No migrations directory found at: {}",
+            migrator.migrations_path().display()
+        );
+        return Ok(());
+    }
+
+    let status = migrator.status()?;
+    for warning in lint(&status) {
+        eprintln!("warning: {}", warning);
+    }
+    if status.pending.is_empty() {
+        println!("Up to date.");
+        return Ok(());
+    }
+
+    println!("Pending ({}):", status.pending.len());
+    for migration in &status.pending {
+        println!("  - {}", migration.id);
+    }
+    Err(MigrateError::PendingMigrations {
+        count: status.pending.len(),
+    }
+    .into())
+}
+
+/// Problems with migration headers: unknown fields, invalid values and `DependsOn` problems
+/// (which also stop `migrate up`)
+pub fn lint(status: &Status) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for migration in &status.available {
+        let name = migration
+            .file_path
+            .file_name()
+            .map_or(migration.id.clone(), |n| n.to_string_lossy().to_string());
+        for issue in &migration.header.issues {
+            warnings.push(format!("{}:{}: {}", name, issue.line, issue.message));
+        }
+    }
+    warnings.extend(dag::validate(
+        &status.available,
+        &status.applied,
+        status.baseline.as_ref(),
+    ));
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AppliedMigration, Header, Migration};
+    use chrono::Utc;
+
+    #[test]
+    fn test_lint() {
+        let migration = |id: &str, header: &str| Migration {
+            id: id.to_string(),
+            version: id[..5].to_string(),
+            file_path: format!("migrations/{}.sh", id).into(),
+            header: Header::parse(header),
+            ..Default::default()
+        };
+        let status = Status {
+            available: vec![
+                migration("1f700-first", "# Description: First\n"),
+                migration(
+                    "1f710-second",
+                    "# DependsOn: 1f700-first, 1f6zz-gone, 1f705-typo\n# Owner: me\n",
+                ),
+            ],
+            applied: vec![AppliedMigration::new("1f6zz-gone", Utc::now())],
+            baseline: None,
+            state: String::new(),
+            pending: Vec::new(),
+            filtered: Vec::new(),
+            current_version: None,
+            target_version: None,
+        };
+        assert_eq!(
+            lint(&status),
+            [
+                "1f710-second.sh:2: unknown header field 'Owner'",
+                "1f710-second depends on unknown migration '1f705-typo'",
+            ]
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use anyhow::{bail, Result};
 use std::fs::{self, OpenOptions};
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
@@ -9,6 +9,7 @@ use std::os::unix::fs::PermissionsExt;
 use crate::loader::discover_migrations;
 use crate::templates::{get_template, list_templates};
 use crate::version::generate_version;
+use crate::MigrateError;
 
 /// Create a new migration file
 pub fn run(
@@ -38,11 +39,11 @@ pub fn run(
     let template = match get_template(template_name) {
         Some(t) => t,
         None => {
-            bail!(
-                "Unknown template '{}'. Available: {}",
-                template_name,
-                list_templates().collect::<Vec<_>>().join(", ")
-            );
+            return Err(MigrateError::UnknownTemplate {
+                name: template_name.to_string(),
+                available: list_templates().map(String::from).collect(),
+            }
+            .into());
         }
     };
 
@@ -52,14 +53,35 @@ pub fn run(
         project_root.join(migrations_dir)
     };
 
+    // Prepare template content
+    let description_text = description.unwrap_or("TODO: Add description");
+    let content = template
+        .content
+        .replace("{{DESCRIPTION}}", description_text);
+
+    let file_path = create_migration(&migrations_path, name, template.extension, &content)?;
+
+    println!("Created migration: {}", file_path.display());
+
+    Ok(())
+}
+
+/// Write a new migration file with a freshly generated version into the migrations directory.
+/// Returns the path of the created file.
+pub fn create_migration(
+    migrations_path: &Path,
+    name: &str,
+    extension: &str,
+    content: &str,
+) -> Result<PathBuf> {
     // Create migrations directory if it doesn't exist
-    fs::create_dir_all(&migrations_path)?;
+    fs::create_dir_all(migrations_path)?;
 
     // Generate version from current time
     let version = generate_version();
 
     // Check for version collision with existing migrations
-    let existing = discover_migrations(&migrations_path).unwrap_or_default();
+    let existing = discover_migrations(migrations_path).unwrap_or_default();
     if existing.iter().any(|m| m.version == version) {
         bail!(
             "A migration with version {} already exists. Wait a few minutes or use a different time slot.",
@@ -68,7 +90,7 @@ pub fn run(
     }
 
     // Build filename
-    let filename = format!("{}-{}{}", version, name, template.extension);
+    let filename = format!("{}-{}{}", version, name, extension);
     let file_path = migrations_path.join(&filename);
 
     // Check if file already exists
@@ -76,12 +98,6 @@ pub fn run(
         bail!("Migration file already exists: {}", file_path.display());
     }
 
-    // Prepare template content
-    let description_text = description.unwrap_or("TODO: Add description");
-    let content = template
-        .content
-        .replace("{{DESCRIPTION}}", description_text);
-
     // Write file
     let mut file = OpenOptions::new()
         .create(true)
@@ -99,7 +115,5 @@ pub fn run(
         fs::set_permissions(&file_path, perms)?;
     }
 
-    println!("Created migration: {}", file_path.display());
-
-    Ok(())
+    Ok(file_path)
 }
@@ -0,0 +1,183 @@
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::declarative::{Step, StepEnv};
+use crate::executor::{TOUCHED_FILES_ENV, VARIABLES_ENV};
+use crate::render::Variables;
+use crate::structured::DataEdit;
+
+/// Context for helper commands, taken from the `MIGRATE_*` environment of the running
+/// migration. Outside a migration, paths resolve against the current directory.
+struct HelperEnv {
+    project_root: PathBuf,
+    asset_dir: PathBuf,
+    dry_run: bool,
+    touched_log: Option<PathBuf>,
+    variables: Variables,
+}
+
+impl HelperEnv {
+    fn from_env() -> Result<Self> {
+        let cwd = std::env::current_dir()?;
+        let var = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty());
+
+        let project_root = var("MIGRATE_PROJECT_ROOT")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| cwd.clone());
+        let asset_dir = match (var("MIGRATE_MIGRATIONS_DIR"), var("MIGRATE_ID")) {
+            (Some(dir), Some(id)) => PathBuf::from(dir).join(id),
+            _ => cwd,
+        };
+
+        Ok(Self {
+            project_root,
+            asset_dir,
+            dry_run: std::env::var("MIGRATE_DRY_RUN").is_ok_and(|v| v == "true"),
+            touched_log: var(TOUCHED_FILES_ENV).map(PathBuf::from),
+            variables: match std::env::var(VARIABLES_ENV) {
+                Ok(json) if !json.is_empty() => {
+                    serde_json::from_str(&json).context("Invalid MIGRATE_VARS")?
+                }
+                _ => Variables::new(),
+            },
+        })
+    }
+
+    /// Record touched files so the running `migrate up` can include them in its summary.
+    fn report(&self, touched: &[PathBuf]) -> Result<()> {
+        let Some(log) = &self.touched_log else {
+            return Ok(());
+        };
+        if self.dry_run || touched.is_empty() {
+            return Ok(());
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log)
+            .with_context(|| format!("Failed to open {}", log.display()))?;
+        for path in touched {
+            writeln!(file, "{}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Run a built-in step on behalf of a script migration
+pub fn run_step(step: Step) -> Result<()> {
+    let env = HelperEnv::from_env()?;
+    let step_env = StepEnv {
+        project_root: &env.project_root,
+        asset_dir: env.asset_dir.clone(),
+        dry_run: env.dry_run,
+        variables: env.variables.clone(),
+    };
+
+    println!(
+        "{}{}",
+        if env.dry_run { "would " } else { "" },
+        step.describe()
+    );
+    let touched = step.apply(&step_env)?;
+    if touched.is_empty() {
+        println!("  (no changes)");
+    }
+    env.report(&touched)
+}
+
+/// Apply a single structured-data edit to a JSON, YAML or TOML file
+pub fn edit(file: &str, edit: DataEdit) -> Result<()> {
+    let env = HelperEnv::from_env()?;
+    run_step(Step::Edit {
+        path: project_path(&env.project_root, file),
+        edits: vec![edit],
+    })
+}
+
+/// Copy a file or directory from the migration's asset directory into the project
+pub fn copy_asset(asset: &str, dest: Option<&str>) -> Result<()> {
+    let env = HelperEnv::from_env()?;
+    let from = project_path(&env.asset_dir, asset);
+    let to = match dest {
+        Some(dest) => project_path(&env.project_root, dest),
+        None => from.clone(),
+    };
+    run_step(Step::Copy { from, to })
+}
+
+/// Append a line to a file unless it is already present
+pub fn ensure_line(file: &str, line: &str) -> Result<()> {
+    let env = HelperEnv::from_env()?;
+    run_step(Step::EnsureLine {
+        path: project_path(&env.project_root, file),
+        line: line.to_string(),
+    })
+}
+
+/// Replace regex (or literal) matches in every file matching a glob
+pub fn replace(files: &str, pattern: &str, replacement: &str, literal: bool) -> Result<()> {
+    let env = HelperEnv::from_env()?;
+    let (pattern, replacement) = if literal {
+        (regex::escape(pattern), replacement.replace('$', "$$"))
+    } else {
+        (pattern.to_string(), replacement.to_string())
+    };
+    run_step(Step::Replace {
+        files: project_path(&env.project_root, files),
+        pattern,
+        replacement,
+    })
+}
+
+/// Render an asset template (or a directory of `*.tmpl` files) into the project
+pub fn render_template(template: &str, dest: &str, vars: &[(String, String)]) -> Result<()> {
+    let env = HelperEnv::from_env()?;
+    let vars = vars
+        .iter()
+        .map(|(name, value)| (name.clone(), parse_value(value)))
+        .collect();
+    run_step(Step::Render {
+        from: project_path(&env.asset_dir, template),
+        to: project_path(&env.project_root, dest),
+        vars,
+    })
+}
+
+/// Turn a user-supplied path into one relative to `base`; absolute paths inside `base` are
+/// accepted, anything else is left for step validation to reject.
+fn project_path(base: &Path, raw: &str) -> String {
+    Path::new(raw)
+        .strip_prefix(base)
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| raw.to_string())
+}
+
+/// Parse a command-line value: valid JSON is used as-is, anything else is a plain string.
+pub fn parse_value(raw: &str) -> Value {
+    serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+/// Parse a JSON document given inline or as `@path` to read it from a file.
+pub fn parse_json_arg(raw: &str) -> Result<Value> {
+    let content = match raw.strip_prefix('@') {
+        Some(path) => {
+            fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?
+        }
+        None => raw.to_string(),
+    };
+    serde_json::from_str(&content).context("Invalid JSON")
+}
+
+/// Parse a `key=value` assignment given on the command line
+pub fn parse_assignment(raw: &str) -> Result<(String, String)> {
+    match raw.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => bail!("Expected key=value, got '{}'", raw),
+    }
+}
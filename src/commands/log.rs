@@ -0,0 +1,219 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use regex::Regex;
+
+use crate::reporter::format_duration;
+use crate::{AppliedMigration, Migrator};
+
+/// Filters for `migrate log`
+#[derive(Default)]
+pub struct LogOptions {
+    /// Only migrations applied at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only migrations applied before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Only migrations whose ID matches
+    pub grep: Option<Regex>,
+}
+
+/// Consecutive history records from the same `migrate up` run
+struct Run<'a> {
+    run_id: Option<&'a str>,
+    migrations: Vec<&'a AppliedMigration>,
+}
+
+/// Print the history as a timeline grouped by run
+pub fn run(migrator: &Migrator, options: &LogOptions) -> Result<()> {
+    let migrations_path = migrator.migrations_path();
+    if !migrations_path.exists() {
+        println!(
+            "No migrations directory found at: {}",
+            migrations_path.display()
+        );
+        return Ok(());
+    }
+
+    let status = migrator.status()?;
+    let matching: Vec<&AppliedMigration> = status
+        .applied
+        .iter()
+        .filter(|m| options.matches(m))
+        .collect();
+    if matching.is_empty() {
+        if status.applied.is_empty() {
+            println!("No migrations have been applied.");
+        } else {
+            println!("No applied migrations match.");
+        }
+        return Ok(());
+    }
+
+    for (index, run) in group_runs(&matching).iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        println!("{}", run_header(run));
+        for migration in &run.migrations {
+            let mut line = format!(
+                "  {} {}  {}",
+                if migration.not_applicable { "○" } else { "+" },
+                migration.id,
+                migration.applied_at.format("%H:%M:%S")
+            );
+            if let Some(duration) = migration.duration {
+                line.push_str(&format!("  ({})", format_duration(duration)));
+            }
+            if migration.not_applicable {
+                line.push_str("  (not applicable)");
+            } else if status.is_baselined(migration) {
+                line.push_str("  (baseline)");
+            }
+            println!("{}", line);
+        }
+    }
+
+    Ok(())
+}
+
+impl LogOptions {
+    fn matches(&self, migration: &AppliedMigration) -> bool {
+        self.since.is_none_or(|since| migration.applied_at >= since)
+            && self.until.is_none_or(|until| migration.applied_at < until)
+            && self
+                .grep
+                .as_ref()
+                .is_none_or(|re| re.is_match(&migration.id))
+    }
+}
+
+/// Group records into runs. Records written before runs were tracked have no run ID; each
+/// consecutive stretch of them is shown as one group.
+fn group_runs<'a>(migrations: &[&'a AppliedMigration]) -> Vec<Run<'a>> {
+    let mut runs: Vec<Run<'a>> = Vec::new();
+    for migration in migrations {
+        let run_id = migration.run_id.as_deref();
+        match runs.last_mut() {
+            Some(run) if run.run_id == run_id => run.migrations.push(migration),
+            _ => runs.push(Run {
+                run_id,
+                migrations: vec![migration],
+            }),
+        }
+    }
+    runs
+}
+
+fn run_header(run: &Run) -> String {
+    let first = run.migrations[0];
+    let mut header = format!(
+        "{}  {}",
+        first.applied_at.format("%Y-%m-%d %H:%M:%S"),
+        match run.run_id {
+            Some(id) => format!("run {}", id),
+            None => "(run not recorded)".to_string(),
+        }
+    );
+    match (&first.user, &first.host) {
+        (Some(user), Some(host)) => header.push_str(&format!("  {}@{}", user, host)),
+        (Some(user), None) => header.push_str(&format!("  {}", user)),
+        (None, Some(host)) => header.push_str(&format!("  @{}", host)),
+        (None, None) => {}
+    }
+    if let Some(version) = &first.migrate_version {
+        header.push_str(&format!("  migrate {}", version));
+    }
+    if let Some(commit) = &first.commit {
+        header.push_str(&format!("  commit {}", &commit[..commit.len().min(12)]));
+    }
+    header
+}
+
+/// Parse `--since`: a date (start of day, UTC) or a date and time
+pub fn parse_since(s: &str) -> Result<DateTime<Utc>> {
+    parse_time(s, false)
+}
+
+/// Parse `--until`: a date (through the end of that day, UTC) or a date and time
+pub fn parse_until(s: &str) -> Result<DateTime<Utc>> {
+    parse_time(s, true)
+}
+
+fn parse_time(s: &str, end_of_day: bool) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(s, format) {
+            return Ok(time.and_utc());
+        }
+    }
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").with_context(|| {
+        format!(
+            "Invalid time '{}' (expected YYYY-MM-DD, YYYY-MM-DD HH:MM[:SS] or RFC 3339)",
+            s
+        )
+    })?;
+    let start = date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+    Ok(if end_of_day {
+        start + Duration::days(1)
+    } else {
+        start
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applied(id: &str, at: &str, run_id: Option<&str>) -> AppliedMigration {
+        AppliedMigration {
+            run_id: run_id.map(str::to_string),
+            ..AppliedMigration::new(id, parse_since(at).unwrap())
+        }
+    }
+
+    #[test]
+    fn test_group_runs() {
+        let history = [
+            applied("1f700-old", "2026-01-01 10:00", None),
+            applied("1f701-older", "2026-01-01 10:05", None),
+            applied("1f710-a", "2026-02-01 09:00", Some("run-1")),
+            applied("1f711-b", "2026-02-01 09:00", Some("run-1")),
+            applied("1f720-c", "2026-03-01 12:00", Some("run-2")),
+        ];
+        let refs: Vec<&AppliedMigration> = history.iter().collect();
+        let runs = group_runs(&refs);
+
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[0].run_id, None);
+        assert_eq!(runs[0].migrations.len(), 2);
+        assert_eq!(runs[1].run_id, Some("run-1"));
+        assert_eq!(runs[1].migrations.len(), 2);
+        assert_eq!(runs[2].migrations[0].id, "1f720-c");
+        assert_eq!(
+            run_header(&runs[1]),
+            "2026-02-01 09:00:00  run run-1".to_string()
+        );
+    }
+
+    #[test]
+    fn test_filters() {
+        let options = LogOptions {
+            since: Some(parse_since("2026-02-01").unwrap()),
+            until: Some(parse_until("2026-02-01").unwrap()),
+            grep: Some(Regex::new("config").unwrap()),
+        };
+        assert!(options.matches(&applied("1f710-config", "2026-02-01 23:59", None)));
+        assert!(!options.matches(&applied("1f710-config", "2026-02-02 00:00", None)));
+        assert!(!options.matches(&applied("1f710-config", "2026-01-31 23:59", None)));
+        assert!(!options.matches(&applied("1f710-other", "2026-02-01 12:00", None)));
+
+        assert_eq!(
+            parse_since("2026-02-01T10:00:00+02:00")
+                .unwrap()
+                .to_rfc3339(),
+            "2026-02-01T08:00:00+00:00"
+        );
+        assert!(parse_since("yesterday").is_err());
+    }
+}
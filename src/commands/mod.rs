@@ -1,4 +1,12 @@
 pub mod baseline;
+pub mod check;
 pub mod create;
+pub mod helper;
+pub mod log;
+pub mod record;
+pub mod show;
 pub mod status;
+pub mod targets;
 pub mod up;
+pub mod verify;
+pub mod workspaces;
@@ -0,0 +1,198 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::commands::create::create_migration;
+use crate::config::PROJECT_STATE_DIR;
+use crate::loader::extract_id;
+use crate::templates::PATCH_TEMPLATE;
+
+/// Name of the patch file stored in a recorded migration's asset directory
+pub const PATCH_FILE: &str = "changes.patch";
+
+/// Record a new migration from manual edits to the project, or from an existing commit
+pub fn run(
+    project_root: &Path,
+    migrations_dir: &Path,
+    name: &str,
+    from_git: Option<&str>,
+    description: Option<&str>,
+) -> Result<()> {
+    let project_root = if project_root.is_absolute() {
+        project_root.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(project_root)
+    };
+
+    let migrations_path = if migrations_dir.is_absolute() {
+        migrations_dir.to_path_buf()
+    } else {
+        project_root.join(migrations_dir)
+    };
+
+    if git(&project_root, &["rev-parse", "--is-inside-work-tree"], None).is_err() {
+        bail!(
+            "Recording requires a git working tree, but {} is not inside one",
+            project_root.display()
+        );
+    }
+
+    // Never record changes to the migrations directory itself (new migrations, history)
+    // or to project-local state
+    let mut excluded: Vec<String> = migrations_path
+        .strip_prefix(&project_root)
+        .ok()
+        .filter(|p| !p.as_os_str().is_empty())
+        .map(|p| format!(":(exclude){}", p.display()))
+        .into_iter()
+        .collect();
+    excluded.push(format!(":(exclude){}", PROJECT_STATE_DIR));
+
+    let patch = match from_git {
+        Some(rev) => diff_revision(&project_root, rev, &excluded)?,
+        None => record_interactively(&project_root, &excluded)?,
+    };
+
+    let changed_files = patch
+        .split(|&b| b == b'\n')
+        .filter(|line| line.starts_with(b"diff --git "))
+        .count();
+    if changed_files == 0 {
+        bail!("No changes recorded; no migration was created");
+    }
+
+    let description = match (description, from_git) {
+        (Some(d), _) => d.to_string(),
+        (None, Some(rev)) => format!("Apply changes from {}", rev),
+        (None, None) => "Apply recorded changes".to_string(),
+    };
+    let content = PATCH_TEMPLATE
+        .content
+        .replace("{{DESCRIPTION}}", &description);
+
+    let file_path = create_migration(&migrations_path, name, PATCH_TEMPLATE.extension, &content)?;
+
+    let filename = file_path
+        .file_name()
+        .and_then(|f| f.to_str())
+        .context("Invalid migration filename")?;
+    let asset_dir = migrations_path.join(extract_id(filename));
+    fs::create_dir_all(&asset_dir)
+        .with_context(|| format!("Failed to create asset directory: {}", asset_dir.display()))?;
+    let patch_path = asset_dir.join(PATCH_FILE);
+    fs::write(&patch_path, &patch)
+        .with_context(|| format!("Failed to write patch: {}", patch_path.display()))?;
+
+    println!("Created migration: {}", file_path.display());
+    println!(
+        "Recorded {} changed file(s) in {}",
+        changed_files,
+        patch_path.display()
+    );
+
+    Ok(())
+}
+
+/// Produce a patch for the changes introduced by a commit (or a `a..b` range).
+fn diff_revision(project_root: &Path, rev: &str, excluded: &[String]) -> Result<Vec<u8>> {
+    let range = if rev.contains("..") {
+        rev.to_string()
+    } else {
+        format!("{}^!", rev)
+    };
+
+    let mut args = vec!["diff", "--binary", "--relative", range.as_str(), "--", "."];
+    args.extend(excluded.iter().map(String::as_str));
+    git(project_root, &args, None)
+        .with_context(|| format!("Failed to read changes from git revision '{}'", rev))
+}
+
+/// Snapshot the tree, wait for the developer to make their edits, then diff against the snapshot.
+fn record_interactively(project_root: &Path, excluded: &[String]) -> Result<Vec<u8>> {
+    let index = SnapshotIndex::create(project_root)?;
+    let before = index.snapshot(project_root)?;
+
+    println!("Snapshot taken of {}", project_root.display());
+    print!("Make your changes, then press Enter to record them (Ctrl-C to abort)... ");
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .context("Failed to read from stdin")?;
+
+    let after = index.snapshot(project_root)?;
+
+    let mut args = vec![
+        "diff",
+        "--binary",
+        "--relative",
+        before.as_str(),
+        after.as_str(),
+        "--",
+        ".",
+    ];
+    args.extend(excluded.iter().map(String::as_str));
+    git(project_root, &args, None)
+}
+
+/// A private git index used to snapshot the working tree without touching the real index.
+struct SnapshotIndex {
+    path: PathBuf,
+}
+
+impl SnapshotIndex {
+    fn create(project_root: &Path) -> Result<Self> {
+        let resolve = |arg: &str| -> Result<PathBuf> {
+            let out = git(project_root, &["rev-parse", "--git-path", arg], None)?;
+            Ok(project_root.join(String::from_utf8_lossy(&out).trim()))
+        };
+
+        let real_index = resolve("index")?;
+        let path = resolve(&format!("migrate-record-index-{}", std::process::id()))?;
+
+        // Start from the real index so unchanged files hash quickly
+        if real_index.exists() {
+            fs::copy(&real_index, &path).context("Failed to copy git index")?;
+        }
+
+        Ok(Self { path })
+    }
+
+    /// Stage every file under the project root into the private index and return its tree id.
+    fn snapshot(&self, project_root: &Path) -> Result<String> {
+        git(project_root, &["add", "-A", "--", "."], Some(&self.path))?;
+        let tree = git(project_root, &["write-tree"], Some(&self.path))?;
+        Ok(String::from_utf8_lossy(&tree).trim().to_string())
+    }
+}
+
+impl Drop for SnapshotIndex {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Run a git command in the project root and return its stdout.
+fn git(project_root: &Path, args: &[&str], index: Option<&Path>) -> Result<Vec<u8>> {
+    let mut command = Command::new("git");
+    command.args(args).current_dir(project_root);
+    if let Some(index) = index {
+        command.env("GIT_INDEX_FILE", index);
+    }
+
+    let output = command
+        .output()
+        .context("Failed to run git (is it installed?)")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(output.stdout)
+}
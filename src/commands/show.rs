@@ -0,0 +1,257 @@
+use anyhow::{bail, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::baseline::version_lte;
+use crate::header::format_timeout;
+use crate::loader::extract_version;
+use crate::migrator::Status;
+use crate::reporter::format_duration;
+use crate::version::version_time;
+use crate::{AppliedMigration, Header, Migration, Migrator};
+
+/// Where a migration stands relative to the project's state
+#[derive(Debug, PartialEq, Eq)]
+enum MigrationState {
+    Pending,
+    Applied,
+    /// Recorded in history without running to completion (see `RunIf`)
+    NotApplicable,
+    /// At or before the baseline version
+    Baselined,
+    /// Recorded in history but no longer in the migrations directory
+    Missing,
+}
+
+/// Print everything known about one migration. `id` may be a full ID or just its version.
+pub fn run(migrator: &Migrator, id: &str) -> Result<()> {
+    let status = migrator.status()?;
+    let matches =
+        |candidate: &str| candidate == id || candidate.split_once('-').map(|(v, _)| v) == Some(id);
+    let migration = status.available.iter().find(|m| matches(&m.id));
+    let applied = status.applied.iter().rev().find(|a| match migration {
+        Some(m) => a.id == m.id,
+        None => matches(&a.id),
+    });
+    let Some(migration_id) = migration.map(|m| &m.id).or(applied.map(|a| &a.id)) else {
+        bail!("Unknown migration: {}", id);
+    };
+
+    println!("{}", migration_id);
+    let state = migration_state(&status, migration, applied);
+    println!(
+        "  State:       {}",
+        match state {
+            MigrationState::Pending => "pending",
+            MigrationState::Applied => "applied",
+            MigrationState::NotApplicable => "not applicable",
+            MigrationState::Baselined => "baselined",
+            MigrationState::Missing => "missing on disk",
+        }
+    );
+    match migration {
+        Some(m) if m.is_function() => println!("  File:        (function migration)"),
+        Some(m) => println!(
+            "  File:        {}",
+            m.file_path
+                .strip_prefix(migrator.project_root())
+                .unwrap_or(&m.file_path)
+                .display()
+        ),
+        None => println!("  File:        (not found)"),
+    }
+    if let Some(time) = extract_version(migration_id).and_then(|v| version_time(&v)) {
+        println!("  Created:     {}", time.format("%Y-%m-%d %H:%M UTC"));
+    }
+    if let Some(migration) = migration {
+        print_header(&migration.header);
+    }
+    if let Some(applied) = applied {
+        print_applied(applied);
+    }
+
+    let asset_dir = migrator.migrations_path().join(migration_id);
+    let assets = list_assets(&asset_dir)?;
+    if !assets.is_empty() {
+        println!("  Assets ({}):", assets.len());
+        for asset in assets {
+            println!("    {}", asset.display());
+        }
+    }
+
+    Ok(())
+}
+
+fn migration_state(
+    status: &Status,
+    migration: Option<&Migration>,
+    applied: Option<&AppliedMigration>,
+) -> MigrationState {
+    match (migration, applied) {
+        (None, _) => MigrationState::Missing,
+        (Some(_), Some(a)) if a.not_applicable => MigrationState::NotApplicable,
+        (Some(_), Some(a)) if status.is_baselined(a) => MigrationState::Baselined,
+        (Some(_), Some(_)) => MigrationState::Applied,
+        (Some(m), None) => {
+            if status
+                .baseline
+                .as_ref()
+                .is_some_and(|b| version_lte(&m.version, &b.version))
+            {
+                MigrationState::Baselined
+            } else {
+                MigrationState::Pending
+            }
+        }
+    }
+}
+
+fn print_header(header: &Header) {
+    if let Some(description) = &header.description {
+        println!("  Description: {}", description);
+    }
+    if !header.tags.is_empty() {
+        println!("  Tags:        {}", header.tags.join(", "));
+    }
+    if !header.requires.is_empty() {
+        println!("  Requires:    {}", header.requires.join(", "));
+    }
+    if let Some(timeout) = header.timeout {
+        println!("  Timeout:     {}", format_timeout(timeout));
+    }
+    if !header.depends_on.is_empty() {
+        println!("  Depends on:  {}", header.depends_on.join(", "));
+    }
+    for (name, value) in &header.env {
+        println!("  Env:         {}={}", name, value);
+    }
+    for condition in &header.run_if {
+        println!("  Run if:      {}", condition);
+    }
+    for command in &header.verify {
+        println!("  Verify:      {}", command);
+    }
+    for param in &header.params {
+        println!("  Param:       {}", param);
+    }
+}
+
+fn print_applied(applied: &AppliedMigration) {
+    println!(
+        "  Applied:     {}",
+        applied.applied_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    if let Some(duration) = applied.duration {
+        println!("  Duration:    {}", format_duration(duration));
+    }
+    if let Some(exit_code) = applied.exit_code {
+        println!("  Exit code:   {}", exit_code);
+    }
+    if let Some(run_id) = &applied.run_id {
+        println!("  Run:         {}", run_id);
+    }
+    match (&applied.user, &applied.host) {
+        (Some(user), Some(host)) => println!("  By:          {}@{}", user, host),
+        (Some(user), None) => println!("  By:          {}", user),
+        (None, Some(host)) => println!("  Host:        {}", host),
+        (None, None) => {}
+    }
+    if let Some(version) = &applied.migrate_version {
+        println!("  migrate:     {}", version);
+    }
+    if let Some(commit) = &applied.commit {
+        println!("  Commit:      {}", commit);
+    }
+    for (key, value) in &applied.outputs {
+        println!("  Output:      {}={}", key, value);
+    }
+}
+
+/// Files in an asset directory, relative to it and sorted
+fn list_assets(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if dir.is_dir() {
+        collect(dir, dir, &mut files)?;
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn collect(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect(root, &path, files)?;
+        } else {
+            files.push(path.strip_prefix(root).unwrap_or(&path).to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Baseline;
+    use chrono::Utc;
+
+    fn status(baseline: Option<&str>) -> Status {
+        Status {
+            available: Vec::new(),
+            applied: Vec::new(),
+            baseline: baseline.map(|version| Baseline {
+                version: version.to_string(),
+                created: Utc::now(),
+                summary: None,
+            }),
+            state: String::new(),
+            pending: Vec::new(),
+            filtered: Vec::new(),
+            current_version: None,
+            target_version: None,
+        }
+    }
+
+    #[test]
+    fn test_migration_state() {
+        let migration = Migration {
+            id: "1f72f-init".to_string(),
+            version: "1f72f".to_string(),
+            file_path: PathBuf::from("migrations/1f72f-init.sh"),
+            ..Default::default()
+        };
+        let applied = AppliedMigration::new("1f72f-init", Utc::now());
+
+        let plain = status(None);
+        assert_eq!(
+            migration_state(&plain, Some(&migration), None),
+            MigrationState::Pending
+        );
+        assert_eq!(
+            migration_state(&plain, Some(&migration), Some(&applied)),
+            MigrationState::Applied
+        );
+        assert_eq!(
+            migration_state(&plain, None, Some(&applied)),
+            MigrationState::Missing
+        );
+        let skipped = AppliedMigration {
+            not_applicable: true,
+            ..applied.clone()
+        };
+        assert_eq!(
+            migration_state(&plain, Some(&migration), Some(&skipped)),
+            MigrationState::NotApplicable
+        );
+
+        let baselined = status(Some("1f730"));
+        assert_eq!(
+            migration_state(&baselined, Some(&migration), None),
+            MigrationState::Baselined
+        );
+        assert_eq!(
+            migration_state(&baselined, Some(&migration), Some(&applied)),
+            MigrationState::Baselined
+        );
+    }
+}
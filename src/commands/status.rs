@@ -1,16 +1,10 @@
 use anyhow::Result;
-use std::path::Path;
 
-use crate::loader::discover_migrations;
-use crate::state::{get_current_version, get_pending, get_target_version, read_history};
+use crate::{Header, Migrator};
 
 /// Show the status of all migrations
-pub fn run(project_root: &Path, migrations_dir: &Path) -> Result<()> {
-    let migrations_path = if migrations_dir.is_absolute() {
-        migrations_dir.to_path_buf()
-    } else {
-        project_root.join(migrations_dir)
-    };
+pub fn run(migrator: &Migrator) -> Result<()> {
+    let migrations_path = migrator.migrations_path();
 
     if !migrations_path.exists() {
         println!(
@@ -20,24 +14,21 @@ pub fn run(project_root: &Path, migrations_dir: &Path) -> Result<()> {
         return Ok(());
     }
 
-    let available = discover_migrations(&migrations_path)?;
-    let state = read_history(&migrations_path)?;
-    let pending = get_pending(&available, &state);
+    let status = migrator.status()?;
 
-    if available.is_empty() && state.baseline.is_none() {
+    if status.available.is_empty() && status.baseline.is_none() {
         println!("No migrations found in: {}", migrations_path.display());
         return Ok(());
     }
 
-    let current_version = get_current_version(&available, &state.applied);
-    let target_version = get_target_version(&available);
-
     println!("Migration Status");
     println!("================");
     println!();
+    println!("State: {}", status.state);
+    println!();
 
     // Show baseline info if present
-    if let Some(ref b) = state.baseline {
+    if let Some(ref b) = status.baseline {
         println!("Baseline: {} ({})", b.version, b.created.format("%Y-%m-%d"));
         if let Some(ref summary) = b.summary {
             for line in summary.lines() {
@@ -48,11 +39,12 @@ pub fn run(project_root: &Path, migrations_dir: &Path) -> Result<()> {
     }
 
     // Show version summary line
-    match (&current_version, &target_version) {
-        (None, Some(target)) if state.baseline.is_some() => {
+    let pending = &status.pending;
+    match (&status.current_version, &status.target_version) {
+        (None, Some(target)) if status.baseline.is_some() => {
             println!(
                 "Version: {} -> {} ({} pending)",
-                state.baseline.as_ref().unwrap().version,
+                status.baseline.as_ref().unwrap().version,
                 target,
                 pending.len()
             );
@@ -71,10 +63,10 @@ pub fn run(project_root: &Path, migrations_dir: &Path) -> Result<()> {
                 pending.len()
             );
         }
-        (None, None) if state.baseline.is_some() => {
+        (None, None) if status.baseline.is_some() => {
             println!(
                 "Version: {} (up to date, baselined)",
-                state.baseline.as_ref().unwrap().version
+                status.baseline.as_ref().unwrap().version
             );
         }
         _ => {}
@@ -82,28 +74,29 @@ pub fn run(project_root: &Path, migrations_dir: &Path) -> Result<()> {
     println!();
 
     // Show applied migrations
-    if !state.applied.is_empty() {
-        println!("Applied ({}):", state.applied.len());
-        for migration in &state.applied {
-            // Check if this migration is at or before baseline
-            let is_baselined = state
-                .baseline
-                .as_ref()
-                .is_some_and(|b| extract_version(&migration.id) <= Some(b.version.clone()));
-
-            if is_baselined {
-                println!(
-                    "  + {}  {}  (baseline)",
-                    migration.id,
-                    migration.applied_at.format("%Y-%m-%d %H:%M:%S")
-                );
-            } else {
-                println!(
-                    "  + {}  {}",
-                    migration.id,
-                    migration.applied_at.format("%Y-%m-%d %H:%M:%S")
-                );
-            }
+    if !status.applied.is_empty() {
+        println!("Applied ({}):", status.applied.len());
+        for migration in &status.applied {
+            let header = status
+                .available
+                .iter()
+                .find(|m| m.id == migration.id)
+                .map(|m| &m.header);
+            // Not-applicable migrations are in history but had no effect
+            println!(
+                "  {} {}  {}{}{}",
+                if migration.not_applicable { "○" } else { "+" },
+                migration.id,
+                migration.applied_at.format("%Y-%m-%d %H:%M:%S"),
+                if migration.not_applicable {
+                    "  (not applicable)"
+                } else if status.is_baselined(migration) {
+                    "  (baseline)"
+                } else {
+                    ""
+                },
+                header.map(summary).unwrap_or_default()
+            );
         }
         println!();
     }
@@ -111,19 +104,33 @@ pub fn run(project_root: &Path, migrations_dir: &Path) -> Result<()> {
     // Show pending migrations
     if !pending.is_empty() {
         println!("Pending ({}):", pending.len());
-        for migration in &pending {
-            println!("  - {}", migration.id);
+        for migration in pending {
+            println!("  - {}{}", migration.id, summary(&migration.header));
+        }
+    }
+
+    // Show migrations left out by --tag / --exclude-tag
+    if !status.filtered.is_empty() {
+        if !pending.is_empty() {
+            println!();
+        }
+        println!("Filtered out by tags ({}):", status.filtered.len());
+        for migration in &status.filtered {
+            println!("  · {}{}", migration.id, summary(&migration.header));
         }
     }
 
     Ok(())
 }
 
-/// Extract version from a migration ID (e.g., "1f72f-init" -> "1f72f")
-fn extract_version(id: &str) -> Option<String> {
-    if id.len() >= 5 && id.chars().nth(5) == Some('-') {
-        Some(id[..5].to_string())
-    } else {
-        None
+/// Description and tags from a migration's header, to follow its ID
+fn summary(header: &Header) -> String {
+    let mut summary = String::new();
+    if let Some(description) = &header.description {
+        summary.push_str(&format!("  {}", description));
+    }
+    if !header.tags.is_empty() {
+        summary.push_str(&format!("  [{}]", header.tags.join(", ")));
     }
+    summary
 }
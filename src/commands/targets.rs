@@ -0,0 +1,393 @@
+use anyhow::{bail, Context, Result};
+use glob::glob;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::commands::helper::parse_value;
+use crate::config::{Config, Location, CONFIG_FILE};
+use crate::migrator::Progress;
+use crate::{Migrator, Stream, TagFilter};
+
+/// Options for applying migrations to several target roots
+pub struct TargetOptions<'a> {
+    pub dry_run: bool,
+    /// Maximum number of targets migrated at the same time
+    pub parallel: usize,
+    /// Stop starting new targets after the first failure
+    pub fail_fast: bool,
+    pub variables: &'a [(String, String)],
+    /// Values for migration parameters; targets are never prompted
+    pub params: &'a [(String, String)],
+    /// State location for every target; by default each target keeps state in `.migrate/`
+    pub state_location: Option<Location>,
+    pub tags: TagFilter,
+}
+
+/// Outcome of one migration for one target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cell {
+    Applied,
+    Previewed,
+    Failed,
+    /// `RunIf` did not hold or the script reported it was not applicable
+    NotApplicable,
+    /// Not run because an earlier migration for the same target failed
+    Skipped,
+    /// Already applied (or baselined) for this target
+    UpToDate,
+    /// Left out by `--tag`/`--exclude-tag`; still to be applied
+    Excluded,
+}
+
+impl Cell {
+    fn symbol(self) -> &'static str {
+        match self {
+            Cell::Applied => "✓",
+            Cell::Previewed => "~",
+            Cell::Failed => "✗",
+            Cell::NotApplicable => "○",
+            Cell::Skipped => "·",
+            Cell::UpToDate => "-",
+            Cell::Excluded => "/",
+        }
+    }
+}
+
+struct TargetResult {
+    label: String,
+    /// Per-migration outcomes; empty if the target could not be migrated at all
+    cells: Vec<(String, Cell)>,
+    error: Option<String>,
+    started: bool,
+}
+
+/// Resolve `--targets`: a file listing one directory per line (`#` comments allowed), or
+/// a glob matching target directories. Relative paths resolve against `base`.
+pub fn resolve_targets(spec: &str, base: &Path) -> Result<Vec<PathBuf>> {
+    let path = base.join(spec);
+    if path.is_file() {
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read targets file: {}", path.display()))?;
+        let dir = path.parent().unwrap_or(base);
+        let targets: Vec<PathBuf> = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| dir.join(line))
+            .collect();
+        if let Some(missing) = targets.iter().find(|t| !t.is_dir()) {
+            bail!("Target is not a directory: {}", missing.display());
+        }
+        return Ok(targets);
+    }
+
+    let pattern = path.to_str().context("Invalid targets pattern")?;
+    let mut targets: Vec<PathBuf> = glob(pattern)
+        .with_context(|| format!("Invalid targets pattern: {}", spec))?
+        .filter_map(|entry| entry.ok())
+        .filter(|p| p.is_dir())
+        .collect();
+    targets.sort();
+    if targets.is_empty() {
+        bail!("No target directories match '{}'", spec);
+    }
+    Ok(targets)
+}
+
+/// Apply the migrations in `migrations_dir` to every target root, each with its own state
+pub fn run(
+    migrations_dir: &Path,
+    targets: &[PathBuf],
+    base: &Path,
+    options: &TargetOptions,
+) -> Result<()> {
+    if !migrations_dir.exists() {
+        println!(
+            "No migrations directory found at: {}",
+            migrations_dir.display()
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{} migrations to {} target(s)...",
+        if options.dry_run {
+            "Would apply"
+        } else {
+            "Applying"
+        },
+        targets.len()
+    );
+    println!();
+
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let results: Mutex<Vec<Option<TargetResult>>> =
+        Mutex::new(targets.iter().map(|_| None).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..options.parallel.clamp(1, targets.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(target) = targets.get(index) else {
+                    break;
+                };
+                let label = target
+                    .strip_prefix(base)
+                    .unwrap_or(target)
+                    .display()
+                    .to_string();
+
+                let result = if stop.load(Ordering::SeqCst) {
+                    TargetResult {
+                        label,
+                        cells: Vec::new(),
+                        error: None,
+                        started: false,
+                    }
+                } else {
+                    let result = migrate_target(target, label, migrations_dir, options);
+                    if result.error.is_some() && options.fail_fast {
+                        stop.store(true, Ordering::SeqCst);
+                    }
+                    result
+                };
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    let results: Vec<TargetResult> = results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect();
+    print_matrix(&results);
+
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    let not_started = results.iter().filter(|r| !r.started).count();
+    println!();
+    println!(
+        "{} target(s) succeeded, {} failed{}",
+        results.len() - failed - not_started,
+        failed,
+        if not_started > 0 {
+            format!(", {} not started (--fail-fast)", not_started)
+        } else {
+            String::new()
+        }
+    );
+
+    if failed > 0 {
+        bail!(
+            "Migrations failed for {} of {} target(s)",
+            failed,
+            results.len()
+        );
+    }
+    Ok(())
+}
+
+fn migrate_target(
+    target: &Path,
+    label: String,
+    migrations_dir: &Path,
+    options: &TargetOptions,
+) -> TargetResult {
+    let prefix = label.clone();
+    let mut migrator = Migrator::new(target)
+        .migrations_dir(migrations_dir)
+        .dry_run(options.dry_run)
+        .tag_filter(options.tags.clone())
+        .on_progress(move |progress| match progress {
+            Progress::Started(migration) => println!("[{}] → {}", prefix, migration.id),
+            Progress::Output {
+                line,
+                stream: Stream::Stdout,
+                ..
+            } => println!("[{}]   {}", prefix, line),
+            Progress::Output { line, .. } => eprintln!("[{}]   {}", prefix, line),
+            Progress::Message { message, .. } => println!("[{}]   {}", prefix, message),
+            Progress::Failed { result, .. } => {
+                println!("[{}]   ✗ failed (exit code {})", prefix, result.exit_code)
+            }
+            Progress::NotApplicable { reason, .. } => {
+                println!("[{}]   ○ not applicable: {}", prefix, reason)
+            }
+            Progress::Completed { .. } | Progress::Skipped { .. } => {}
+        });
+    let mut result = TargetResult {
+        label,
+        cells: Vec::new(),
+        error: None,
+        started: true,
+    };
+
+    match options.state_location {
+        Some(location) => migrator = migrator.state_location(location),
+        // Keep per-target state unless the target configures its own
+        None if !target.join(CONFIG_FILE).exists() => {
+            migrator = migrator.state_location(Location::Project)
+        }
+        None => {
+            if let Err(e) = check_own_state(target, migrations_dir) {
+                result.error = Some(format!("{:#}", e));
+                return result;
+            }
+        }
+    }
+    for (name, value) in options.variables {
+        migrator = migrator.variable(name.as_str(), parse_value(value));
+    }
+    for (name, value) in options.params {
+        migrator = migrator.param(name.as_str(), value.as_str());
+    }
+
+    let outcome = migrator.status().and_then(|status| {
+        let report = migrator.apply()?;
+        Ok((status, report))
+    });
+    let (status, report) = match outcome {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            result.error = Some(format!("{:#}", e));
+            return result;
+        }
+    };
+
+    let failed_id = report.failed.as_ref().map(|f| f.migration.id.as_str());
+    for migration in &status.available {
+        let cell = if status.filtered.iter().any(|f| f.id == migration.id) {
+            Cell::Excluded
+        } else if !status.pending.iter().any(|p| p.id == migration.id) {
+            Cell::UpToDate
+        } else if Some(migration.id.as_str()) == failed_id {
+            Cell::Failed
+        } else if let Some(outcome) = report
+            .outcomes
+            .iter()
+            .find(|o| o.migration.id == migration.id)
+        {
+            if outcome.not_applicable {
+                Cell::NotApplicable
+            } else if options.dry_run {
+                Cell::Previewed
+            } else {
+                Cell::Applied
+            }
+        } else {
+            Cell::Skipped
+        };
+        result.cells.push((migration.id.clone(), cell));
+    }
+
+    if let Some(failed) = &report.failed {
+        result.error = Some(
+            failed
+                .result
+                .as_ref()
+                .and_then(|r| r.error.clone())
+                .unwrap_or_else(|| format!("Migration {} failed", failed.migration.id)),
+        );
+    }
+    result
+}
+
+/// Fail if a target's `migrate.toml` keeps state next to migrations outside the target,
+/// where every target would share it
+fn check_own_state(target: &Path, migrations_dir: &Path) -> Result<()> {
+    let state = Config::load(target)?.state;
+    if state.path.is_none()
+        && state.location == Location::Migrations
+        && !migrations_dir.starts_with(target)
+    {
+        bail!(
+            "{} keeps state in the shared migrations directory; set [state] location to \
+             \"project\" or \"user\" to apply migrations to it with --targets",
+            target.join(CONFIG_FILE).display()
+        );
+    }
+    Ok(())
+}
+
+fn print_matrix(results: &[TargetResult]) {
+    let mut migrations: Vec<&str> = Vec::new();
+    for result in results {
+        for (id, _) in &result.cells {
+            if !migrations.contains(&id.as_str()) {
+                migrations.push(id);
+            }
+        }
+    }
+    migrations.sort();
+
+    let label_width = results
+        .iter()
+        .map(|r| r.label.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Target".len());
+
+    println!();
+    let mut header = format!("{:<width$}", "Target", width = label_width);
+    for id in &migrations {
+        header.push_str(&format!("  {}", id));
+    }
+    println!("{}", header);
+
+    for result in results {
+        let mut row = format!("{:<width$}", result.label, width = label_width);
+        for id in &migrations {
+            let symbol = result
+                .cells
+                .iter()
+                .find(|(cell_id, _)| cell_id == id)
+                .map_or(" ", |(_, cell)| cell.symbol());
+            row.push_str(&format!("  {:<width$}", symbol, width = id.chars().count()));
+        }
+        if !result.started {
+            row.push_str("  (not started)");
+        } else if let Some(error) = &result.error {
+            row.push_str(&format!("  {}", error));
+        }
+        println!("{}", row.trim_end());
+    }
+    println!();
+    println!(
+        "✓ applied  ~ previewed  ✗ failed  ○ not applicable  · skipped after failure  \
+         - already applied  / excluded by tag"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resolve_targets_from_glob_and_file() {
+        let dir = TempDir::new().unwrap();
+        for name in ["apps/b", "apps/a", "other"] {
+            fs::create_dir_all(dir.path().join(name)).unwrap();
+        }
+        fs::write(dir.path().join("apps/README.md"), "").unwrap();
+
+        let targets = resolve_targets("apps/*", dir.path()).unwrap();
+        assert_eq!(
+            targets,
+            vec![dir.path().join("apps/a"), dir.path().join("apps/b")]
+        );
+
+        fs::write(dir.path().join("targets.txt"), "# apps\napps/a\n\nother\n").unwrap();
+        let targets = resolve_targets("targets.txt", dir.path()).unwrap();
+        assert_eq!(
+            targets,
+            vec![dir.path().join("apps/a"), dir.path().join("other")]
+        );
+
+        assert!(resolve_targets("missing/*", dir.path()).is_err());
+    }
+}
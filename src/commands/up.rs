@@ -1,154 +1,185 @@
-use anyhow::Result;
-use chrono::Utc;
-use std::path::Path;
-
-use crate::baseline::{delete_baselined_migrations, DeletedItem};
-use crate::executor::execute;
-use crate::loader::discover_migrations;
-use crate::state::{append_baseline, append_history, get_pending, read_history, Baseline};
-use crate::ExecutionContext;
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::rc::Rc;
+
+use crate::baseline::version_lte;
+use crate::commands::helper::parse_value;
+use crate::reporter::{Event, Format, MigrationMessage, Reporter, TouchedFile};
+use crate::run_report::{suite_name, write_report, ReportSpec, RunRecorder, Suite};
+use crate::{MigrateError, Migrator, Param};
+
+/// Options for `migrate up`
+pub struct UpOptions<'a> {
+    pub dry_run: bool,
+    /// Create a baseline at the final version after applying
+    pub baseline: bool,
+    /// Keep migration files when creating a baseline
+    pub keep: bool,
+    pub variables: &'a [(String, String)],
+    /// Values for migration parameters; others are prompted for when stdin is a terminal
+    pub params: &'a [(String, String)],
+    pub format: Format,
+    /// Report files to write when the run ends
+    pub reports: &'a [ReportSpec],
+    /// When set, the run's test cases are added here instead of writing `reports`, so
+    /// that runs in several workspaces can share one report
+    pub suites: Option<&'a RefCell<Vec<Suite>>>,
+    /// Migrations to run at once
+    pub jobs: usize,
+}
 
 /// Apply all pending migrations
-pub fn run(
-    project_root: &Path,
-    migrations_dir: &Path,
-    dry_run: bool,
-    create_baseline: bool,
-    keep: bool,
-) -> Result<()> {
-    let project_root = if project_root.is_absolute() {
-        project_root.to_path_buf()
-    } else {
-        std::env::current_dir()?.join(project_root)
-    };
-
-    let migrations_path = if migrations_dir.is_absolute() {
-        migrations_dir.to_path_buf()
-    } else {
-        project_root.join(migrations_dir)
-    };
-
-    if !migrations_path.exists() {
-        println!(
-            "No migrations directory found at: {}",
-            migrations_path.display()
-        );
-        return Ok(());
+pub fn run(migrator: Migrator, options: &UpOptions) -> Result<()> {
+    let reporter: Rc<dyn Reporter> = Rc::from(options.format.reporter());
+    let recorder = Rc::new(RunRecorder::default());
+    let progress_reporter = Rc::clone(&reporter);
+    let progress_recorder = Rc::clone(&recorder);
+    let mut migrator = migrator
+        .dry_run(options.dry_run)
+        .baseline_on_success(options.baseline)
+        .keep_files(options.keep)
+        .jobs(options.jobs)
+        .on_progress(move |progress| {
+            let event = Event::from(progress);
+            progress_reporter.report(&event);
+            progress_recorder.report(&event);
+        });
+    for (name, value) in options.variables {
+        migrator = migrator.variable(name.as_str(), parse_value(value));
+    }
+    for (name, value) in options.params {
+        migrator = migrator.param(name.as_str(), value.as_str());
+    }
+    if io::stdin().is_terminal() {
+        migrator = migrator.prompt_params(prompt);
     }
 
-    let available = discover_migrations(&migrations_path)?;
-    let state = read_history(&migrations_path)?;
-    let pending = get_pending(&available, &state);
-
-    if pending.is_empty() {
-        println!("No pending migrations.");
+    if !migrator.migrations_path().exists() {
+        reporter.report(&Event::Notice {
+            message: &format!(
+                "No migrations directory found at: {}",
+                migrator.migrations_path().display()
+            ),
+        });
         return Ok(());
     }
 
-    println!(
-        "{} {} migration(s)...",
-        if dry_run { "Would apply" } else { "Applying" },
-        pending.len()
-    );
-    println!();
-
-    let mut last_applied_version: Option<String> = None;
-
-    for migration in &pending {
-        println!("→ {}", migration.id);
-
-        if dry_run {
-            println!("  (dry run - skipped)");
-            last_applied_version = Some(migration.version.clone());
-            continue;
+    let status = migrator.status()?;
+    if let Some(baseline) = &status.baseline {
+        for migration in &status.available {
+            let applied = status.applied.iter().any(|a| a.id == migration.id);
+            if !applied && version_lte(&migration.version, &baseline.version) {
+                recorder.skip(
+                    &migration.id,
+                    &format!("covered by baseline {}", baseline.version),
+                );
+            }
         }
+    }
 
-        let ctx = ExecutionContext {
-            project_root: project_root.clone(),
-            migrations_dir: migrations_path.clone(),
-            migration_id: migration.id.clone(),
-            dry_run,
-        };
+    for migration in &status.filtered {
+        recorder.skip(&migration.id, "excluded by tag filter");
+    }
 
-        let result = execute(migration, &ctx)?;
+    reporter.report(&Event::RunStarted {
+        pending: status.pending.len(),
+        dry_run: options.dry_run,
+    });
+    if status.pending.is_empty() {
+        return write_reports(&migrator, options, &recorder);
+    }
 
-        if result.success {
-            let applied_at = Utc::now();
-            append_history(&migrations_path, &migration.id, applied_at)?;
-            last_applied_version = Some(migration.version.clone());
-            println!("  ✓ completed");
-        } else {
-            println!("  ✗ failed (exit code {})", result.exit_code);
-            if let Some(error) = result.error {
-                println!("    {}", error);
-            }
-            return Err(anyhow::anyhow!(
-                "Migration {} failed with exit code {}",
-                migration.id,
-                result.exit_code
-            ));
+    let report = migrator.apply()?;
+    for migration in &status.pending {
+        if !recorder.has_case(&migration.id) {
+            recorder.skip(&migration.id, "not run after an earlier failure");
         }
     }
 
-    println!();
-    println!("All migrations applied successfully.");
-
-    // Handle --baseline flag
-    if create_baseline {
-        if let Some(version) = last_applied_version {
-            println!();
-            if dry_run {
-                println!("Would create baseline at version '{}'", version);
-                if !keep {
-                    let to_delete: Vec<_> = available
-                        .iter()
-                        .filter(|m| m.version.as_str() <= version.as_str())
-                        .collect();
-                    if !to_delete.is_empty() {
-                        let asset_dir_count = to_delete
-                            .iter()
-                            .filter(|m| {
-                                m.file_path
-                                    .parent()
-                                    .map(|p| p.join(&m.id).is_dir())
-                                    .unwrap_or(false)
-                            })
-                            .count();
-                        if asset_dir_count > 0 {
-                            println!(
-                                "Would delete {} migration file(s) and {} asset directory(ies)",
-                                to_delete.len(),
-                                asset_dir_count
-                            );
-                        } else {
-                            println!("Would delete {} migration file(s)", to_delete.len());
-                        }
-                    }
-                }
-            } else {
-                let new_baseline = Baseline {
-                    version: version.clone(),
-                    created: Utc::now(),
-                    summary: None,
-                };
-
-                append_baseline(&migrations_path, &new_baseline)?;
-                println!("Created baseline at version '{}'", version);
+    let touched_files = report
+        .touched_files()
+        .into_iter()
+        .map(|(id, path)| TouchedFile {
+            id,
+            path: path
+                .strip_prefix(migrator.project_root())
+                .unwrap_or(path)
+                .display()
+                .to_string(),
+        })
+        .collect();
+    reporter.report(&Event::RunFinished {
+        success: report.is_success(),
+        applied: report
+            .outcomes
+            .iter()
+            .filter(|o| o.applied_at.is_some())
+            .count(),
+        touched_files,
+        messages: report
+            .messages()
+            .into_iter()
+            .map(|(id, message)| MigrationMessage { id, message })
+            .collect(),
+    });
+    write_reports(&migrator, options, &recorder)?;
+
+    if let Some(failed) = &report.failed {
+        let exit_code = failed.result.as_ref().map_or(-1, |r| r.exit_code);
+        return Err(MigrateError::MigrationFailed {
+            id: failed.migration.id.clone(),
+            exit_code,
+        }
+        .into());
+    }
 
-                if !keep {
-                    let deleted = delete_baselined_migrations(&version, &available)?;
-                    let (files, dirs): (Vec<&DeletedItem>, Vec<&DeletedItem>) =
-                        deleted.iter().partition(|d| !d.is_directory);
-                    if !files.is_empty() {
-                        println!("Deleted {} migration file(s)", files.len());
-                    }
-                    if !dirs.is_empty() {
-                        println!("Deleted {} asset directory(ies)", dirs.len());
-                    }
-                }
-            }
+    if let Some(baseline) = &report.baseline {
+        reporter.report(&Event::from(baseline));
+        if !baseline.deleted.is_empty() {
+            let (directories, files): (Vec<_>, Vec<_>) =
+                baseline.deleted.iter().partition(|d| d.is_directory);
+            reporter.report(&Event::FilesDeleted {
+                files: files.iter().map(|d| d.path.as_str()).collect(),
+                directories: directories.iter().map(|d| d.path.as_str()).collect(),
+            });
         }
     }
 
     Ok(())
 }
+
+fn write_reports(migrator: &Migrator, options: &UpOptions, recorder: &RunRecorder) -> Result<()> {
+    let suite = Suite {
+        name: suite_name(migrator.project_root()),
+        cases: recorder.cases(),
+    };
+    if let Some(suites) = options.suites {
+        suites.borrow_mut().push(suite);
+        return Ok(());
+    }
+    for spec in options.reports {
+        // Relative report paths are relative to where migrate was invoked, like other paths
+        write_report(spec, options.dry_run, std::slice::from_ref(&suite))?;
+    }
+    Ok(())
+}
+
+/// Ask for a parameter on the terminal; an empty answer keeps the default
+fn prompt(param: &Param) -> Result<Option<String>> {
+    let mut question = param.name.clone();
+    if let Some(description) = &param.description {
+        question.push_str(&format!(" ({})", description));
+    }
+    if let Some(default) = &param.default {
+        question.push_str(&format!(" [{}]", default));
+    }
+    eprint!("{}: ", question);
+    io::stderr().flush()?;
+    let mut line = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .context("Failed to read from stdin")?;
+    Ok(Some(line.trim().to_string()))
+}
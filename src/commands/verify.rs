@@ -0,0 +1,55 @@
+use anyhow::Result;
+
+use crate::verify::Postconditions;
+use crate::{ExecutionContext, MigrateError, Migrator};
+
+/// Check the postconditions of applied migrations again
+pub fn run(migrator: &Migrator) -> Result<()> {
+    let status = migrator.status()?;
+    let mut checked = Vec::new();
+    for migration in &status.available {
+        let applied = status.applied.iter().rev().find(|a| a.id == migration.id);
+        if applied.is_none_or(|a| a.not_applicable) {
+            continue;
+        }
+        let postconditions = Postconditions::load(migration)?;
+        if !postconditions.is_empty() {
+            checked.push((migration, postconditions));
+        }
+    }
+    if checked.is_empty() {
+        println!("No applied migrations have postconditions.");
+        return Ok(());
+    }
+
+    println!("Verifying {} applied migration(s)...", checked.len());
+    let mut failed = 0;
+    for (migration, postconditions) in &checked {
+        println!();
+        println!("→ {}", migration.id);
+        let ctx = ExecutionContext::new(
+            migrator.project_root().to_path_buf(),
+            migrator.migrations_path().to_path_buf(),
+            migration.id.clone(),
+            false,
+            Default::default(),
+        );
+        let failures = postconditions.check(&ctx)?;
+        if failures.is_empty() {
+            println!("  ✓ verified");
+        } else {
+            failed += 1;
+            println!("  ✗ {} postcondition(s) failed", failures.len());
+            for failure in failures {
+                println!("    {}", failure);
+            }
+        }
+    }
+
+    println!();
+    println!("{} verified, {} failed", checked.len() - failed, failed);
+    if failed > 0 {
+        return Err(MigrateError::VerificationFailed { count: failed }.into());
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+use crate::reporter::{Event, Format};
+use crate::workspace::workspace_name;
+
+/// Run a command in every workspace, reporting a header for each and continuing past
+/// failures. Fails at the end if any workspace failed, with the first workspace's error as
+/// the cause so its exit code is kept.
+pub fn run(
+    repo_root: &Path,
+    workspaces: &[PathBuf],
+    format: Format,
+    mut command: impl FnMut(&Path) -> Result<()>,
+) -> Result<()> {
+    let reporter = format.reporter();
+    if workspaces.is_empty() {
+        reporter.report(&Event::Notice {
+            message: &format!("No workspaces found in: {}", repo_root.display()),
+        });
+        return Ok(());
+    }
+
+    let mut failed = Vec::new();
+    let mut first_error = None;
+    for workspace in workspaces {
+        let name = workspace_name(repo_root, workspace);
+        reporter.report(&Event::WorkspaceStarted { name: &name });
+        let error = command(workspace).err();
+        reporter.report(&Event::WorkspaceFinished {
+            name: &name,
+            error: error.as_ref().map(|e| format!("{:#}", e)).as_deref(),
+        });
+        if let Some(error) = error {
+            first_error.get_or_insert(error);
+            failed.push(name);
+        }
+    }
+
+    reporter.report(&Event::WorkspacesFinished {
+        total: workspaces.len(),
+        failed: failed.iter().map(String::as_str).collect(),
+    });
+    match first_error {
+        Some(error) => Err(error.context(format!("{} workspace(s) failed", failed.len()))),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,301 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::state::{HISTORY_FILE, LEGACY_BASELINE_FILE, LEGACY_HISTORY_FILE};
+use crate::store::{HistoryFileStore, JsonLinesStore, StateStore};
+
+/// Name of the optional project configuration file, looked up in the project root
+pub const CONFIG_FILE: &str = "migrate.toml";
+
+/// Project configuration from `migrate.toml`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub state: StateConfig,
+    /// Workspace members for `--workspaces`, in a repository root config
+    pub workspace: Option<WorkspaceConfig>,
+}
+
+/// The `[workspace]` table: packages with their own migrations directory
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkspaceConfig {
+    /// Globs, relative to the repository root, matching workspace roots
+    pub members: Vec<String>,
+    /// Globs of workspace roots to leave out
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// The `[state]` table: where applied migrations are recorded
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StateConfig {
+    #[serde(default)]
+    pub backend: Backend,
+    /// Location of the state, relative to the project root (or absolute). Defaults to a file
+    /// in the `location` directory; for the `directory` backend, the directory to use.
+    pub path: Option<PathBuf>,
+    /// Where the state lives when no `path` is given
+    #[serde(default)]
+    pub location: Location,
+}
+
+/// Directory holding the state file when no explicit path is configured
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Location {
+    /// Next to the migrations (shared through version control)
+    #[default]
+    Migrations,
+    /// `.migrate/` in the project root, so each target root has its own state
+    Project,
+    /// `$XDG_STATE_HOME/migrate/<project>-<hash>/`, outside the project entirely
+    User,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    /// The plain-text `history` file format
+    #[default]
+    History,
+    /// One JSON object per line
+    Jsonl,
+    /// A SQLite database (requires the `sqlite` feature)
+    Sqlite,
+    /// A history file in a directory outside the migrations directory
+    Directory,
+}
+
+impl Config {
+    /// Load `migrate.toml` from the project root, or the defaults if it doesn't exist
+    pub fn load(project_root: &Path) -> Result<Self> {
+        let path = project_root.join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Invalid config: {}", path.display()))
+    }
+}
+
+impl StateConfig {
+    /// Use an explicit state file, choosing the backend from its extension
+    /// (`.jsonl`, `.db`/`.sqlite`, anything else is a history file)
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        self.backend = match path.extension().and_then(|e| e.to_str()) {
+            Some("jsonl") => Backend::Jsonl,
+            Some("db" | "sqlite" | "sqlite3") => Backend::Sqlite,
+            _ => Backend::History,
+        };
+        self.path = Some(path);
+        self
+    }
+
+    /// Open the configured state store. A relative `path` is resolved against the project root.
+    pub fn open(&self, project_root: &Path, migrations_dir: &Path) -> Result<Box<dyn StateStore>> {
+        let path = self.path.as_ref().map(|p| project_root.join(p));
+        let state_dir = match self.location {
+            Location::Migrations => migrations_dir.to_path_buf(),
+            Location::Project => project_root.join(PROJECT_STATE_DIR),
+            Location::User => user_state_dir(project_root)?,
+        };
+        if path.is_none() && self.location != Location::Migrations {
+            self.check_no_migrations_state(project_root, migrations_dir, &state_dir)?;
+        }
+        let store: Box<dyn StateStore> = match self.backend {
+            Backend::History => match path {
+                Some(file) => Box::new(HistoryFileStore::at(file)),
+                None => Box::new(HistoryFileStore::new(state_dir)),
+            },
+            Backend::Jsonl => Box::new(JsonLinesStore::new(
+                path.unwrap_or_else(|| state_dir.join("history.jsonl")),
+            )),
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite => Box::new(crate::store::SqliteStore::new(
+                path.unwrap_or_else(|| state_dir.join("history.db")),
+            )),
+            #[cfg(not(feature = "sqlite"))]
+            Backend::Sqlite => {
+                bail!("The sqlite state backend requires migrate to be built with the `sqlite` feature")
+            }
+            Backend::Directory => match path {
+                Some(dir) => Box::new(HistoryFileStore::at(dir.join(HISTORY_FILE))),
+                None => bail!("The directory state backend requires `path` in [state]"),
+            },
+        };
+        Ok(store)
+    }
+
+    /// Name of the state file the backend keeps in the state directory
+    fn default_file(&self) -> Option<&'static str> {
+        match self.backend {
+            Backend::History => Some(HISTORY_FILE),
+            Backend::Jsonl => Some("history.jsonl"),
+            Backend::Sqlite => Some("history.db"),
+            Backend::Directory => None,
+        }
+    }
+
+    /// Fail if the project's migrations directory already has state that a `project` or
+    /// `user` location would ignore, which would apply every migration again
+    fn check_no_migrations_state(
+        &self,
+        project_root: &Path,
+        migrations_dir: &Path,
+        state_dir: &Path,
+    ) -> Result<()> {
+        let Some(name) = self.default_file() else {
+            return Ok(());
+        };
+        let (existing, configured) = (migrations_dir.join(name), state_dir.join(name));
+        // Shared migrations outside the project (e.g. with --targets) keep their own state
+        if !migrations_dir.starts_with(project_root) || configured.exists() {
+            return Ok(());
+        }
+        if existing.exists() {
+            bail!(
+                "Applied migrations are recorded in {}, but the state location is now {}; \
+                 move the file there to keep them, or delete it to start over",
+                existing.display(),
+                configured.display()
+            );
+        }
+        // Legacy files are only converted to a history file where they are
+        for legacy in [LEGACY_HISTORY_FILE, LEGACY_BASELINE_FILE] {
+            let existing = migrations_dir.join(legacy);
+            if existing.exists() {
+                bail!(
+                    "Applied migrations are recorded in the legacy file {}, but the state \
+                     location is now {}; run `migrate status` once with location = \"migrations\" \
+                     to convert it, then move the history file there, or delete it to start over",
+                    existing.display(),
+                    configured.display()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// State directory inside the project root for `location = "project"`
+pub const PROJECT_STATE_DIR: &str = ".migrate";
+
+/// Per-user state directory for a project, keyed by its canonical path so that different
+/// checkouts don't share state: `$XDG_STATE_HOME/migrate/<name>-<hash>`
+fn user_state_dir(project_root: &Path) -> Result<PathBuf> {
+    let base = match std::env::var_os("XDG_STATE_HOME").filter(|v| !v.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => match std::env::var_os("HOME").filter(|v| !v.is_empty()) {
+            Some(home) => PathBuf::from(home).join(".local/state"),
+            None => {
+                bail!("Cannot locate user state directory: neither XDG_STATE_HOME nor HOME is set")
+            }
+        },
+    };
+
+    let root = project_root
+        .canonicalize()
+        .unwrap_or_else(|_| project_root.to_path_buf());
+    let name = root
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "root".to_string());
+    Ok(base.join("migrate").join(format!(
+        "{}-{:016x}",
+        name,
+        fnv1a(root.to_string_lossy().as_bytes())
+    )))
+}
+
+/// FNV-1a, a hash that is stable across Rust versions (unlike `DefaultHasher`)
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_state_config() {
+        let config: Config =
+            toml::from_str("[state]\nbackend = \"jsonl\"\npath = \"state/log.jsonl\"\n").unwrap();
+        assert_eq!(config.state.backend, Backend::Jsonl);
+        assert_eq!(config.state.path, Some(PathBuf::from("state/log.jsonl")));
+
+        let config: Config = toml::from_str("[workspace]\nmembers = [\"packages/*\"]\n").unwrap();
+        assert_eq!(config.workspace.unwrap().members, ["packages/*"]);
+
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.state.backend, Backend::History);
+        assert!(toml::from_str::<Config>("[state]\nbackend = \"redis\"\n").is_err());
+    }
+
+    #[test]
+    fn test_state_path_selects_backend() {
+        let config = StateConfig::default().with_path("state.jsonl");
+        assert_eq!(config.backend, Backend::Jsonl);
+        let config = StateConfig::default().with_path("/tmp/app/history");
+        assert_eq!(config.backend, Backend::History);
+    }
+
+    #[test]
+    fn test_user_state_dir_is_keyed_by_root() {
+        let a = user_state_dir(Path::new("/nonexistent/one/app")).unwrap();
+        let b = user_state_dir(Path::new("/nonexistent/two/app")).unwrap();
+        assert_ne!(a, b);
+        assert!(a.file_name().unwrap().to_string_lossy().starts_with("app-"));
+    }
+
+    #[test]
+    fn test_new_location_does_not_ignore_migrations_state() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let migrations_dir = root.join("migrations");
+        fs::create_dir(&migrations_dir).unwrap();
+        let config = StateConfig {
+            location: Location::Project,
+            ..StateConfig::default()
+        };
+        assert!(config.open(root, &migrations_dir).is_ok());
+
+        fs::write(migrations_dir.join(HISTORY_FILE), "").unwrap();
+        let error = config.open(root, &migrations_dir).err().unwrap();
+        assert!(
+            error.to_string().contains("move the file there"),
+            "{}",
+            error
+        );
+
+        fs::create_dir(root.join(PROJECT_STATE_DIR)).unwrap();
+        fs::rename(
+            migrations_dir.join(HISTORY_FILE),
+            root.join(PROJECT_STATE_DIR).join(HISTORY_FILE),
+        )
+        .unwrap();
+        assert!(config.open(root, &migrations_dir).is_ok());
+
+        // Legacy state files count too
+        fs::remove_file(root.join(PROJECT_STATE_DIR).join(HISTORY_FILE)).unwrap();
+        for legacy in [LEGACY_HISTORY_FILE, LEGACY_BASELINE_FILE] {
+            fs::write(migrations_dir.join(legacy), "").unwrap();
+            let error = config.open(root, &migrations_dir).err().unwrap();
+            assert!(error.to_string().contains("legacy file"), "{}", error);
+            fs::remove_file(migrations_dir.join(legacy)).unwrap();
+        }
+
+        // Shared migrations outside the project don't count
+        fs::write(migrations_dir.join(HISTORY_FILE), "").unwrap();
+        let target = root.join("target");
+        assert!(config.open(&target, &migrations_dir).is_ok());
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::baseline::version_lte;
+use crate::loader::extract_version;
+use crate::state::Baseline;
+use crate::{AppliedMigration, Migration};
+
+/// Check `DependsOn` headers: every dependency must exist (on disk, in history or covered by
+/// the baseline), must not have a later version than the migration that needs it, and
+/// dependencies must not form a cycle. Returns one message per problem.
+pub fn validate(
+    available: &[Migration],
+    applied: &[AppliedMigration],
+    baseline: Option<&Baseline>,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    for migration in available {
+        for dependency in &migration.header.depends_on {
+            match available.iter().find(|m| &m.id == dependency) {
+                Some(found) if found.version > migration.version => problems.push(format!(
+                    "{} depends on {}, which has a later version",
+                    migration.id, dependency
+                )),
+                Some(_) => {}
+                None if applied.iter().any(|a| &a.id == dependency)
+                    || is_baselined(dependency, baseline) => {}
+                None => problems.push(format!(
+                    "{} depends on unknown migration '{}'",
+                    migration.id, dependency
+                )),
+            }
+        }
+    }
+    if let Some(cycle) = find_cycle(available) {
+        problems.push(format!("Dependency cycle: {}", cycle.join(" -> ")));
+    }
+    problems
+}
+
+/// Whether a migration ID is at or before the baseline version
+pub fn is_baselined(id: &str, baseline: Option<&Baseline>) -> bool {
+    baseline.is_some_and(|b| extract_version(id).is_some_and(|v| version_lte(&v, &b.version)))
+}
+
+/// Sort migrations so each comes after the dependencies it shares the list with, otherwise
+/// by version. Migrations left in a cycle keep version order at the end.
+pub fn order(migrations: Vec<Migration>) -> Vec<Migration> {
+    let position: HashMap<&str, usize> = migrations
+        .iter()
+        .enumerate()
+        .map(|(i, m)| (m.id.as_str(), i))
+        .collect();
+    let mut waiting_on: Vec<usize> = vec![0; migrations.len()];
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); migrations.len()];
+    for (i, migration) in migrations.iter().enumerate() {
+        for dependency in &migration.header.depends_on {
+            if let Some(&d) = position.get(dependency.as_str()) {
+                waiting_on[i] += 1;
+                dependents[d].push(i);
+            }
+        }
+    }
+
+    let key = |i: usize| (migrations[i].version.clone(), migrations[i].id.clone(), i);
+    let mut ready: BTreeSet<(String, String, usize)> = (0..migrations.len())
+        .filter(|&i| waiting_on[i] == 0)
+        .map(key)
+        .collect();
+    let mut sorted = Vec::with_capacity(migrations.len());
+    while let Some((_, _, i)) = ready.pop_first() {
+        sorted.push(i);
+        for &dependent in &dependents[i] {
+            waiting_on[dependent] -= 1;
+            if waiting_on[dependent] == 0 {
+                ready.insert(key(dependent));
+            }
+        }
+    }
+    let mut rest: Vec<usize> = (0..migrations.len())
+        .filter(|i| !sorted.contains(i))
+        .collect();
+    rest.sort_by_key(|&i| key(i));
+    sorted.extend(rest);
+
+    let mut slots: Vec<Option<Migration>> = migrations.into_iter().map(Some).collect();
+    sorted.into_iter().filter_map(|i| slots[i].take()).collect()
+}
+
+/// A dependency cycle among `migrations`, as the IDs along it (first repeated at the end)
+fn find_cycle(migrations: &[Migration]) -> Option<Vec<String>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        New,
+        Visiting,
+        Done,
+    }
+
+    fn visit(
+        i: usize,
+        migrations: &[Migration],
+        marks: &mut [Mark],
+        path: &mut Vec<usize>,
+    ) -> Option<Vec<String>> {
+        marks[i] = Mark::Visiting;
+        path.push(i);
+        for dependency in &migrations[i].header.depends_on {
+            let Some(d) = migrations.iter().position(|m| &m.id == dependency) else {
+                continue;
+            };
+            match marks[d] {
+                Mark::Visiting => {
+                    let start = path.iter().position(|&p| p == d).unwrap_or(0);
+                    let mut cycle: Vec<String> = path[start..]
+                        .iter()
+                        .map(|&p| migrations[p].id.clone())
+                        .collect();
+                    cycle.push(migrations[d].id.clone());
+                    return Some(cycle);
+                }
+                Mark::New => {
+                    if let Some(cycle) = visit(d, migrations, marks, path) {
+                        return Some(cycle);
+                    }
+                }
+                Mark::Done => {}
+            }
+        }
+        path.pop();
+        marks[i] = Mark::Done;
+        None
+    }
+
+    let mut marks = vec![Mark::New; migrations.len()];
+    for i in 0..migrations.len() {
+        if marks[i] == Mark::New {
+            if let Some(cycle) = visit(i, migrations, &mut marks, &mut Vec::new()) {
+                return Some(cycle);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Header;
+    use chrono::Utc;
+
+    fn migration(id: &str, depends_on: &[&str]) -> Migration {
+        Migration {
+            id: id.to_string(),
+            version: id[..5].to_string(),
+            file_path: format!("{}.sh", id).into(),
+            header: Header {
+                depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn ids(migrations: &[Migration]) -> Vec<&str> {
+        migrations.iter().map(|m| m.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_validate() {
+        let available = vec![
+            migration("1f700-a", &[]),
+            migration("1f710-b", &["1f700-a", "1f600-old", "1f500-gone"]),
+            migration("1f720-c", &["1f730-d"]),
+            migration("1f730-d", &["1f7zz-missing"]),
+        ];
+        let applied = vec![AppliedMigration::new("1f600-old", Utc::now())];
+        let baseline = Baseline {
+            version: "1f5zz".to_string(),
+            created: Utc::now(),
+            summary: None,
+        };
+        assert_eq!(
+            validate(&available, &applied, Some(&baseline)),
+            [
+                "1f720-c depends on 1f730-d, which has a later version",
+                "1f730-d depends on unknown migration '1f7zz-missing'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_reports_cycle() {
+        let available = vec![
+            migration("1f700-a", &[]),
+            migration("1f710-b", &["1f710-c"]),
+            migration("1f710-c", &["1f710-b"]),
+        ];
+        assert_eq!(
+            validate(&available, &[], None),
+            ["Dependency cycle: 1f710-b -> 1f710-c -> 1f710-b"]
+        );
+    }
+
+    #[test]
+    fn test_order() {
+        // Same version: dependencies decide, otherwise IDs and versions
+        let ordered = order(vec![
+            migration("1f700-z", &["1f700-y"]),
+            migration("1f700-y", &[]),
+            migration("1f6zz-first", &[]),
+            migration("1f710-last", &["1f700-z"]),
+            migration("1f700-a", &[]),
+        ]);
+        assert_eq!(
+            ids(&ordered),
+            ["1f6zz-first", "1f700-a", "1f700-y", "1f700-z", "1f710-last"]
+        );
+    }
+}
@@ -0,0 +1,668 @@
+use anyhow::{bail, Context, Result};
+use glob::{glob, Pattern};
+use regex::Regex;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::render::{render_path, Variables};
+use crate::structured::{edit_file, DataEdit};
+use crate::verify::Assertion;
+use crate::{ExecutionContext, ExecutionResult, Migration, Outputs};
+
+/// A declarative migration: an ordered list of built-in steps read from a `.toml` file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeclarativeMigration {
+    /// Human-readable description of the migration
+    pub description: Option<String>,
+    /// Steps to execute, in order
+    #[serde(default)]
+    pub steps: Vec<Step>,
+    /// Postconditions checked after the steps run
+    #[serde(default)]
+    pub verify: Vec<Assertion>,
+}
+
+/// A built-in file operation. Paths are relative to the project root;
+/// asset paths are relative to the migration's asset directory.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Step {
+    /// Copy a file or directory from the asset directory into the project
+    Copy { from: String, to: String },
+    /// Write a file with the given content, replacing it if it exists
+    Write { path: String, content: String },
+    /// Delete a file or directory (no-op if it doesn't exist)
+    Delete { path: String },
+    /// Rename or move a file or directory
+    #[serde(alias = "move")]
+    Rename { from: String, to: String },
+    /// Create a directory and any missing parents
+    Mkdir { path: String },
+    /// Set file permissions (e.g. `mode = 0o755`)
+    Chmod { path: String, mode: u32 },
+    /// Create a symlink at `link` pointing to `target`
+    Symlink { target: String, link: String },
+    /// Replace regex matches in every file matching a glob
+    Replace {
+        files: String,
+        pattern: String,
+        replacement: String,
+    },
+    /// Append content to a file, creating it if needed
+    Append { path: String, content: String },
+    /// Append a line to a file unless an identical line is already present
+    EnsureLine { path: String, line: String },
+    /// Edit keys in a JSON, YAML or TOML file, preserving its formatting where possible
+    Edit { path: String, edits: Vec<DataEdit> },
+    /// Render a template file (or a directory, rendering its `*.tmpl` files) from the asset directory
+    Render {
+        from: String,
+        to: String,
+        /// Variables for this step, overriding project and command-line variables
+        #[serde(default)]
+        vars: Variables,
+    },
+}
+
+/// Paths a step operates on, resolved against the project root and asset directory
+pub struct StepEnv<'a> {
+    pub project_root: &'a Path,
+    pub asset_dir: PathBuf,
+    pub dry_run: bool,
+    /// Template variables available to render steps
+    pub variables: Variables,
+}
+
+impl DeclarativeMigration {
+    /// Parse a declarative migration file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read migration: {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Invalid declarative migration: {}", path.display()))
+    }
+}
+
+/// Execute a declarative migration in-process.
+/// In dry-run mode each step reports what it would change without touching the filesystem.
+/// Steps that read paths an earlier step would change are listed but not previewed, since
+/// the files they need don't exist yet.
+pub fn execute(migration: &Migration, ctx: &ExecutionContext) -> Result<ExecutionResult> {
+    let declarative = DeclarativeMigration::load(&migration.file_path)?;
+    let env = StepEnv {
+        project_root: &ctx.project_root,
+        asset_dir: ctx.migrations_dir.join(&migration.id),
+        dry_run: ctx.dry_run,
+        variables: ctx.variables.clone(),
+    };
+    let mut touched_files: Vec<PathBuf> = Vec::new();
+
+    for (index, step) in declarative.steps.iter().enumerate() {
+        ctx.log(format_args!(
+            "{}{}",
+            if ctx.dry_run { "would " } else { "" },
+            step.describe()
+        ));
+
+        if ctx.dry_run && step.depends_on(env.project_root, &touched_files) {
+            ctx.log(format_args!("  (not previewed: depends on earlier steps)"));
+            for path in step.writes() {
+                if let Ok(path) = resolve(env.project_root, path) {
+                    if !touched_files.contains(&path) {
+                        touched_files.push(path);
+                    }
+                }
+            }
+            continue;
+        }
+
+        match step.apply(&env) {
+            Ok(touched) => {
+                // Globs can expand to many files, so show which ones the preview would change
+                if ctx.dry_run && matches!(step, Step::Replace { .. }) {
+                    for path in &touched {
+                        let shown = path.strip_prefix(&ctx.project_root).unwrap_or(path);
+                        ctx.log(format_args!("  {}", shown.display()));
+                    }
+                }
+                for path in touched {
+                    if !touched_files.contains(&path) {
+                        touched_files.push(path);
+                    }
+                }
+            }
+            Err(e) => {
+                return Ok(ExecutionResult {
+                    success: false,
+                    exit_code: 1,
+                    error: Some(format!(
+                        "Step {} ({}) failed: {:#}",
+                        index + 1,
+                        step.describe(),
+                        e
+                    )),
+                    touched_files,
+                    outputs: Outputs::new(),
+                    messages: Vec::new(),
+                });
+            }
+        }
+    }
+
+    Ok(ExecutionResult {
+        success: true,
+        exit_code: 0,
+        error: None,
+        touched_files,
+        outputs: Outputs::new(),
+        messages: Vec::new(),
+    })
+}
+
+impl Step {
+    /// One-line summary of the step, used for progress and preview output
+    pub fn describe(&self) -> String {
+        match self {
+            Step::Copy { from, to } => format!("copy {} -> {}", from, to),
+            Step::Write { path, .. } => format!("write {}", path),
+            Step::Delete { path } => format!("delete {}", path),
+            Step::Rename { from, to } => format!("rename {} -> {}", from, to),
+            Step::Mkdir { path } => format!("mkdir {}", path),
+            Step::Chmod { path, mode } => format!("chmod {:o} {}", mode, path),
+            Step::Symlink { target, link } => format!("symlink {} -> {}", link, target),
+            Step::Replace { files, pattern, .. } => format!("replace /{}/ in {}", pattern, files),
+            Step::Append { path, .. } => format!("append to {}", path),
+            Step::EnsureLine { path, line } => format!("ensure line {:?} in {}", line, path),
+            Step::Edit { path, edits } => format!(
+                "edit {} ({})",
+                path,
+                edits
+                    .iter()
+                    .map(DataEdit::describe)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Step::Render { from, to, .. } => format!("render {} -> {}", from, to),
+        }
+    }
+
+    /// Project paths the step reads or requires to exist; `replace` reads its glob
+    fn reads(&self) -> Vec<&str> {
+        match self {
+            Step::Copy { .. } | Step::Write { .. } | Step::Symlink { .. } | Step::Render { .. } => {
+                vec![]
+            }
+            Step::Rename { from, .. } => vec![from],
+            Step::Replace { files, .. } => vec![files],
+            Step::Delete { path }
+            | Step::Mkdir { path }
+            | Step::Chmod { path, .. }
+            | Step::Append { path, .. }
+            | Step::EnsureLine { path, .. }
+            | Step::Edit { path, .. } => vec![path],
+        }
+    }
+
+    /// Project paths the step creates, changes or removes; `replace` changes files in place
+    fn writes(&self) -> Vec<&str> {
+        match self {
+            Step::Copy { to, .. } | Step::Render { to, .. } => vec![to],
+            Step::Rename { from, to } => vec![from, to],
+            Step::Symlink { link, .. } => vec![link],
+            Step::Replace { .. } => vec![],
+            Step::Write { path, .. }
+            | Step::Delete { path }
+            | Step::Mkdir { path }
+            | Step::Chmod { path, .. }
+            | Step::Append { path, .. }
+            | Step::EnsureLine { path, .. }
+            | Step::Edit { path, .. } => vec![path],
+        }
+    }
+
+    /// Whether the step reads a path that one of `changed` is, contains or lies within.
+    /// Invalid paths don't count, so applying the step reports them.
+    fn depends_on(&self, project_root: &Path, changed: &[PathBuf]) -> bool {
+        if let Step::Replace { files, .. } = self {
+            let Ok(full) = resolve(project_root, files) else {
+                return false;
+            };
+            let Ok(pattern) = Pattern::new(&full.to_string_lossy()) else {
+                return false;
+            };
+            // The directory the glob searches, up to its first wildcard
+            let base: PathBuf = full
+                .components()
+                .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+                .collect();
+            return changed
+                .iter()
+                .any(|path| pattern.matches_path(path) || base.starts_with(path));
+        }
+        self.reads()
+            .into_iter()
+            .filter_map(|path| resolve(project_root, path).ok())
+            .any(|read| {
+                changed
+                    .iter()
+                    .any(|path| read.starts_with(path) || path.starts_with(&read))
+            })
+    }
+
+    /// Apply the step, returning the paths it changed (or would change in dry-run mode).
+    pub fn apply(&self, env: &StepEnv) -> Result<Vec<PathBuf>> {
+        match self {
+            Step::Copy { from, to } => {
+                let source = resolve(&env.asset_dir, from)?;
+                let dest = resolve(env.project_root, to)?;
+                if !source.exists() {
+                    bail!("Asset not found: {}", source.display());
+                }
+                if !env.dry_run {
+                    create_parent(&dest)?;
+                    copy_recursive(&source, &dest)?;
+                }
+                Ok(vec![dest])
+            }
+            Step::Write { path, content } => {
+                let dest = resolve(env.project_root, path)?;
+                if !env.dry_run {
+                    create_parent(&dest)?;
+                    fs::write(&dest, content)
+                        .with_context(|| format!("Failed to write {}", dest.display()))?;
+                }
+                Ok(vec![dest])
+            }
+            Step::Delete { path } => {
+                let target = resolve(env.project_root, path)?;
+                if fs::symlink_metadata(&target).is_err() {
+                    return Ok(vec![]);
+                }
+                if !env.dry_run {
+                    if target.is_dir() && !target.is_symlink() {
+                        fs::remove_dir_all(&target)
+                    } else {
+                        fs::remove_file(&target)
+                    }
+                    .with_context(|| format!("Failed to delete {}", target.display()))?;
+                }
+                Ok(vec![target])
+            }
+            Step::Rename { from, to } => {
+                let source = resolve(env.project_root, from)?;
+                let dest = resolve(env.project_root, to)?;
+                if fs::symlink_metadata(&source).is_err() {
+                    bail!("Cannot rename missing path: {}", source.display());
+                }
+                if !env.dry_run {
+                    create_parent(&dest)?;
+                    fs::rename(&source, &dest).with_context(|| {
+                        format!(
+                            "Failed to rename {} to {}",
+                            source.display(),
+                            dest.display()
+                        )
+                    })?;
+                }
+                Ok(vec![source, dest])
+            }
+            Step::Mkdir { path } => {
+                let dir = resolve(env.project_root, path)?;
+                if dir.is_dir() {
+                    return Ok(vec![]);
+                }
+                if !env.dry_run {
+                    fs::create_dir_all(&dir)
+                        .with_context(|| format!("Failed to create {}", dir.display()))?;
+                }
+                Ok(vec![dir])
+            }
+            Step::Chmod { path, mode } => {
+                let target = resolve(env.project_root, path)?;
+                if !target.exists() {
+                    bail!("Cannot chmod missing path: {}", target.display());
+                }
+                if !env.dry_run {
+                    set_mode(&target, *mode)?;
+                }
+                Ok(vec![target])
+            }
+            Step::Symlink { target, link } => {
+                let link_path = resolve(env.project_root, link)?;
+                if !env.dry_run {
+                    create_parent(&link_path)?;
+                    if fs::symlink_metadata(&link_path).is_ok() {
+                        fs::remove_file(&link_path).with_context(|| {
+                            format!("Failed to replace {}", link_path.display())
+                        })?;
+                    }
+                    symlink(Path::new(target), &link_path)?;
+                }
+                Ok(vec![link_path])
+            }
+            Step::Replace {
+                files,
+                pattern,
+                replacement,
+            } => {
+                let regex =
+                    Regex::new(pattern).with_context(|| format!("Invalid pattern: {}", pattern))?;
+                let mut touched = Vec::new();
+                for path in glob_files(env.project_root, files)? {
+                    let content = fs::read_to_string(&path)
+                        .with_context(|| format!("Failed to read {}", path.display()))?;
+                    let replaced = regex.replace_all(&content, replacement.as_str());
+                    if replaced != content {
+                        if !env.dry_run {
+                            fs::write(&path, replaced.as_bytes())
+                                .with_context(|| format!("Failed to write {}", path.display()))?;
+                        }
+                        touched.push(path);
+                    }
+                }
+                Ok(touched)
+            }
+            Step::Append { path, content } => {
+                let dest = resolve(env.project_root, path)?;
+                let existing = read_optional(&dest)?;
+                if !env.dry_run {
+                    create_parent(&dest)?;
+                    fs::write(&dest, append_text(&existing, content))
+                        .with_context(|| format!("Failed to write {}", dest.display()))?;
+                }
+                Ok(vec![dest])
+            }
+            Step::EnsureLine { path, line } => {
+                let dest = resolve(env.project_root, path)?;
+                let existing = read_optional(&dest)?;
+                if existing.lines().any(|l| l == line) {
+                    return Ok(vec![]);
+                }
+                if !env.dry_run {
+                    create_parent(&dest)?;
+                    fs::write(&dest, append_text(&existing, &format!("{}\n", line)))
+                        .with_context(|| format!("Failed to write {}", dest.display()))?;
+                }
+                Ok(vec![dest])
+            }
+            Step::Edit { path, edits } => {
+                let dest = resolve(env.project_root, path)?;
+                if edit_file(&dest, edits, env.dry_run)? {
+                    Ok(vec![dest])
+                } else {
+                    Ok(vec![])
+                }
+            }
+            Step::Render { from, to, vars } => {
+                let source = resolve(&env.asset_dir, from)?;
+                let dest = resolve(env.project_root, to)?;
+                let mut variables = env.variables.clone();
+                variables.extend(vars.clone());
+                render_path(&source, &dest, &variables, env.dry_run)
+            }
+        }
+    }
+}
+
+/// Resolve a relative path under a base directory, rejecting paths that escape it
+/// or that name the base directory itself.
+pub fn resolve(base: &Path, relative: &str) -> Result<PathBuf> {
+    let path = Path::new(relative);
+    if path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+        || !path.components().any(|c| matches!(c, Component::Normal(_)))
+    {
+        bail!(
+            "Path must be relative and name something inside {}: '{}'",
+            base.display(),
+            relative
+        );
+    }
+    Ok(base.join(path))
+}
+
+/// Expand a glob relative to the project root into the list of matching files.
+fn glob_files(project_root: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    let full = resolve(project_root, pattern)?;
+    let full = full.to_str().context("Invalid glob pattern")?;
+    Ok(glob(full)
+        .with_context(|| format!("Invalid glob pattern: {}", pattern))?
+        .filter_map(|entry| entry.ok())
+        .filter(|path| path.is_file())
+        .collect())
+}
+
+fn read_optional(path: &Path) -> Result<String> {
+    if path.exists() {
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+    } else {
+        Ok(String::new())
+    }
+}
+
+/// Append text, separating it from existing content that lacks a trailing newline.
+fn append_text(existing: &str, addition: &str) -> String {
+    let mut result = existing.to_string();
+    if !result.is_empty() && !result.ends_with('\n') {
+        result.push('\n');
+    }
+    result.push_str(addition);
+    result
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    Ok(())
+}
+
+fn copy_recursive(source: &Path, dest: &Path) -> Result<()> {
+    if source.is_dir() {
+        fs::create_dir_all(dest)
+            .with_context(|| format!("Failed to create directory: {}", dest.display()))?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &dest.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(source, dest).with_context(|| {
+            format!("Failed to copy {} to {}", source.display(), dest.display())
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .with_context(|| format!("Failed to chmod {}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<()> {
+    bail!("chmod is only supported on Unix")
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(target, link)
+        .with_context(|| format!("Failed to create symlink {}", link.display()))
+}
+
+#[cfg(not(unix))]
+fn symlink(_target: &Path, _link: &Path) -> Result<()> {
+    bail!("symlink is only supported on Unix")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> DeclarativeMigration {
+        toml::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn test_parse_steps() {
+        let migration = parse(
+            r#"
+description = "Set up tooling"
+
+[[steps]]
+action = "copy"
+from = "prettierrc.json"
+to = ".prettierrc.json"
+
+[[steps]]
+action = "move"
+from = "a.txt"
+to = "b.txt"
+
+[[steps]]
+action = "chmod"
+path = "run.sh"
+mode = 0o755
+
+[[steps]]
+action = "ensure-line"
+path = ".gitignore"
+line = "dist/"
+
+[[steps]]
+action = "edit"
+path = "package.json"
+edits = [
+    { op = "set", key = "scripts.format", value = "prettier --write ." },
+    { op = "delete", key = "scripts.lint" },
+]
+"#,
+        );
+        assert_eq!(migration.description.as_deref(), Some("Set up tooling"));
+        assert_eq!(migration.steps.len(), 5);
+        assert!(matches!(migration.steps[1], Step::Rename { .. }));
+        assert!(matches!(
+            migration.steps[2],
+            Step::Chmod { mode: 0o755, .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_action() {
+        let result: Result<DeclarativeMigration, _> =
+            toml::from_str("[[steps]]\naction = \"explode\"\npath = \"x\"\n");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_resolve_rejects_escaping_paths() {
+        let base = Path::new("/project");
+        assert_eq!(
+            resolve(base, "src/main.ts").unwrap(),
+            PathBuf::from("/project/src/main.ts")
+        );
+        assert!(resolve(base, "../outside").is_err());
+        assert!(resolve(base, "/etc/passwd").is_err());
+        assert!(resolve(base, "").is_err());
+        assert!(resolve(base, ".").is_err());
+        assert!(resolve(base, "./").is_err());
+        assert_eq!(
+            resolve(base, "./src").unwrap(),
+            PathBuf::from("/project/src")
+        );
+    }
+
+    #[test]
+    fn test_depends_on_earlier_changes() {
+        let root = Path::new("/project");
+        let changed = [
+            PathBuf::from("/project/src/lib"),
+            PathBuf::from("/project/a.ts"),
+        ];
+        let edit = |path: &str| Step::Edit {
+            path: path.to_string(),
+            edits: vec![],
+        };
+        let replace = |files: &str| Step::Replace {
+            files: files.to_string(),
+            pattern: "x".to_string(),
+            replacement: "y".to_string(),
+        };
+
+        assert!(edit("src/lib/package.json").depends_on(root, &changed));
+        assert!(edit("src").depends_on(root, &changed));
+        assert!(!edit("src/main.ts").depends_on(root, &changed));
+        assert!(replace("*.ts").depends_on(root, &changed));
+        assert!(replace("src/lib/**/*.ts").depends_on(root, &changed));
+        assert!(!replace("test/*.ts").depends_on(root, &changed));
+        assert!(!Step::Write {
+            path: "a.ts".to_string(),
+            content: String::new(),
+        }
+        .depends_on(root, &changed));
+    }
+
+    #[test]
+    fn test_steps_apply_and_dry_run() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join(".gitignore"), "node_modules/").unwrap();
+        fs::write(root.join("main.ts"), "const a = oldName();\n").unwrap();
+
+        let steps = [
+            Step::EnsureLine {
+                path: ".gitignore".to_string(),
+                line: "dist/".to_string(),
+            },
+            Step::Replace {
+                files: "*.ts".to_string(),
+                pattern: r"oldName\(".to_string(),
+                replacement: "newName(".to_string(),
+            },
+            Step::Write {
+                path: "config/app.json".to_string(),
+                content: "{}\n".to_string(),
+            },
+        ];
+
+        // Dry run reports the files without changing them
+        let dry_env = StepEnv {
+            project_root: root,
+            asset_dir: root.join("assets"),
+            dry_run: true,
+            variables: Variables::new(),
+        };
+        for step in &steps {
+            assert_eq!(step.apply(&dry_env).unwrap().len(), 1);
+        }
+        assert_eq!(
+            fs::read_to_string(root.join(".gitignore")).unwrap(),
+            "node_modules/"
+        );
+        assert!(!root.join("config").exists());
+
+        let env = StepEnv {
+            dry_run: false,
+            ..dry_env
+        };
+        for step in &steps {
+            step.apply(&env).unwrap();
+        }
+        assert_eq!(
+            fs::read_to_string(root.join(".gitignore")).unwrap(),
+            "node_modules/\ndist/\n"
+        );
+        assert!(fs::read_to_string(root.join("main.ts"))
+            .unwrap()
+            .contains("newName("));
+        assert!(root.join("config/app.json").exists());
+
+        // Re-applying ensure-line is a no-op
+        assert!(steps[0].apply(&env).unwrap().is_empty());
+    }
+}
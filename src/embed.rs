@@ -0,0 +1,239 @@
+use anyhow::{bail, Context, Result};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::PROJECT_STATE_DIR;
+use crate::migrator::ApplyReport;
+use crate::state::HISTORY_FILE;
+use crate::Migrator;
+
+/// Include the file written by [`generate`] from `OUT_DIR`, evaluating to an
+/// [`EmbeddedMigrations`](crate::embed::EmbeddedMigrations)
+#[macro_export]
+macro_rules! include_migrations {
+    ($name:literal) => {
+        include!(concat!(env!("OUT_DIR"), "/", $name))
+    };
+}
+
+/// A file from the embedded migrations directory
+#[derive(Debug)]
+pub struct EmbeddedFile {
+    /// Path relative to the migrations directory, with `/` separators
+    pub path: &'static str,
+    pub contents: &'static [u8],
+    /// Unix permission bits, so scripts stay executable
+    pub mode: u32,
+}
+
+/// A migrations directory compiled into the binary
+#[derive(Debug)]
+pub struct EmbeddedMigrations {
+    files: &'static [EmbeddedFile],
+}
+
+impl EmbeddedMigrations {
+    pub const fn new(files: &'static [EmbeddedFile]) -> Self {
+        Self { files }
+    }
+
+    pub fn files(&self) -> &'static [EmbeddedFile] {
+        self.files
+    }
+
+    /// Write the embedded files to `dir`
+    pub fn extract(&self, dir: &Path) -> Result<()> {
+        for file in self.files {
+            let path = dir.join(file.path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, file.contents)
+                .with_context(|| format!("Failed to extract {}", path.display()))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&path, fs::Permissions::from_mode(file.mode))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply pending migrations to `target`, recording state in `<target>/.migrate/history`
+    /// (the `migrate` CLI's history format)
+    pub fn apply(&self, target: impl AsRef<Path>) -> Result<ApplyReport> {
+        self.apply_with(target, |migrator| migrator)
+    }
+
+    /// Like [`apply`](Self::apply), letting `configure` adjust the migrator (dry run,
+    /// variables, progress callback, a different state file, ...)
+    pub fn apply_with(
+        &self,
+        target: impl AsRef<Path>,
+        configure: impl FnOnce(Migrator) -> Migrator,
+    ) -> Result<ApplyReport> {
+        let target = target.as_ref();
+        // Removed when it goes out of scope, after the migrations have run
+        let extracted = tempfile::Builder::new()
+            .prefix("migrate-embedded-")
+            .tempdir()
+            .context("Failed to create a directory for the embedded migrations")?;
+        self.extract(extracted.path())?;
+
+        let migrator = Migrator::new(target)
+            .migrations_dir(extracted.path())
+            .state_file(target.join(PROJECT_STATE_DIR).join(HISTORY_FILE));
+        configure(migrator).apply()
+    }
+}
+
+/// Build-script helper: embed `migrations_dir` (relative to the crate root) by writing
+/// `OUT_DIR/<out_name>` for [`include_migrations!`], and ask Cargo to rebuild when it changes.
+pub fn generate(migrations_dir: impl AsRef<Path>, out_name: &str) -> Result<()> {
+    let out_dir = std::env::var_os("OUT_DIR").context("OUT_DIR is not set; call from build.rs")?;
+    let migrations_dir = fs::canonicalize(migrations_dir.as_ref()).with_context(|| {
+        format!(
+            "Migrations directory not found: {}",
+            migrations_dir.as_ref().display()
+        )
+    })?;
+    println!("cargo:rerun-if-changed={}", migrations_dir.display());
+    generate_to(&migrations_dir, &PathBuf::from(out_dir).join(out_name))
+}
+
+/// Write the Rust source embedding `migrations_dir` to `out_file`
+pub fn generate_to(migrations_dir: &Path, out_file: &Path) -> Result<()> {
+    if !migrations_dir.is_dir() {
+        bail!(
+            "Migrations directory not found: {}",
+            migrations_dir.display()
+        );
+    }
+
+    let mut files = Vec::new();
+    collect(migrations_dir, migrations_dir, &mut files)?;
+    files.sort();
+
+    let mut source = String::from("migrate::embed::EmbeddedMigrations::new(&[\n");
+    for path in &files {
+        let relative = path
+            .strip_prefix(migrations_dir)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let absolute = path
+            .to_str()
+            .context("Non-UTF-8 path in migrations directory")?;
+        writeln!(
+            source,
+            "    migrate::embed::EmbeddedFile {{ path: {:?}, contents: include_bytes!({:?}), mode: 0o{:o} }},",
+            relative,
+            absolute,
+            file_mode(path)?
+        )?;
+    }
+    source.push_str("])\n");
+
+    fs::write(out_file, source).with_context(|| format!("Failed to write {}", out_file.display()))
+}
+
+/// Collect migration files and assets, leaving out state the CLI may have written
+fn collect(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if dir == root && is_state_file(&name) {
+            continue;
+        }
+        if path.is_dir() {
+            collect(root, &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_state_file(name: &str) -> bool {
+    name == HISTORY_FILE
+        || name.starts_with(&format!("{}.", HISTORY_FILE))
+        || name == ".history"
+        || name == ".baseline"
+}
+
+fn file_mode(path: &Path) -> Result<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Ok(fs::metadata(path)?.permissions().mode() & 0o777)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        Ok(0o644)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    static FILES: &[EmbeddedFile] = &[
+        EmbeddedFile {
+            path: "00001-add-config.toml",
+            contents:
+                b"[[steps]]\naction = \"copy\"\nfrom = \"config.json\"\nto = \"config.json\"\n",
+            mode: 0o644,
+        },
+        EmbeddedFile {
+            path: "00001-add-config/config.json",
+            contents: b"{\"version\": 2}\n",
+            mode: 0o644,
+        },
+    ];
+    static MIGRATIONS: EmbeddedMigrations = EmbeddedMigrations::new(FILES);
+
+    #[test]
+    fn test_apply_embedded_migrations() {
+        let target = TempDir::new().unwrap();
+
+        let report = MIGRATIONS.apply(target.path()).unwrap();
+        assert!(report.is_success());
+        assert_eq!(report.outcomes.len(), 1);
+        assert_eq!(
+            fs::read_to_string(target.path().join("config.json")).unwrap(),
+            "{\"version\": 2}\n"
+        );
+        let history = fs::read_to_string(target.path().join(".migrate/history")).unwrap();
+        assert!(history.starts_with("00001-add-config "));
+
+        // Already applied
+        let report = MIGRATIONS.apply(target.path()).unwrap();
+        assert!(report.outcomes.is_empty());
+    }
+
+    #[test]
+    fn test_generate_skips_state_files() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("00001-init")).unwrap();
+        fs::write(dir.path().join("00001-init.sh"), "#!/bin/sh\n").unwrap();
+        fs::write(dir.path().join("00001-init/asset.txt"), "asset").unwrap();
+        fs::write(
+            dir.path().join("history"),
+            "00001-init 2024-01-01T00:00:00Z\n",
+        )
+        .unwrap();
+
+        let out = dir.path().join("out.rs");
+        generate_to(dir.path(), &out).unwrap();
+        let source = fs::read_to_string(&out).unwrap();
+
+        assert!(source.contains("path: \"00001-init.sh\""));
+        assert!(source.contains("path: \"00001-init/asset.txt\""));
+        assert!(!source.contains("path: \"history\""));
+        assert!(source.contains("include_bytes!("));
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// Failures callers may want to handle specifically. Library functions return
+/// `anyhow::Result`; these variants can be recovered with `downcast_ref` (or [`exit_code`]),
+/// and the CLI maps each one to its own exit code.
+#[derive(Debug)]
+pub enum MigrateError {
+    /// A migration did not succeed
+    MigrationFailed { id: String, exit_code: i32 },
+    /// Migrations are pending (`migrate check`)
+    PendingMigrations { count: usize },
+    /// The requested baseline is not valid for this project
+    InvalidBaseline(String),
+    /// The state file could not be parsed
+    HistoryCorrupt {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// Another migrate process holds the state lock
+    LockHeld { path: PathBuf },
+    /// `migrate create --template` named a template that doesn't exist
+    UnknownTemplate {
+        name: String,
+        available: Vec<String>,
+    },
+    /// `DependsOn` headers name missing or later migrations, or form a cycle
+    InvalidDependencies(Vec<String>),
+    /// Applied migrations whose postconditions no longer hold (`migrate verify`)
+    VerificationFailed { count: usize },
+    /// Required `Param`s of pending migrations have no value
+    MissingParameters(Vec<String>),
+}
+
+impl MigrateError {
+    /// Process exit code used by the CLI; 1 is any other error and 2 a usage error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            MigrateError::MigrationFailed { .. } => 3,
+            MigrateError::PendingMigrations { .. } => 4,
+            MigrateError::InvalidBaseline(_) => 5,
+            MigrateError::HistoryCorrupt { .. } => 6,
+            MigrateError::LockHeld { .. } => 7,
+            MigrateError::UnknownTemplate { .. } => 8,
+            MigrateError::InvalidDependencies(_) => 9,
+            MigrateError::VerificationFailed { .. } => 10,
+            MigrateError::MissingParameters(_) => 11,
+        }
+    }
+}
+
+impl fmt::Display for MigrateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrateError::MigrationFailed { id, exit_code } => {
+                write!(f, "Migration {} failed with exit code {}", id, exit_code)
+            }
+            MigrateError::PendingMigrations { count } => {
+                write!(f, "{} pending migration(s)", count)
+            }
+            MigrateError::InvalidBaseline(reason) => write!(f, "{}", reason),
+            MigrateError::HistoryCorrupt {
+                path,
+                line,
+                message,
+            } => write!(f, "{} (line {} of {})", message, line, path.display()),
+            MigrateError::LockHeld { path } => write!(
+                f,
+                "State is locked by another migrate process ({})",
+                path.display()
+            ),
+            MigrateError::UnknownTemplate { name, available } => write!(
+                f,
+                "Unknown template '{}'. Available: {}",
+                name,
+                available.join(", ")
+            ),
+            MigrateError::InvalidDependencies(problems) => {
+                write!(f, "Invalid migration dependencies:")?;
+                for problem in problems {
+                    write!(f, "\n  {}", problem)?;
+                }
+                Ok(())
+            }
+            MigrateError::VerificationFailed { count } => {
+                write!(f, "{} migration(s) failed verification", count)
+            }
+            MigrateError::MissingParameters(missing) => {
+                write!(f, "Missing required migration parameters:")?;
+                for line in missing {
+                    write!(f, "\n  {}", line)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for MigrateError {}
+
+/// Exit code for an error: that of the first [`MigrateError`] in its chain, otherwise 1
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<MigrateError>())
+        .map_or(1, MigrateError::exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_exit_code_survives_context() {
+        let error = Err::<(), _>(MigrateError::LockHeld {
+            path: PathBuf::from("history.lock"),
+        })
+        .context("Failed to apply migrations")
+        .unwrap_err();
+        assert_eq!(exit_code(&error), 7);
+        assert_eq!(exit_code(&anyhow::anyhow!("I/O error")), 1);
+    }
+}
@@ -1,31 +1,418 @@
 use anyhow::{Context, Result};
-use std::process::Command;
+use glob::glob;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, BufRead, BufReader, PipeReader, PipeWriter, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::NamedTempFile;
 
-use crate::{ExecutionContext, ExecutionResult, Migration};
+use crate::declarative;
+use crate::header::{format_timeout, Condition};
+use crate::messages::{Message, MESSAGE_FD, MESSAGE_FD_ENV};
+use crate::outputs::{self, Outputs, OUTPUT_ENV};
+use crate::params;
+use crate::{ExecutionContext, ExecutionResult, Migration, Stream};
 
-/// Execute a migration file as a subprocess.
-/// The migration receives context via environment variables.
+/// Environment variable naming the file where `migrate helper` commands record touched files
+pub const TOUCHED_FILES_ENV: &str = "MIGRATE_TOUCHED_FILES";
+
+/// Environment variable holding the template variables as a JSON object
+pub const VARIABLES_ENV: &str = "MIGRATE_VARS";
+
+/// How often a running script is checked for having exited or timed out
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How long to keep reading a script's pipes after it exited, once they go quiet
+const DRAIN_IDLE: Duration = Duration::from_millis(100);
+
+/// The longest to keep reading a script's pipes after it exited, even if a background
+/// process it left running keeps writing to them
+const DRAIN_LIMIT: Duration = Duration::from_secs(1);
+
+/// Exit code a script returns to mean "not applicable to this project": the migration is
+/// recorded as not applicable instead of failing, and won't run again
+pub const NOT_APPLICABLE_EXIT_CODE: i32 = 99;
+
+/// Execute a migration.
+/// Script migrations run as a subprocess and receive context via environment variables;
+/// declarative migrations run in-process.
 pub fn execute(migration: &Migration, ctx: &ExecutionContext) -> Result<ExecutionResult> {
-    let status = Command::new(&migration.file_path)
+    if migration.is_declarative() {
+        return declarative::execute(migration, ctx);
+    }
+
+    if let Some(missing) = migration
+        .header
+        .requires
+        .iter()
+        .find(|command| !command_exists(command))
+    {
+        return Ok(ExecutionResult {
+            success: false,
+            exit_code: 127,
+            error: Some(format!(
+                "Migration {} requires '{}', which was not found on PATH",
+                migration.id, missing
+            )),
+            touched_files: Vec::new(),
+            outputs: Outputs::new(),
+            messages: Vec::new(),
+        });
+    }
+
+    for collision in outputs::env_collisions(&ctx.earlier_outputs) {
+        ctx.message(Message::Warning { message: collision });
+    }
+
+    // Unique per execution: the same migration may run for several targets at once
+    let touched_log = temp_file("migrate-touched-")?;
+    let output_file = temp_file("migrate-output-")?;
+
+    let mut command = context_command(&migration.file_path, ctx);
+    command
+        .env(
+            "MIGRATE_NOT_APPLICABLE",
+            NOT_APPLICABLE_EXIT_CODE.to_string(),
+        )
+        .env(TOUCHED_FILES_ENV, touched_log.path())
+        .env(OUTPUT_ENV, output_file.path())
+        .env(VARIABLES_ENV, serde_json::to_string(&ctx.variables)?)
+        .envs(migration.header.env.iter().map(|(k, v)| (k, v)));
+    // Let scripts call `"$MIGRATE_BIN" helper ...` even when migrate isn't on PATH
+    if let Ok(exe) = std::env::current_exe() {
+        command.env("MIGRATE_BIN", exe);
+    }
+
+    // A timeout too long to represent is no timeout
+    let deadline = migration
+        .header
+        .timeout
+        .and_then(|t| Instant::now().checked_add(t));
+    let messages = message_pipe(&mut command)?;
+    let status = run_forwarding(&mut command, ctx, messages, deadline);
+    let outputs = outputs::read(output_file.path());
+    let mut touched_files = read_touched_files(touched_log.path());
+    for path in ctx.take_touched_files() {
+        if !touched_files.contains(&path) {
+            touched_files.push(path);
+        }
+    }
+    let messages = ctx.take_messages();
+    let status =
+        status.with_context(|| format!("Failed to execute migration: {}", migration.id))?;
+
+    let (success, exit_code, error, outputs) = match (status.map(|s| s.code()), outputs) {
+        (None, _) => (
+            false,
+            -1,
+            Some(format!(
+                "Migration {} timed out after {}",
+                migration.id,
+                format_timeout(migration.header.timeout.unwrap_or_default())
+            )),
+            Outputs::new(),
+        ),
+        (Some(Some(code @ (0 | NOT_APPLICABLE_EXIT_CODE))), Ok(outputs)) => {
+            (true, code, None, outputs)
+        }
+        (Some(Some(0 | NOT_APPLICABLE_EXIT_CODE)), Err(e)) => (
+            false,
+            1,
+            Some(format!(
+                "Migration {} wrote invalid outputs: {:#}",
+                migration.id, e
+            )),
+            Outputs::new(),
+        ),
+        (Some(code), _) => {
+            let code = code.unwrap_or(-1);
+            (
+                false,
+                code,
+                Some(format!(
+                    "Migration {} failed with exit code {}",
+                    migration.id, code
+                )),
+                Outputs::new(),
+            )
+        }
+    };
+    Ok(ExecutionResult {
+        success,
+        exit_code,
+        error,
+        touched_files,
+        outputs,
+        messages,
+    })
+}
+
+/// Check a `RunIf` condition in the project root. Commands run through `sh -c` with the
+/// same `MIGRATE_*` variables as the migration and must exit 0.
+pub fn check_condition(condition: &Condition, ctx: &ExecutionContext) -> Result<bool> {
+    match condition {
+        Condition::FileExists(path) => Ok(ctx.project_root.join(path).exists()),
+        Condition::GlobMatches(pattern) => {
+            let full = ctx.project_root.join(pattern);
+            let full = full.to_str().context("Invalid glob pattern")?;
+            let mut paths =
+                glob(full).with_context(|| format!("Invalid glob pattern: {}", pattern))?;
+            Ok(paths.any(|path| path.is_ok()))
+        }
+        Condition::Command(script) => {
+            let status = context_command("sh", ctx)
+                .arg("-c")
+                .arg(script)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .with_context(|| format!("Failed to run RunIf command: {}", script))?;
+            Ok(status.success())
+        }
+    }
+}
+
+/// A command that runs in the project root with the `MIGRATE_*` variables describing `ctx`
+pub(crate) fn context_command(program: impl AsRef<OsStr>, ctx: &ExecutionContext) -> Command {
+    let mut command = Command::new(program);
+    command
         .env("MIGRATE_PROJECT_ROOT", &ctx.project_root)
         .env("MIGRATE_MIGRATIONS_DIR", &ctx.migrations_dir)
         .env("MIGRATE_ID", &ctx.migration_id)
         .env("MIGRATE_DRY_RUN", ctx.dry_run.to_string())
-        .current_dir(&ctx.project_root)
-        .status()
-        .with_context(|| format!("Failed to execute migration: {}", migration.id))?;
+        .envs(
+            ctx.params
+                .iter()
+                .map(|(name, value)| (params::env_name(name), value)),
+        )
+        .envs(ctx.earlier_outputs.iter().flat_map(|(id, outputs)| {
+            outputs
+                .iter()
+                .map(move |(key, value)| (outputs::env_name(id, key), value))
+        }))
+        .current_dir(&ctx.project_root);
+    command
+}
 
-    Ok(ExecutionResult {
-        success: status.success(),
-        exit_code: status.code().unwrap_or(-1),
-        error: if status.success() {
-            None
-        } else {
-            Some(format!(
-                "Migration {} failed with exit code {}",
-                migration.id,
-                status.code().unwrap_or(-1)
-            ))
-        },
+/// Run `command`, sending its output to the context's sink if it has one. Returns `None` if
+/// it was killed for running past `deadline`.
+pub(crate) fn run(
+    command: &mut Command,
+    ctx: &ExecutionContext,
+    deadline: Option<Instant>,
+) -> Result<Option<ExitStatus>> {
+    run_forwarding(command, ctx, None, deadline)
+}
+
+/// A pipe whose write end a script inherits as file descriptor [`MESSAGE_FD`]
+#[cfg(unix)]
+fn message_pipe(command: &mut Command) -> Result<Option<(PipeReader, PipeWriter)>> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::process::CommandExt;
+
+    let (reader, writer) = io::pipe().context("Failed to create message pipe")?;
+    let fd = writer.as_raw_fd();
+    command.env(MESSAGE_FD_ENV, MESSAGE_FD.to_string());
+    // Runs in the child after stdio is set up. A descriptor moved with dup2 is not
+    // close-on-exec; if the pipe already is MESSAGE_FD, clear the flag instead.
+    unsafe {
+        command.pre_exec(move || {
+            let result = if fd == MESSAGE_FD {
+                libc::fcntl(fd, libc::F_SETFD, 0)
+            } else {
+                libc::dup2(fd, MESSAGE_FD)
+            };
+            if result == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(Some((reader, writer)))
+}
+
+#[cfg(not(unix))]
+fn message_pipe(_command: &mut Command) -> Result<Option<(PipeReader, PipeWriter)>> {
+    Ok(None)
+}
+
+/// Run `command`, passing each line of its stdout and stderr to the context's sink (if it
+/// has one) and each line read from `messages` to [`ExecutionContext::message`] as they
+/// arrive. Returns `None` if it was killed for running past `deadline`.
+fn run_forwarding(
+    command: &mut Command,
+    ctx: &ExecutionContext,
+    messages: Option<(PipeReader, PipeWriter)>,
+    deadline: Option<Instant>,
+) -> Result<Option<ExitStatus>> {
+    let sink = ctx.output();
+    if sink.is_some() {
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+    let child = command.spawn();
+    // Close our copy of the write end, so reading stops once the script's copy is closed
+    let messages = messages.map(|(reader, _)| reader);
+    let mut child = child?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut readers = Vec::new();
+    if let Some(out) = child.stdout.take() {
+        readers.push(forward_lines(out, sender.clone(), |line| {
+            Received::Output(line, Stream::Stdout)
+        }));
+    }
+    if let Some(err) = child.stderr.take() {
+        readers.push(forward_lines(err, sender.clone(), |line| {
+            Received::Output(line, Stream::Stderr)
+        }));
+    }
+    if let Some(messages) = messages {
+        readers.push(forward_lines(messages, sender.clone(), Received::Message));
+    }
+    drop(sender);
+    // Ends once every pipe is closed, or soon after the script exits: a background process
+    // it started inherits the pipes and may keep them open long after. Returning drops the
+    // receiver, so the reader threads close their pipes on the next line they read.
+    let mut exited = None;
+    let mut drain_until: Option<Instant> = None;
+    loop {
+        let wait = match drain_until {
+            Some(until) => {
+                let remaining = until.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Ok(exited);
+                }
+                remaining.min(DRAIN_IDLE)
+            }
+            None => POLL_INTERVAL,
+        };
+        match receiver.recv_timeout(wait) {
+            Ok(Received::Output(line, stream)) => {
+                if let Some(sink) = sink {
+                    sink.emit(&line, stream);
+                }
+            }
+            Ok(Received::Message(line)) => {
+                if !line.trim().is_empty() {
+                    ctx.message(Message::parse(&line));
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+            // Whatever the script wrote before exiting has been read by now
+            Err(RecvTimeoutError::Timeout) if exited.is_some() => return Ok(exited),
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+        if exited.is_none() {
+            exited = child.try_wait()?;
+            if exited.is_some() {
+                drain_until = Some(Instant::now() + DRAIN_LIMIT);
+            } else if deadline.is_some_and(|d| Instant::now() >= d) {
+                kill(&mut child)?;
+                return Ok(None);
+            }
+        }
+    }
+    for reader in readers {
+        reader.join().ok();
+    }
+    match exited {
+        Some(status) => Ok(Some(status)),
+        None => wait_until(&mut child, deadline),
+    }
+}
+
+/// A line read from one of a child's pipes
+enum Received {
+    Output(String, Stream),
+    Message(String),
+}
+
+/// Wait for `child` to exit, killing it if it is still running at `deadline`
+fn wait_until(child: &mut Child, deadline: Option<Instant>) -> Result<Option<ExitStatus>> {
+    let Some(deadline) = deadline else {
+        return Ok(Some(child.wait()?));
+    };
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            kill(child)?;
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn kill(child: &mut Child) -> Result<()> {
+    child.kill().ok();
+    child.wait()?;
+    Ok(())
+}
+
+/// Whether `command` is an executable path or found on PATH
+fn command_exists(command: &str) -> bool {
+    if command.contains('/') {
+        return Path::new(command).is_file();
+    }
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(command).is_file()))
+}
+
+fn forward_lines(
+    pipe: impl Read + Send + 'static,
+    sender: mpsc::Sender<Received>,
+    wrap: fn(String) -> Received,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(pipe).lines() {
+            let Ok(line) = line else { break };
+            if sender.send(wrap(line)).is_err() {
+                break;
+            }
+        }
     })
 }
+
+/// An in-process migration implemented as a Rust function
+pub type MigrationFn = Box<dyn Fn(&ExecutionContext) -> Result<()>>;
+
+/// Run a Rust function migration, turning an error into a failed result
+pub fn execute_fn(f: &MigrationFn, ctx: &ExecutionContext) -> ExecutionResult {
+    let outcome = f(ctx);
+    ExecutionResult {
+        success: outcome.is_ok(),
+        exit_code: if outcome.is_ok() { 0 } else { 1 },
+        error: outcome.err().map(|e| format!("{:#}", e)),
+        touched_files: ctx.take_touched_files(),
+        outputs: ctx.take_outputs(),
+        messages: ctx.take_messages(),
+    }
+}
+
+/// An empty temporary file, removed when dropped
+fn temp_file(prefix: &str) -> Result<NamedTempFile> {
+    tempfile::Builder::new()
+        .prefix(prefix)
+        .tempfile()
+        .context("Failed to create temporary file")
+}
+
+/// Read the touched-files log written by helpers, de-duplicating entries.
+fn read_touched_files(log: &Path) -> Vec<PathBuf> {
+    let content = fs::read_to_string(log).unwrap_or_default();
+
+    let mut files: Vec<PathBuf> = Vec::new();
+    for line in content.lines().filter(|l| !l.is_empty()) {
+        let path = PathBuf::from(line);
+        if !files.contains(&path) {
+            files.push(path);
+        }
+    }
+    files
+}
@@ -0,0 +1,455 @@
+use regex::Regex;
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Metadata from the comment header at the top of a migration file:
+///
+/// ```text
+/// #!/usr/bin/env bash
+/// # Description: Add TypeScript configuration
+/// # Tags: typescript, tooling
+/// # Requires: node, jq
+/// # Timeout: 5m
+/// # DependsOn: 1f72f-init
+/// # Env: NODE_ENV=production
+/// # RunIf: file-exists tsconfig.json
+/// # Verify: test -f tsconfig.json
+/// # Param: target=es2022 ECMAScript target for tsconfig.json
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Header {
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    /// Commands that must be on PATH for the migration to run
+    pub requires: Vec<String>,
+    /// Kill the migration if it runs longer than this
+    pub timeout: Option<Duration>,
+    /// IDs of migrations that must be applied first
+    pub depends_on: Vec<String>,
+    /// Extra environment variables for the script
+    pub env: Vec<(String, String)>,
+    /// Conditions that must all hold for the migration to run; otherwise it is recorded as
+    /// not applicable
+    pub run_if: Vec<Condition>,
+    /// Shell commands that must exit 0 after the migration runs
+    pub verify: Vec<String>,
+    /// Input parameters, passed to the script as `MIGRATE_PARAM_<NAME>`
+    pub params: Vec<Param>,
+    /// Unknown fields and invalid values, reported by `migrate check`
+    pub issues: Vec<HeaderIssue>,
+}
+
+/// A `RunIf` precondition, checked in the project root
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    /// `file-exists <path>`: the file or directory exists
+    FileExists(String),
+    /// `glob-matches <pattern>`: at least one path matches
+    GlobMatches(String),
+    /// Any other value: a shell command that must exit 0
+    Command(String),
+}
+
+impl Condition {
+    fn parse(value: &str) -> Option<Self> {
+        let (name, arg) = value.split_once(char::is_whitespace).unwrap_or((value, ""));
+        let arg = arg.trim().to_string();
+        match name {
+            "file-exists" | "glob-matches" if arg.is_empty() => None,
+            "file-exists" => Some(Condition::FileExists(arg)),
+            "glob-matches" => Some(Condition::GlobMatches(arg)),
+            _ if value.is_empty() => None,
+            _ => Some(Condition::Command(value.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::FileExists(path) => write!(f, "file-exists {}", path),
+            Condition::GlobMatches(pattern) => write!(f, "glob-matches {}", pattern),
+            Condition::Command(command) => write!(f, "{}", command),
+        }
+    }
+}
+
+/// A `Param` declaration: `name[=default] [required] [description]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub default: Option<String>,
+    /// The run fails before any migration starts if no value is supplied
+    pub required: bool,
+    pub description: Option<String>,
+}
+
+impl Param {
+    fn parse(value: &str) -> Option<Self> {
+        let mut words = value.split_whitespace();
+        let first = words.next()?;
+        let (name, default) = match first.split_once('=') {
+            Some((name, default)) => (name, Some(default.to_string())),
+            None => (first, None),
+        };
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return None;
+        }
+        let mut rest: Vec<&str> = words.collect();
+        let required = rest.first() == Some(&"required");
+        if required {
+            rest.remove(0);
+        }
+        Some(Param {
+            name: name.to_string(),
+            default,
+            required,
+            description: (!rest.is_empty()).then(|| rest.join(" ")),
+        })
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(default) = &self.default {
+            write!(f, "={}", default)?;
+        }
+        if self.required {
+            write!(f, " required")?;
+        }
+        if let Some(description) = &self.description {
+            write!(f, " {}", description)?;
+        }
+        Ok(())
+    }
+}
+
+/// A problem with one header line
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderIssue {
+    /// 1-based line number in the migration file
+    pub line: usize,
+    pub message: String,
+}
+
+const FIELDS: &[&str] = &[
+    "Description",
+    "Tags",
+    "Requires",
+    "Timeout",
+    "DependsOn",
+    "Env",
+    "RunIf",
+    "Verify",
+    "Param",
+];
+
+fn field_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^\s*(?:#|//)\s*([A-Za-z][A-Za-z-]*):(.*)$").unwrap())
+}
+
+impl Header {
+    /// Parse the header: `Key: value` comments (`#` or `//`) before the first blank line.
+    /// Field names are case-insensitive; list fields may be repeated and take comma- or
+    /// space-separated values.
+    pub fn parse(content: &str) -> Self {
+        let mut header = Header::default();
+        let mut started = false;
+
+        for (index, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                if started {
+                    break;
+                }
+                continue;
+            }
+            started = true;
+
+            let Some(captures) = field_pattern().captures(line) else {
+                continue;
+            };
+            let key = &captures[1];
+            let value = captures[2].trim();
+            let issue = |message: String| HeaderIssue {
+                line: index + 1,
+                message,
+            };
+            let Some(field) = FIELDS.iter().find(|f| f.eq_ignore_ascii_case(key)) else {
+                header
+                    .issues
+                    .push(issue(format!("unknown header field '{}'", key)));
+                continue;
+            };
+
+            match *field {
+                "Description" if !value.is_empty() => header.description = Some(value.to_string()),
+                "Description" => {}
+                "Tags" => header.tags.extend(split_list(value)),
+                "Requires" => header.requires.extend(split_list(value)),
+                "DependsOn" => header.depends_on.extend(split_list(value)),
+                "Timeout" => match parse_duration(value) {
+                    Some(timeout) => header.timeout = Some(timeout),
+                    None => header.issues.push(issue(format!(
+                        "invalid Timeout '{}' (expected e.g. 90s, 5m or 1h)",
+                        value
+                    ))),
+                },
+                "Env" => match value.split_once('=') {
+                    Some((name, val)) if !name.trim().is_empty() => header
+                        .env
+                        .push((name.trim().to_string(), val.trim().to_string())),
+                    _ => header.issues.push(issue(format!(
+                        "invalid Env '{}' (expected NAME=VALUE)",
+                        value
+                    ))),
+                },
+                "RunIf" => match Condition::parse(value) {
+                    Some(condition) => header.run_if.push(condition),
+                    None => header.issues.push(issue(format!(
+                        "invalid RunIf '{}' (expected file-exists <path>, glob-matches <pattern> or a command)",
+                        value
+                    ))),
+                },
+                "Verify" if !value.is_empty() => header.verify.push(value.to_string()),
+                "Verify" => header
+                    .issues
+                    .push(issue("empty Verify (expected a command)".to_string())),
+                "Param" => match Param::parse(value) {
+                    Some(param) => header.params.push(param),
+                    None => header.issues.push(issue(format!(
+                        "invalid Param '{}' (expected name[=default] [required] [description])",
+                        value
+                    ))),
+                },
+                _ => unreachable!(),
+            }
+        }
+        header
+    }
+}
+
+/// Selects migrations by their `Tags` header
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TagFilter {
+    /// Only migrations with at least one of these tags (all migrations if empty)
+    pub include: Vec<String>,
+    /// Never migrations with any of these tags
+    pub exclude: Vec<String>,
+}
+
+impl TagFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether a migration with `tags` is selected
+    pub fn matches(&self, tags: &[String]) -> bool {
+        (self.include.is_empty() || tags.iter().any(|t| self.include.contains(t)))
+            && !tags.iter().any(|t| self.exclude.contains(t))
+    }
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+/// Parse a duration like `500ms`, `90s`, `5m` or `1h` (a bare number is seconds)
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let n: u64 = number.parse().ok()?;
+    match unit.trim() {
+        "ms" => Some(Duration::from_millis(n)),
+        "" | "s" => Some(Duration::from_secs(n)),
+        "m" => Some(Duration::from_secs(n.checked_mul(60)?)),
+        "h" => Some(Duration::from_secs(n.checked_mul(3600)?)),
+        _ => None,
+    }
+}
+
+/// Format a duration in the largest unit that represents it exactly, as [`parse_duration`] reads it
+pub fn format_timeout(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    if !millis.is_multiple_of(1000) {
+        format!("{}ms", millis)
+    } else if millis > 0 && millis.is_multiple_of(3_600_000) {
+        format!("{}h", millis / 3_600_000)
+    } else if millis > 0 && millis.is_multiple_of(60_000) {
+        format!("{}m", millis / 60_000)
+    } else {
+        format!("{}s", millis / 1000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bash_header() {
+        let header = Header::parse(
+            "#!/usr/bin/env bash\n\
+             set -euo pipefail\n\
+             # Description: Add TypeScript configuration\n\
+             # Tags: typescript, tooling\n\
+             # tags: ci\n\
+             # Requires: node jq\n\
+             # Timeout: 5m\n\
+             # DependsOn: 1f72f-init\n\
+             # Env: NODE_ENV=production\n\
+             # Env: EMPTY=\n\
+             # RunIf: file-exists package.json\n\
+             # RunIf: grep -q react package.json\n\
+             # Verify: test -f tsconfig.json\n\
+             # Param: api_host required New API hostname\n\
+             # Param: team=platform\n\
+             \n\
+             # Note: not part of the header\n",
+        );
+        assert_eq!(
+            header.description.as_deref(),
+            Some("Add TypeScript configuration")
+        );
+        assert_eq!(header.tags, ["typescript", "tooling", "ci"]);
+        assert_eq!(header.requires, ["node", "jq"]);
+        assert_eq!(header.timeout, Some(Duration::from_secs(300)));
+        assert_eq!(header.depends_on, ["1f72f-init"]);
+        assert_eq!(
+            header.env,
+            [
+                ("NODE_ENV".to_string(), "production".to_string()),
+                ("EMPTY".to_string(), String::new())
+            ]
+        );
+        assert_eq!(
+            header.run_if,
+            [
+                Condition::FileExists("package.json".to_string()),
+                Condition::Command("grep -q react package.json".to_string())
+            ]
+        );
+        assert_eq!(header.run_if[1].to_string(), "grep -q react package.json");
+        assert_eq!(header.verify, ["test -f tsconfig.json"]);
+        assert_eq!(
+            header.params,
+            [
+                Param {
+                    name: "api_host".to_string(),
+                    default: None,
+                    required: true,
+                    description: Some("New API hostname".to_string()),
+                },
+                Param {
+                    name: "team".to_string(),
+                    default: Some("platform".to_string()),
+                    required: false,
+                    description: None,
+                }
+            ]
+        );
+        assert_eq!(
+            header.params[0].to_string(),
+            "api_host required New API hostname"
+        );
+        assert!(header.issues.is_empty());
+    }
+
+    #[test]
+    fn test_parse_slash_comments_and_issues() {
+        let header = Header::parse(
+            "#!/usr/bin/env node\n\
+             // Description: Node migration\n\
+             // Owner: platform\n\
+             // Timeout: soon\n\
+             // Env: missing-equals\n\
+             // RunIf: glob-matches\n\
+             // Param: 1st-choice\n",
+        );
+        assert_eq!(header.description.as_deref(), Some("Node migration"));
+        let issues: Vec<(usize, &str)> = header
+            .issues
+            .iter()
+            .map(|i| (i.line, i.message.as_str()))
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                (3, "unknown header field 'Owner'"),
+                (4, "invalid Timeout 'soon' (expected e.g. 90s, 5m or 1h)"),
+                (5, "invalid Env 'missing-equals' (expected NAME=VALUE)"),
+                (
+                    6,
+                    "invalid RunIf 'glob-matches' (expected file-exists <path>, glob-matches <pattern> or a command)"
+                ),
+                (
+                    7,
+                    "invalid Param '1st-choice' (expected name[=default] [required] [description])"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_template_headers() {
+        for template in crate::templates::TEMPLATES {
+            let header = Header::parse(&template.content.replace("{{DESCRIPTION}}", "Test"));
+            assert!(header.issues.is_empty(), "{}: {:?}", template.name, header);
+            if template.name != "toml" {
+                assert_eq!(
+                    header.description.as_deref(),
+                    Some("Test"),
+                    "{}",
+                    template.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_tag_filter() {
+        let tags = |list: &[&str]| list.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        let filter = TagFilter {
+            include: tags(&["frontend", "ci"]),
+            exclude: tags(&["slow"]),
+        };
+        assert!(filter.matches(&tags(&["frontend"])));
+        assert!(filter.matches(&tags(&["ci", "lint"])));
+        assert!(!filter.matches(&tags(&["frontend", "slow"])));
+        assert!(!filter.matches(&[]));
+
+        let exclude_only = TagFilter {
+            exclude: tags(&["ci-only"]),
+            ..Default::default()
+        };
+        assert!(exclude_only.matches(&[]));
+        assert!(!exclude_only.matches(&tags(&["ci-only"])));
+        assert!(TagFilter::default().matches(&tags(&["anything"])));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("5 m"), Some(Duration::from_secs(300)));
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration("3d"), None);
+        assert_eq!(parse_duration("9999999999999999h"), None);
+        assert_eq!(parse_duration("99999999999999999999s"), None);
+
+        for s in ["500ms", "90s", "5m", "2h", "0s"] {
+            assert_eq!(format_timeout(parse_duration(s).unwrap()), s);
+        }
+    }
+}
@@ -1,32 +1,128 @@
 pub mod baseline;
 pub mod commands;
+pub mod config;
+pub mod dag;
+pub mod declarative;
+pub mod embed;
+pub mod error;
 pub mod executor;
+pub mod header;
 pub mod loader;
+pub mod messages;
+pub mod migrator;
+pub mod outputs;
+pub mod params;
+pub mod render;
+pub mod reporter;
+pub mod run_report;
 pub mod state;
+pub mod store;
+pub mod structured;
 pub mod templates;
+pub mod verify;
 pub mod version;
+pub mod workspace;
 
+pub use error::MigrateError;
+pub use header::{Header, Param, TagFilter};
+pub use messages::Message;
+pub use migrator::Migrator;
+pub use outputs::Outputs;
+
+use anyhow::{bail, Context, Result};
 use chrono::{DateTime, Utc};
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
 
 /// Metadata for a discovered migration file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Migration {
     /// Migration ID (e.g., "1f72f-init")
     pub id: String,
     /// Version string (e.g., "1f72f")
     pub version: String,
-    /// Absolute path to the migration file
+    /// Absolute path to the migration file (empty for function migrations)
     pub file_path: PathBuf,
+    /// How the migration runs
+    pub kind: MigrationKind,
+    /// Metadata from the file's comment header
+    pub header: Header,
 }
 
-/// Record of an applied migration
-#[derive(Debug, Clone)]
+/// How a migration is executed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MigrationKind {
+    /// A script run with its interpreter
+    #[default]
+    Script,
+    /// A `.toml` file of built-in steps, executed in-process
+    Declarative,
+    /// A Rust function registered with [`Migrator::migration`]
+    Function,
+}
+
+impl MigrationKind {
+    /// The kind of a migration file, from its extension
+    pub fn from_path(path: &Path) -> Self {
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            MigrationKind::Declarative
+        } else {
+            MigrationKind::Script
+        }
+    }
+}
+
+impl Migration {
+    /// Whether this is a declarative (`.toml`) migration executed in-process
+    pub fn is_declarative(&self) -> bool {
+        self.kind == MigrationKind::Declarative
+    }
+
+    /// Whether this is a Rust function registered with [`Migrator::migration`]
+    pub fn is_function(&self) -> bool {
+        self.kind == MigrationKind::Function
+    }
+}
+
+/// Record of an applied migration. Everything but `id` and `applied_at` is audit
+/// information that older history files don't have.
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct AppliedMigration {
     /// Migration ID
     pub id: String,
     /// When the migration was applied
     pub applied_at: DateTime<Utc>,
+    /// How long the migration took to run
+    pub duration: Option<Duration>,
+    pub exit_code: Option<i32>,
+    /// Version of `migrate` that applied the migration
+    pub migrate_version: Option<String>,
+    /// OS user that ran `migrate`
+    pub user: Option<String>,
+    pub host: Option<String>,
+    /// Shared by all migrations applied in the same run
+    pub run_id: Option<String>,
+    /// Git commit of the project when the migration was applied
+    pub commit: Option<String>,
+    /// Recorded without changing anything: its `RunIf` condition was false or it exited with
+    /// [`executor::NOT_APPLICABLE_EXIT_CODE`]
+    pub not_applicable: bool,
+    /// Values the migration passed on to later migrations
+    pub outputs: Outputs,
+}
+
+impl AppliedMigration {
+    pub fn new(id: impl Into<String>, applied_at: DateTime<Utc>) -> Self {
+        Self {
+            id: id.into(),
+            applied_at,
+            ..Default::default()
+        }
+    }
 }
 
 /// Execution context passed via environment variables
@@ -40,6 +136,260 @@ pub struct ExecutionContext {
     pub migration_id: String,
     /// Whether this is a dry run
     pub dry_run: bool,
+    /// Template variables (project variables file plus `--set` values)
+    pub variables: render::Variables,
+    /// Values of the parameters the migration declares
+    pub params: Vec<(String, String)>,
+    /// Outputs of migrations applied before this one, by migration ID
+    pub earlier_outputs: BTreeMap<String, Outputs>,
+    /// Outputs set by in-process migrations
+    outputs: RefCell<Outputs>,
+    /// Files reported as changed by in-process migrations
+    touched_files: RefCell<Vec<PathBuf>>,
+    /// Receives output lines instead of stdout, when set
+    output: Option<OutputSink>,
+    /// Warnings, notes and follow-ups sent so far
+    messages: RefCell<Vec<Message>>,
+    /// Receives messages instead of stdout, when set
+    on_message: Option<MessageSink>,
+}
+
+type OutputCallback = Rc<dyn Fn(&str, Stream)>;
+
+/// Callback receiving a migration's output one line at a time
+#[derive(Clone)]
+pub struct OutputSink(OutputCallback);
+
+/// Which stream a line of migration output was written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl OutputSink {
+    pub fn new(f: impl Fn(&str, Stream) + 'static) -> Self {
+        Self(Rc::new(f))
+    }
+
+    pub fn emit(&self, line: &str, stream: Stream) {
+        (self.0)(line, stream)
+    }
+}
+
+impl fmt::Debug for OutputSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OutputSink")
+    }
+}
+
+type MessageCallback = Rc<dyn Fn(&Message)>;
+
+/// Callback receiving a migration's structured messages as they arrive
+#[derive(Clone)]
+pub struct MessageSink(MessageCallback);
+
+impl MessageSink {
+    pub fn new(f: impl Fn(&Message) + 'static) -> Self {
+        Self(Rc::new(f))
+    }
+
+    pub fn emit(&self, message: &Message) {
+        (self.0)(message)
+    }
+}
+
+impl fmt::Debug for MessageSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MessageSink")
+    }
+}
+
+impl ExecutionContext {
+    pub fn new(
+        project_root: PathBuf,
+        migrations_dir: PathBuf,
+        migration_id: String,
+        dry_run: bool,
+        variables: render::Variables,
+    ) -> Self {
+        Self {
+            project_root,
+            migrations_dir,
+            migration_id,
+            dry_run,
+            variables,
+            params: Vec::new(),
+            earlier_outputs: BTreeMap::new(),
+            outputs: RefCell::new(Outputs::new()),
+            touched_files: RefCell::new(Vec::new()),
+            output: None,
+            messages: RefCell::new(Vec::new()),
+            on_message: None,
+        }
+    }
+
+    /// Send output (step descriptions, `log` messages, script stdout and stderr) to `sink`
+    /// instead of printing it
+    pub fn with_output(mut self, sink: OutputSink) -> Self {
+        self.output = Some(sink);
+        self
+    }
+
+    /// Send messages (see [`ExecutionContext::message`]) to `sink` instead of printing them
+    pub fn with_messages(mut self, sink: MessageSink) -> Self {
+        self.on_message = Some(sink);
+        self
+    }
+
+    /// Set the values of the migration's parameters
+    pub fn with_params(mut self, params: Vec<(String, String)>) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// The value of a parameter, if it has one
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Set the outputs of migrations applied before this one
+    pub fn with_earlier_outputs(mut self, outputs: BTreeMap<String, Outputs>) -> Self {
+        self.earlier_outputs = outputs;
+        self
+    }
+
+    /// An output of a migration applied before this one
+    pub fn output_of(&self, id: &str, key: &str) -> Option<&str> {
+        self.earlier_outputs
+            .get(id)
+            .and_then(|outputs| outputs.get(key))
+            .map(|v| v.as_str())
+    }
+
+    /// Pass a value on to later migrations, like a script writing `key=value` to
+    /// `MIGRATE_OUTPUT`
+    pub fn set_output(&self, key: impl Into<String>, value: impl Into<String>) -> Result<()> {
+        let key = key.into();
+        if !outputs::is_valid_key(&key) {
+            bail!("Invalid output key '{}'", key);
+        }
+        self.outputs.borrow_mut().insert(key, value.into());
+        Ok(())
+    }
+
+    /// Where output goes, if it is captured rather than printed
+    pub fn output(&self) -> Option<&OutputSink> {
+        self.output.as_ref()
+    }
+
+    /// Resolve a path relative to the project root, rejecting paths that escape it
+    pub fn path(&self, relative: &str) -> Result<PathBuf> {
+        declarative::resolve(&self.project_root, relative)
+    }
+
+    /// The migration's asset directory (`<migrations>/<id>/`)
+    pub fn asset_dir(&self) -> PathBuf {
+        self.migrations_dir.join(&self.migration_id)
+    }
+
+    /// Resolve a path inside the asset directory
+    pub fn asset(&self, relative: &str) -> Result<PathBuf> {
+        declarative::resolve(&self.asset_dir(), relative)
+    }
+
+    /// Read a file from the asset directory
+    pub fn read_asset(&self, relative: &str) -> Result<Vec<u8>> {
+        let path = self.asset(relative)?;
+        std::fs::read(&path).with_context(|| format!("Failed to read asset {}", path.display()))
+    }
+
+    /// Write a project file (creating parent directories), unless this is a dry run
+    pub fn write(&self, relative: &str, contents: impl AsRef<[u8]>) -> Result<()> {
+        let path = self.path(relative)?;
+        self.log(format_args!(
+            "{}write {}",
+            if self.dry_run { "would " } else { "" },
+            relative
+        ));
+        if !self.dry_run {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, contents)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        self.touch(path);
+        Ok(())
+    }
+
+    /// Run a built-in declarative step (copy, replace, edit, render, ...)
+    pub fn run_step(&self, step: &declarative::Step) -> Result<()> {
+        let env = declarative::StepEnv {
+            project_root: &self.project_root,
+            asset_dir: self.asset_dir(),
+            dry_run: self.dry_run,
+            variables: self.variables.clone(),
+        };
+        self.log(format_args!(
+            "{}{}",
+            if self.dry_run { "would " } else { "" },
+            step.describe()
+        ));
+        for path in step.apply(&env)? {
+            self.touch(path);
+        }
+        Ok(())
+    }
+
+    /// Report a file as changed, for the run summary
+    pub fn touch(&self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        let mut touched = self.touched_files.borrow_mut();
+        if !touched.contains(&path) {
+            touched.push(path);
+        }
+    }
+
+    /// Emit a progress message for this migration
+    pub fn log(&self, message: impl Display) {
+        match &self.output {
+            Some(sink) => sink.emit(&message.to_string(), Stream::Stdout),
+            None => println!("  {}", message),
+        }
+    }
+
+    /// Send a structured message to the runner, like a script writing to
+    /// `MIGRATE_MESSAGE_FD`. `Touched` paths are relative to the project root.
+    pub fn message(&self, message: Message) {
+        if let Message::Touched { path } = &message {
+            self.touch(self.project_root.join(path));
+            return;
+        }
+        match &self.on_message {
+            Some(sink) => sink.emit(&message),
+            None => println!("  {}", message),
+        }
+        if message.is_summarized() {
+            self.messages.borrow_mut().push(message);
+        }
+    }
+
+    pub(crate) fn take_messages(&self) -> Vec<Message> {
+        self.messages.take()
+    }
+
+    pub(crate) fn take_touched_files(&self) -> Vec<PathBuf> {
+        self.touched_files.take()
+    }
+
+    pub(crate) fn take_outputs(&self) -> Outputs {
+        self.outputs.take()
+    }
 }
 
 /// Result of executing a migration
@@ -51,4 +401,10 @@ pub struct ExecutionResult {
     pub exit_code: i32,
     /// Error message if any
     pub error: Option<String>,
+    /// Files the migration reported changing (via built-in steps or `migrate helper`)
+    pub touched_files: Vec<PathBuf>,
+    /// Values passed on to later migrations
+    pub outputs: Outputs,
+    /// Warnings, notes and follow-ups the migration sent
+    pub messages: Vec<Message>,
 }
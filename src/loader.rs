@@ -1,9 +1,12 @@
 use anyhow::{Context, Result};
 use glob::glob;
+use std::fs;
 use std::path::Path;
 
+use crate::declarative::DeclarativeMigration;
+use crate::verify::VERIFY_SCRIPT_SUFFIX;
 use crate::version::is_valid_version;
-use crate::Migration;
+use crate::{Header, Migration, MigrationKind};
 
 /// Discover all migrations in the given directory.
 /// Migrations must match the pattern XXXXX-name.ext where XXXXX is a 5-char base36 version
@@ -20,11 +23,16 @@ pub fn discover_migrations(dir: &Path) -> Result<Vec<Migration>> {
         .filter(|path| path.is_file())
         .filter_map(|path| {
             let filename = path.file_name()?.to_str()?;
+            if filename.ends_with(VERIFY_SCRIPT_SUFFIX) {
+                return None;
+            }
             let version = extract_version(filename)?;
             let id = extract_id(filename);
             Some(Migration {
                 id,
                 version,
+                header: read_header(&path),
+                kind: MigrationKind::from_path(&path),
                 file_path: path,
             })
         })
@@ -64,6 +72,19 @@ pub fn extract_id(filename: &str) -> String {
     }
 }
 
+/// Read a migration file's header. Declarative migrations may also give their
+/// description as a `description` key.
+pub fn read_header(path: &Path) -> Header {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    let mut header = Header::parse(&content);
+    if header.description.is_none() && path.extension().is_some_and(|ext| ext == "toml") {
+        header.description = toml::from_str::<DeclarativeMigration>(&content)
+            .ok()
+            .and_then(|d| d.description);
+    }
+    header
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -87,4 +108,29 @@ mod tests {
         assert_eq!(extract_id("00000-add-config.ts"), "00000-add-config");
         assert_eq!(extract_id("zzzzz-no-extension"), "zzzzz-no-extension");
     }
+
+    #[test]
+    fn test_discover_reads_headers() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("1f72f-init.sh"),
+            "#!/usr/bin/env bash\nset -euo pipefail\n# Description: Set up the project\n# Tags: setup\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("1f730-steps.toml"),
+            "description = \"Steps\"\nsteps = []\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("1f72f-init.verify.sh"), "#!/bin/sh\n").unwrap();
+
+        let migrations = discover_migrations(dir.path()).unwrap();
+        assert_eq!(migrations.len(), 2);
+        assert_eq!(
+            migrations[0].header.description.as_deref(),
+            Some("Set up the project")
+        );
+        assert_eq!(migrations[0].header.tags, ["setup"]);
+        assert_eq!(migrations[1].header.description.as_deref(), Some("Steps"));
+    }
 }
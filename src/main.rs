@@ -1,8 +1,18 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand};
+use regex::Regex;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
 use migrate::commands;
+use migrate::config::Location;
+use migrate::reporter::Format;
+use migrate::run_report::{parse_report_spec, write_report, ReportSpec};
+use migrate::structured::DataEdit;
+use migrate::workspace::discover_workspaces;
+use migrate::{Migrator, TagFilter};
 
 #[derive(Parser)]
 #[command(name = "migrate", version, about = "Generic file migration tool")]
@@ -15,6 +25,22 @@ struct Cli {
     #[arg(short = 'm', long, default_value = "migrations")]
     migrations: PathBuf,
 
+    /// State file recording applied migrations, relative to the root (default: <migrations>/history)
+    #[arg(long)]
+    state: Option<PathBuf>,
+
+    /// Where to keep state when --state is not given
+    #[arg(long, value_enum)]
+    state_location: Option<Location>,
+
+    /// Run status, up or check in every workspace (directory with its own migrations) below the root
+    #[arg(long)]
+    workspaces: bool,
+
+    /// Only workspaces whose path relative to the root matches this glob (repeatable; implies --workspaces)
+    #[arg(long, value_name = "GLOB")]
+    workspace: Vec<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -22,7 +48,44 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Show migration status
-    Status,
+    Status {
+        #[command(flatten)]
+        tags: TagArgs,
+    },
+
+    /// Exit with an error if any migrations are pending
+    Check {
+        #[command(flatten)]
+        tags: TagArgs,
+    },
+
+    /// Show applied migrations as a timeline grouped by run
+    Log {
+        /// Only migrations applied at or after this time (YYYY-MM-DD, YYYY-MM-DD HH:MM[:SS] or RFC 3339; UTC)
+        #[arg(long, value_name = "TIME", value_parser = commands::log::parse_since)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only migrations applied before this time (a bare date includes that whole day)
+        #[arg(long, value_name = "TIME", value_parser = commands::log::parse_until)]
+        until: Option<DateTime<Utc>>,
+
+        /// Only migrations whose ID matches this regex
+        #[arg(long, value_name = "REGEX")]
+        grep: Option<Regex>,
+    },
+
+    /// Show details of one migration: file, creation time, description, assets and apply record
+    Show {
+        /// Migration ID or version
+        id: String,
+    },
+
+    /// Re-run the postcondition checks of applied migrations
+    Verify {
+        /// Check `Verify` headers, `[[verify]]` assertions and `<id>.verify.sh` scripts
+        #[arg(long, required = true)]
+        postconditions: bool,
+    },
 
     /// Apply pending migrations
     Up {
@@ -37,6 +100,46 @@ enum Commands {
         /// Keep migration files when using --baseline (don't delete)
         #[arg(long)]
         keep: bool,
+
+        /// Template variable for rendered assets, or value for a migration `Param` (repeatable)
+        #[arg(long = "set", value_name = "KEY=VALUE", value_parser = commands::helper::parse_assignment)]
+        vars: Vec<(String, String)>,
+
+        /// TOML file of migration parameter values (overridden by --set)
+        #[arg(long = "params", value_name = "FILE")]
+        params_file: Option<PathBuf>,
+
+        /// Apply to every target root listed in a file or matching a glob, each with its own state
+        #[arg(long, value_name = "FILE_OR_GLOB", conflicts_with = "baseline")]
+        targets: Option<String>,
+
+        /// Number of targets to migrate concurrently (with --targets)
+        #[arg(long, default_value_t = 1, requires = "targets")]
+        parallel: usize,
+
+        /// Stop starting new targets after the first failure (with --targets)
+        #[arg(long, requires = "targets")]
+        fail_fast: bool,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = Format::Human, conflicts_with = "targets")]
+        format: Format,
+
+        /// Also write a report of the run: junit=<path> or markdown=<path> (repeatable)
+        #[arg(long, value_name = "KIND=PATH", value_parser = parse_report_spec, conflicts_with = "targets")]
+        report: Vec<ReportSpec>,
+
+        #[command(flatten)]
+        tags: TagArgs,
+
+        /// Run up to N migrations at once when their dependencies allow it
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 1,
+            conflicts_with = "targets"
+        )]
+        jobs: usize,
     },
 
     /// Create a new migration
@@ -44,7 +147,7 @@ enum Commands {
         /// Migration name (e.g., "add-config")
         name: Option<String>,
 
-        /// Template to use (bash, ts, python, node, ruby)
+        /// Template to use (bash, ts, python, node, ruby, toml)
         #[arg(short = 't', long, default_value = "bash")]
         template: String,
 
@@ -57,6 +160,20 @@ enum Commands {
         list_templates: bool,
     },
 
+    /// Record a new migration from manual edits (or an existing commit)
+    Record {
+        /// Migration name (e.g., "rename-config")
+        name: String,
+
+        /// Use the changes introduced by a git revision instead of recording edits interactively
+        #[arg(long, value_name = "REV")]
+        from_git: Option<String>,
+
+        /// Migration description
+        #[arg(short = 'd', long)]
+        description: Option<String>,
+    },
+
     /// Create a baseline at a specific version (removes old migration files)
     Baseline {
         /// Version to baseline at (e.g., "1fb2g")
@@ -74,21 +191,241 @@ enum Commands {
         #[arg(long)]
         keep: bool,
     },
+
+    /// Helpers for use inside script migrations
+    Helper {
+        #[command(subcommand)]
+        command: HelperCommand,
+    },
+}
+
+/// Select migrations by their `Tags` header
+#[derive(Args)]
+struct TagArgs {
+    /// Only migrations with this tag (repeatable)
+    #[arg(long = "tag", value_name = "TAG")]
+    include: Vec<String>,
+
+    /// Leave out migrations with this tag (repeatable)
+    #[arg(long = "exclude-tag", value_name = "TAG")]
+    exclude: Vec<String>,
+}
+
+impl TagArgs {
+    fn filter(&self) -> TagFilter {
+        TagFilter {
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+        }
+    }
+}
+
+/// Parameter values from `--params` followed by `--set`, so `--set` wins
+fn params(file: Option<&Path>, vars: &[(String, String)]) -> Result<Vec<(String, String)>> {
+    let mut params = match file {
+        Some(file) => migrate::params::load_file(file)?,
+        None => Vec::new(),
+    };
+    params.extend(vars.iter().cloned());
+    Ok(params)
+}
+
+/// Helpers read MIGRATE_PROJECT_ROOT, MIGRATE_MIGRATIONS_DIR, MIGRATE_ID and MIGRATE_DRY_RUN
+/// from the running migration; paths are relative to the project root (or the asset directory).
+#[derive(Subcommand)]
+enum HelperCommand {
+    /// Copy a file or directory from the migration's asset directory into the project
+    CopyAsset {
+        /// Path within the asset directory
+        asset: String,
+        /// Destination in the project (defaults to the same relative path)
+        dest: Option<String>,
+    },
+
+    /// Append a line to a file unless it is already present
+    EnsureLine { file: String, line: String },
+
+    /// Replace regex matches in every file matching a glob
+    Replace {
+        /// Glob of files to edit (e.g. "src/**/*.ts")
+        files: String,
+        pattern: String,
+        replacement: String,
+
+        /// Treat pattern and replacement as plain text instead of a regex
+        #[arg(long)]
+        literal: bool,
+    },
+
+    /// Render an asset template (or a directory of *.tmpl files) into the project
+    RenderTemplate {
+        /// Template file or directory within the asset directory
+        template: String,
+        /// Destination in the project
+        dest: String,
+
+        /// Template variable, overriding MIGRATE_VARS from the running migration (repeatable)
+        #[arg(long = "set", value_name = "KEY=VALUE", value_parser = commands::helper::parse_assignment)]
+        vars: Vec<(String, String)>,
+    },
+
+    /// Set a key in a JSON, YAML or TOML file (value is parsed as JSON, else used as a string)
+    #[command(visible_alias = "json-set")]
+    Set {
+        file: String,
+        /// Dotted path (settings.debug) or JSON Pointer (/settings/debug)
+        key: String,
+        value: String,
+    },
+
+    /// Delete a key from a JSON, YAML or TOML file
+    #[command(visible_alias = "json-delete")]
+    Delete { file: String, key: String },
+
+    /// Deep-merge a JSON object into a JSON, YAML or TOML file
+    Merge {
+        file: String,
+        /// JSON object to merge (or @path to read it from a file)
+        value: String,
+        /// Key to merge into (defaults to the document root)
+        #[arg(long, default_value = "")]
+        key: String,
+    },
+
+    /// Rename (move) a key in a JSON, YAML or TOML file
+    RenameKey {
+        file: String,
+        from: String,
+        to: String,
+    },
+
+    /// Append a value to an array in a JSON, YAML or TOML file
+    Append {
+        file: String,
+        key: String,
+        value: String,
+    },
+
+    /// Apply an RFC 6902 JSON Patch (inline JSON or @path)
+    JsonPatch { file: String, patch: String },
+
+    /// Apply an RFC 7386 JSON Merge Patch (inline JSON or @path)
+    MergePatch { file: String, patch: String },
+}
+
+impl Cli {
+    /// A migrator for a project root with the selected migrations directory and state location
+    fn migrator(&self, root: &Path) -> Migrator {
+        let mut migrator = Migrator::new(root).migrations_dir(&self.migrations);
+        if let Some(location) = self.state_location {
+            migrator = migrator.state_location(location);
+        }
+        if let Some(state) = &self.state {
+            migrator = migrator.state_file(state);
+        }
+        match &self.command {
+            Commands::Status { tags } | Commands::Check { tags } | Commands::Up { tags, .. } => {
+                migrator.tag_filter(tags.filter())
+            }
+            _ => migrator,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(migrate::error::exit_code(&e) as u8)
+        }
+    }
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+fn run(cli: Cli) -> Result<()> {
+    if cli.workspaces || !cli.workspace.is_empty() {
+        return run_workspaces(&cli);
+    }
+    let migrator = cli.migrator(&cli.root);
 
     match cli.command {
-        Commands::Status => {
-            commands::status::run(&cli.root, &cli.migrations)?;
+        Commands::Status { .. } => {
+            commands::status::run(&migrator)?;
+        }
+        Commands::Check { .. } => {
+            commands::check::run(&migrator)?;
+        }
+        Commands::Log { since, until, grep } => {
+            let options = commands::log::LogOptions { since, until, grep };
+            commands::log::run(&migrator, &options)?;
+        }
+        Commands::Show { id } => {
+            commands::show::run(&migrator, &id)?;
+        }
+        Commands::Verify { .. } => {
+            commands::verify::run(&migrator)?;
+        }
+        Commands::Up {
+            dry_run,
+            vars,
+            targets: Some(spec),
+            parallel,
+            fail_fast,
+            tags,
+            params_file,
+            ..
+        } => {
+            if cli.state.is_some() {
+                anyhow::bail!(
+                    "--state cannot be combined with --targets; each target keeps its own state"
+                );
+            }
+            if cli.state_location == Some(Location::Migrations) {
+                anyhow::bail!(
+                    "--state-location migrations cannot be combined with --targets; \
+                     every target would share the state in the migrations directory"
+                );
+            }
+            let targets = commands::targets::resolve_targets(&spec, migrator.project_root())?;
+            let options = commands::targets::TargetOptions {
+                dry_run,
+                parallel,
+                fail_fast,
+                variables: &vars,
+                params: &params(params_file.as_deref(), &vars)?,
+                state_location: cli.state_location,
+                tags: tags.filter(),
+            };
+            commands::targets::run(
+                migrator.migrations_path(),
+                &targets,
+                migrator.project_root(),
+                &options,
+            )?;
         }
         Commands::Up {
             dry_run,
             baseline,
             keep,
+            vars,
+            format,
+            report,
+            jobs,
+            params_file,
+            ..
         } => {
-            commands::up::run(&cli.root, &cli.migrations, dry_run, baseline, keep)?;
+            let options = commands::up::UpOptions {
+                dry_run,
+                baseline,
+                keep,
+                variables: &vars,
+                params: &params(params_file.as_deref(), &vars)?,
+                format,
+                reports: &report,
+                suites: None,
+                jobs,
+            };
+            commands::up::run(migrator, &options)?;
         }
         Commands::Create {
             name,
@@ -105,22 +442,155 @@ fn main() -> Result<()> {
                 list_templates,
             )?;
         }
+        Commands::Record {
+            name,
+            from_git,
+            description,
+        } => {
+            commands::record::run(
+                &cli.root,
+                &cli.migrations,
+                &name,
+                from_git.as_deref(),
+                description.as_deref(),
+            )?;
+        }
         Commands::Baseline {
             version,
             summary,
             dry_run,
             keep,
         } => {
-            commands::baseline::run(
-                &cli.root,
-                &cli.migrations,
-                &version,
-                summary.as_deref(),
+            commands::baseline::run(migrator, &version, summary.as_deref(), dry_run, keep)?;
+        }
+        Commands::Helper { command } => run_helper(command)?,
+    }
+
+    Ok(())
+}
+
+/// Error for commands that can't run with `--workspaces`
+const WORKSPACES_UNSUPPORTED: &str =
+    "--workspaces only supports status, up (without --targets) and check";
+
+fn run_workspaces(cli: &Cli) -> Result<()> {
+    match &cli.command {
+        Commands::Status { .. } | Commands::Check { .. } | Commands::Up { targets: None, .. } => {}
+        _ => anyhow::bail!(WORKSPACES_UNSUPPORTED),
+    }
+    if cli.state.is_some() {
+        anyhow::bail!(
+            "--state cannot be combined with --workspaces; each workspace keeps its own state"
+        );
+    }
+
+    let root = std::env::current_dir()?.join(&cli.root);
+    let workspaces = discover_workspaces(&root, &cli.migrations, &cli.workspace)?;
+    // Every workspace's run goes into one report per --report, written at the end
+    let suites = RefCell::new(Vec::new());
+    // Status and check only print for people
+    let format = match &cli.command {
+        Commands::Up { format, .. } => *format,
+        _ => Format::Human,
+    };
+    let result = commands::workspaces::run(&root, &workspaces, format, |workspace| {
+        let migrator = cli.migrator(workspace);
+        match &cli.command {
+            Commands::Status { .. } => commands::status::run(&migrator),
+            Commands::Check { .. } => commands::check::run(&migrator),
+            Commands::Up {
                 dry_run,
+                baseline,
                 keep,
-            )?;
+                vars,
+                format,
+                report,
+                jobs,
+                params_file,
+                targets: None,
+                ..
+            } => {
+                let options = commands::up::UpOptions {
+                    dry_run: *dry_run,
+                    baseline: *baseline,
+                    keep: *keep,
+                    variables: vars,
+                    params: &params(params_file.as_deref(), vars)?,
+                    format: *format,
+                    reports: report,
+                    suites: Some(&suites),
+                    jobs: *jobs,
+                };
+                commands::up::run(migrator, &options)
+            }
+            _ => anyhow::bail!(WORKSPACES_UNSUPPORTED),
+        }
+    });
+
+    if let Commands::Up {
+        dry_run, report, ..
+    } = &cli.command
+    {
+        for spec in report {
+            write_report(spec, *dry_run, &suites.borrow())?;
         }
     }
+    result
+}
 
-    Ok(())
+fn run_helper(command: HelperCommand) -> Result<()> {
+    use commands::helper::{edit, parse_json_arg, parse_value};
+
+    match command {
+        HelperCommand::CopyAsset { asset, dest } => {
+            commands::helper::copy_asset(&asset, dest.as_deref())
+        }
+        HelperCommand::EnsureLine { file, line } => commands::helper::ensure_line(&file, &line),
+        HelperCommand::Replace {
+            files,
+            pattern,
+            replacement,
+            literal,
+        } => commands::helper::replace(&files, &pattern, &replacement, literal),
+        HelperCommand::RenderTemplate {
+            template,
+            dest,
+            vars,
+        } => commands::helper::render_template(&template, &dest, &vars),
+        HelperCommand::Set { file, key, value } => edit(
+            &file,
+            DataEdit::Set {
+                key,
+                value: parse_value(&value),
+            },
+        ),
+        HelperCommand::Delete { file, key } => edit(&file, DataEdit::Delete { key }),
+        HelperCommand::Merge { file, value, key } => edit(
+            &file,
+            DataEdit::Merge {
+                key,
+                value: parse_json_arg(&value)?,
+            },
+        ),
+        HelperCommand::RenameKey { file, from, to } => edit(&file, DataEdit::Rename { from, to }),
+        HelperCommand::Append { file, key, value } => edit(
+            &file,
+            DataEdit::Append {
+                key,
+                value: parse_value(&value),
+            },
+        ),
+        HelperCommand::JsonPatch { file, patch } => edit(
+            &file,
+            DataEdit::Patch {
+                patch: parse_json_arg(&patch)?,
+            },
+        ),
+        HelperCommand::MergePatch { file, patch } => edit(
+            &file,
+            DataEdit::MergePatch {
+                patch: parse_json_arg(&patch)?,
+            },
+        ),
+    }
 }
@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+
+/// Environment variable holding the file descriptor a script writes JSON-lines messages to
+pub const MESSAGE_FD_ENV: &str = "MIGRATE_MESSAGE_FD";
+
+/// The descriptor scripts receive messages on; a single digit so `>&3` works in any shell
+pub const MESSAGE_FD: i32 = 3;
+
+/// A structured message from a running migration, one JSON object per line with its kind
+/// in `"type"`, e.g. `{"type": "warning", "message": "..."}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Message {
+    /// How far along the migration is
+    Progress {
+        percent: u8,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+    Warning {
+        message: String,
+    },
+    /// Something the user should know, shown again at the end of the run
+    Note {
+        message: String,
+    },
+    /// A file the migration changed, relative to the project root
+    Touched {
+        path: PathBuf,
+    },
+    /// Something the migration could not do and a person has to finish
+    FollowUp {
+        message: String,
+    },
+}
+
+impl Message {
+    /// Parse a line written to `MIGRATE_MESSAGE_FD`. A line that isn't a valid message
+    /// becomes a warning, so a mistake in a script is noticed without failing it.
+    pub fn parse(line: &str) -> Self {
+        match serde_json::from_str(line) {
+            Ok(Message::Progress { percent, message }) => Message::Progress {
+                percent: percent.min(100),
+                message,
+            },
+            Ok(message) => message,
+            Err(e) => Message::Warning {
+                message: format!("invalid message '{}': {}", line, e),
+            },
+        }
+    }
+
+    /// The message's `"type"`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Message::Progress { .. } => "progress",
+            Message::Warning { .. } => "warning",
+            Message::Note { .. } => "note",
+            Message::Touched { .. } => "touched",
+            Message::FollowUp { .. } => "follow-up",
+        }
+    }
+
+    /// The message's text, if it has any
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Message::Progress { message, .. } => message.as_deref(),
+            Message::Warning { message }
+            | Message::Note { message }
+            | Message::FollowUp { message } => Some(message),
+            Message::Touched { .. } => None,
+        }
+    }
+
+    /// Whether the message is repeated in the summary at the end of a run
+    pub fn is_summarized(&self) -> bool {
+        matches!(
+            self,
+            Message::Warning { .. } | Message::Note { .. } | Message::FollowUp { .. }
+        )
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Message::Progress {
+                percent,
+                message: Some(message),
+            } => write!(f, "[{:>3}%] {}", percent, message),
+            Message::Progress { percent, .. } => write!(f, "[{:>3}%]", percent),
+            Message::Warning { message } => write!(f, "warning: {}", message),
+            Message::Note { message } => write!(f, "note: {}", message),
+            Message::Touched { path } => write!(f, "touched {}", path.display()),
+            Message::FollowUp { message } => write!(f, "follow-up: {}", message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Message::parse(r#"{"type": "progress", "percent": 140, "message": "copying"}"#),
+            Message::Progress {
+                percent: 100,
+                message: Some("copying".to_string())
+            }
+        );
+        assert_eq!(
+            Message::parse(r#"{"type": "follow-up", "message": "rotate the API key"}"#),
+            Message::FollowUp {
+                message: "rotate the API key".to_string()
+            }
+        );
+        assert_eq!(
+            Message::parse(r#"{"type": "touched", "path": "src/app.ts"}"#).to_string(),
+            "touched src/app.ts"
+        );
+        let Message::Warning { message } = Message::parse("50%") else {
+            panic!("expected a warning");
+        };
+        assert!(message.starts_with("invalid message '50%'"), "{}", message);
+    }
+
+    #[test]
+    fn test_display() {
+        let progress = Message::Progress {
+            percent: 5,
+            message: None,
+        };
+        assert_eq!(progress.to_string(), "[  5%]");
+        assert!(!progress.is_summarized());
+        let note = Message::Note {
+            message: "restart the dev server".to_string(),
+        };
+        assert_eq!(note.to_string(), "note: restart the dev server");
+        assert!(note.is_summarized());
+    }
+}
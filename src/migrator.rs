@@ -0,0 +1,1742 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::baseline::{delete_baselined_migrations, validate_baseline, version_lte, DeletedItem};
+use crate::config::{Config, Location};
+use crate::dag;
+use crate::executor::{
+    check_condition, execute, execute_fn, MigrationFn, NOT_APPLICABLE_EXIT_CODE,
+};
+use crate::loader::{discover_migrations, extract_version};
+use crate::params::{self, Prompt};
+use crate::render::{load_variables, Variables};
+use crate::state::{get_current_version, get_pending, get_target_version, Baseline, HistoryState};
+use crate::store::StateStore;
+use crate::verify::verify_result;
+use crate::{
+    AppliedMigration, ExecutionContext, ExecutionResult, Message, MessageSink, MigrateError,
+    Migration, MigrationKind, OutputSink, Outputs, Param, Stream, TagFilter,
+};
+
+/// Default migrations directory, relative to the project root
+pub const DEFAULT_MIGRATIONS_DIR: &str = "migrations";
+
+/// Progress notifications emitted while applying migrations
+#[derive(Debug)]
+pub enum Progress<'a> {
+    /// A migration is about to run (or be previewed)
+    Started(&'a Migration),
+    /// A migration was not run because this is a dry run; declarative and function
+    /// migrations include the result of previewing them
+    Skipped {
+        migration: &'a Migration,
+        preview: Option<&'a ExecutionResult>,
+        duration: Duration,
+    },
+    /// A line of output from a running migration: step descriptions, `log` messages, or a
+    /// script's stdout and stderr. Output is only captured when a progress callback is set.
+    Output {
+        migration: &'a Migration,
+        line: &'a str,
+        stream: Stream,
+    },
+    /// A structured message from a running migration, sent with [`ExecutionContext::message`]
+    /// or through `MIGRATE_MESSAGE_FD`. Touched files are reported with the result instead.
+    Message {
+        migration: &'a Migration,
+        message: &'a Message,
+    },
+    /// A migration succeeded and was recorded in history
+    Completed {
+        migration: &'a Migration,
+        result: &'a ExecutionResult,
+        duration: Duration,
+    },
+    /// A migration's `RunIf` condition did not hold, it exited with
+    /// [`NOT_APPLICABLE_EXIT_CODE`], or a dependency is not applicable. Outside dry runs it
+    /// is recorded in history as not applicable so it won't run again.
+    NotApplicable {
+        migration: &'a Migration,
+        reason: &'a str,
+        duration: Duration,
+    },
+    /// A migration failed; no further migrations are run
+    Failed {
+        migration: &'a Migration,
+        result: &'a ExecutionResult,
+        duration: Duration,
+    },
+}
+
+type ProgressCallback = Rc<dyn Fn(Progress<'_>)>;
+
+/// Runs migrations for a project without going through the CLI.
+///
+/// ```no_run
+/// use migrate::Migrator;
+///
+/// let report = Migrator::new("path/to/project").dry_run(true).apply()?;
+/// for outcome in &report.outcomes {
+///     println!("{}", outcome.migration.id);
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct Migrator {
+    project_root: PathBuf,
+    migrations_dir: PathBuf,
+    dry_run: bool,
+    baseline_on_success: bool,
+    keep_files: bool,
+    variables: Vec<(String, Value)>,
+    /// Supplied values for migration parameters
+    params: BTreeMap<String, String>,
+    prompt: Option<Rc<Prompt<'static>>>,
+    on_progress: Option<ProgressCallback>,
+    state_store: Option<Box<dyn StateStore>>,
+    state_file: Option<PathBuf>,
+    state_location: Option<Location>,
+    /// In-process migrations registered with [`Migrator::migration`]
+    functions: Vec<(String, MigrationFn)>,
+    jobs: usize,
+    tags: TagFilter,
+}
+
+/// Snapshot of a project's migration state
+#[derive(Debug)]
+pub struct Status {
+    /// Migrations found in the migrations directory, sorted by version
+    pub available: Vec<Migration>,
+    /// Migrations recorded in history, in the order they were applied
+    pub applied: Vec<AppliedMigration>,
+    /// The current baseline, if any
+    pub baseline: Option<Baseline>,
+    /// Where the state was read from (e.g. the history file path)
+    pub state: String,
+    /// Migrations that have not been applied and are not covered by the baseline
+    pub pending: Vec<Migration>,
+    /// Unapplied migrations left out by the tag filter; they are not pending
+    pub filtered: Vec<Migration>,
+    /// Version of the most recently applied migration that still exists
+    pub current_version: Option<String>,
+    /// Version of the latest available migration
+    pub target_version: Option<String>,
+}
+
+impl Status {
+    /// Whether an applied migration is at or before the baseline version
+    pub fn is_baselined(&self, migration: &AppliedMigration) -> bool {
+        self.baseline.as_ref().is_some_and(|b| {
+            extract_version(&migration.id).is_some_and(|v| version_lte(&v, &b.version))
+        })
+    }
+}
+
+/// Migrations that `apply` would run, in order
+#[derive(Debug)]
+pub struct Plan {
+    pub pending: Vec<Migration>,
+}
+
+/// Result of running (or previewing) a single migration
+#[derive(Debug)]
+pub struct MigrationOutcome {
+    pub migration: Migration,
+    /// Execution result; `None` for script migrations skipped in a dry run
+    pub result: Option<ExecutionResult>,
+    /// When the migration was recorded in history (not set for dry runs or failures)
+    pub applied_at: Option<DateTime<Utc>>,
+    /// How long the migration (or its preview) took to run
+    pub duration: Duration,
+    /// The migration was not applicable (see [`Progress::NotApplicable`])
+    pub not_applicable: bool,
+}
+
+/// Result of `Migrator::apply`
+#[derive(Debug)]
+pub struct ApplyReport {
+    pub dry_run: bool,
+    /// Migrations that succeeded (or were previewed), in order
+    pub outcomes: Vec<MigrationOutcome>,
+    /// The migration that failed and stopped the run, if any
+    pub failed: Option<MigrationOutcome>,
+    /// Baseline created after applying, when enabled with `baseline_on_success`
+    pub baseline: Option<BaselineReport>,
+}
+
+impl ApplyReport {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_none()
+    }
+
+    /// Files reported as changed, paired with the ID of the migration that changed them
+    pub fn touched_files(&self) -> Vec<(&str, &Path)> {
+        self.outcomes
+            .iter()
+            .filter_map(|o| o.result.as_ref().map(|r| (o.migration.id.as_str(), r)))
+            .flat_map(|(id, r)| r.touched_files.iter().map(move |p| (id, p.as_path())))
+            .collect()
+    }
+
+    /// Warnings, notes and follow-ups sent by migrations, paired with the migration's ID
+    pub fn messages(&self) -> Vec<(&str, &Message)> {
+        self.outcomes
+            .iter()
+            .chain(&self.failed)
+            .filter_map(|o| o.result.as_ref().map(|r| (o.migration.id.as_str(), r)))
+            .flat_map(|(id, r)| r.messages.iter().map(move |m| (id, m)))
+            .collect()
+    }
+}
+
+/// A migration covered by a baseline
+#[derive(Debug)]
+pub struct CoveredMigration {
+    pub migration: Migration,
+    /// Whether the migration has an asset directory next to it
+    pub has_asset_dir: bool,
+}
+
+/// Result of creating a baseline
+#[derive(Debug)]
+pub struct BaselineReport {
+    pub version: String,
+    pub dry_run: bool,
+    /// Whether migration files were kept rather than deleted
+    pub kept_files: bool,
+    /// Migrations at or before the baseline version
+    pub covered: Vec<CoveredMigration>,
+    /// Files and directories that were deleted (empty for dry runs or when keeping files)
+    pub deleted: Vec<DeletedItem>,
+}
+
+impl Migrator {
+    /// Create a migrator for a project, using `migrations/` under the root
+    pub fn new(project_root: impl Into<PathBuf>) -> Self {
+        let project_root = project_root.into();
+        let project_root = if project_root.is_absolute() {
+            project_root
+        } else {
+            std::env::current_dir()
+                .map(|cwd| cwd.join(&project_root))
+                .unwrap_or(project_root)
+        };
+
+        Self {
+            migrations_dir: project_root.join(DEFAULT_MIGRATIONS_DIR),
+            project_root,
+            dry_run: false,
+            baseline_on_success: false,
+            keep_files: false,
+            variables: Vec::new(),
+            params: BTreeMap::new(),
+            prompt: None,
+            on_progress: None,
+            state_store: None,
+            state_file: None,
+            state_location: None,
+            functions: Vec::new(),
+            jobs: 1,
+            tags: TagFilter::default(),
+        }
+    }
+
+    /// Set the migrations directory; relative paths are resolved against the project root
+    pub fn migrations_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.migrations_dir = self.project_root.join(dir);
+        self
+    }
+
+    /// Preview instead of applying: scripts are skipped and declarative steps are only described
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Create a baseline at the last applied version after a successful `apply`
+    pub fn baseline_on_success(mut self, enabled: bool) -> Self {
+        self.baseline_on_success = enabled;
+        self
+    }
+
+    /// Keep migration files and asset directories when creating a baseline
+    pub fn keep_files(mut self, keep: bool) -> Self {
+        self.keep_files = keep;
+        self
+    }
+
+    /// Set a template variable, overriding the project variables file
+    pub fn variable(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.variables.push((name.into(), value.into()));
+        self
+    }
+
+    /// Supply a value for the `Param` of that name, in every migration that declares it
+    pub fn param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.params.insert(name.into(), value.into());
+        self
+    }
+
+    /// Ask for the value of each declared parameter that wasn't supplied, before any
+    /// migration runs. Without a prompt, parameters fall back to their defaults.
+    pub fn prompt_params(
+        mut self,
+        prompt: impl Fn(&Param) -> Result<Option<String>> + 'static,
+    ) -> Self {
+        self.prompt = Some(Rc::new(prompt));
+        self
+    }
+
+    /// Receive progress notifications while migrations are applied
+    pub fn on_progress(mut self, callback: impl Fn(Progress<'_>) + 'static) -> Self {
+        self.on_progress = Some(Rc::new(callback));
+        self
+    }
+
+    /// Persist state in `store` instead of the store configured in `migrate.toml`
+    pub fn state_store(mut self, store: impl StateStore + 'static) -> Self {
+        self.state_store = Some(Box::new(store));
+        self
+    }
+
+    /// Record state in this file instead of the configured location; the backend is chosen
+    /// from the extension (`.jsonl`, `.db`, otherwise a history file). A relative path is
+    /// resolved against the project root, like the migrations directory.
+    pub fn state_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_file = Some(path.into());
+        self
+    }
+
+    /// Override the directory the configured state backend uses
+    pub fn state_location(mut self, location: Location) -> Self {
+        self.state_location = Some(location);
+        self
+    }
+
+    /// Register an in-process migration. `id` uses the same `<version>-<name>` scheme as
+    /// migration files, and the function runs in version order alongside them.
+    pub fn migration(
+        mut self,
+        id: impl Into<String>,
+        f: impl Fn(&ExecutionContext) -> Result<()> + 'static,
+    ) -> Self {
+        self.functions.push((id.into(), Box::new(f)));
+        self
+    }
+
+    pub fn project_root(&self) -> &Path {
+        &self.project_root
+    }
+
+    pub fn migrations_path(&self) -> &Path {
+        &self.migrations_dir
+    }
+
+    /// Read the migration state of the project
+    pub fn status(&self) -> Result<Status> {
+        let store = self.open_store()?;
+        let (available, state) = self.load(&*store)?;
+        let (pending, filtered) = get_pending(&available, &state)
+            .into_iter()
+            .cloned()
+            .partition(|m| self.tags.matches(&m.header.tags));
+
+        Ok(Status {
+            state: store.location(),
+            current_version: get_current_version(&available, &state.applied),
+            target_version: get_target_version(&available),
+            pending,
+            filtered,
+            applied: state.applied,
+            baseline: state.baseline,
+            available,
+        })
+    }
+
+    /// List the migrations `apply` would run, in the order it would start them
+    pub fn plan(&self) -> Result<Plan> {
+        let store = self.open_store()?;
+        let (available, state) = self.load(&*store)?;
+        Ok(Plan {
+            pending: self.pending(&available, &state)?,
+        })
+    }
+
+    /// Apply all pending migrations, stopping at the first failure. Migrations run in
+    /// dependency order (see [`dag::order`]); with [`Migrator::jobs`] above 1, migrations whose
+    /// dependencies are done run concurrently.
+    pub fn apply(&self) -> Result<ApplyReport> {
+        let store = self.open_store()?;
+        let _lock = if self.dry_run {
+            None
+        } else {
+            Some(store.lock()?)
+        };
+        let (available, state) = self.load(&*store)?;
+        let pending = self.pending(&available, &state)?;
+
+        let mut report = ApplyReport {
+            dry_run: self.dry_run,
+            outcomes: Vec::new(),
+            failed: None,
+            baseline: None,
+        };
+        if pending.is_empty() {
+            return Ok(report);
+        }
+
+        let run = Run {
+            store: &*store,
+            state: &state,
+            variables: load_variables(&self.project_root, &self.migrations_dir, &self.variables)?,
+            params: params::resolve(&pending, &self.params, self.prompt.as_deref())?,
+            info: RunInfo::collect(&self.project_root),
+        };
+        if self.jobs > 1 && !self.dry_run {
+            self.apply_concurrently(pending, &run, &mut report)?;
+        } else {
+            for migration in pending {
+                if !self.apply_one(migration, &run, &mut report)? {
+                    break;
+                }
+            }
+        }
+        if report.failed.is_some() {
+            return Ok(report);
+        }
+
+        if self.baseline_on_success {
+            if let Some(version) = report.outcomes.iter().map(|o| &o.migration.version).max() {
+                let version = version.clone();
+                report.baseline = Some(self.write_baseline(&*store, &version, None, &available)?);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Pending migrations selected by the tag filter in dependency order, after checking
+    /// `DependsOn` headers
+    fn pending(&self, available: &[Migration], state: &HistoryState) -> Result<Vec<Migration>> {
+        let problems = dag::validate(available, &state.applied, state.baseline.as_ref());
+        if !problems.is_empty() {
+            return Err(MigrateError::InvalidDependencies(problems).into());
+        }
+        Ok(dag::order(
+            get_pending(available, state)
+                .into_iter()
+                .filter(|m| self.tags.matches(&m.header.tags))
+                .cloned()
+                .collect(),
+        ))
+    }
+
+    /// Only treat migrations selected by `filter` as pending. Migrations it leaves out are
+    /// neither run nor recorded, and migrations that depend on them fail.
+    pub fn tag_filter(mut self, filter: TagFilter) -> Self {
+        self.tags = filter;
+        self
+    }
+
+    /// Set how many migrations `apply` may run at once (default 1). Function migrations
+    /// always run on the calling thread, and dry runs are sequential.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Run (or preview) one migration and record its outcome; false if it failed
+    fn apply_one(&self, migration: Migration, run: &Run, report: &mut ApplyReport) -> Result<bool> {
+        self.notify(Progress::Started(&migration));
+        let started = Instant::now();
+        if let Some(blocked) = self.blocked(&migration, run, report)? {
+            return self.settle(migration, blocked, started, run, report);
+        }
+        self.execute_one(migration, started, run, report)
+    }
+
+    /// Run (or preview) a migration that is not blocked; false if it failed
+    fn execute_one(
+        &self,
+        migration: Migration,
+        started: Instant,
+        run: &Run,
+        report: &mut ApplyReport,
+    ) -> Result<bool> {
+        let ctx = self.context(&migration, run, report);
+        let function = self.function(&migration.id);
+        if self.dry_run {
+            // Declarative and function migrations can preview without side effects
+            let result = match function {
+                Some(f) => Some(execute_fn(f, &ctx)),
+                None if migration.is_declarative() => {
+                    Some(execute(&migration, &ctx).unwrap_or_else(error_result))
+                }
+                None => None,
+            };
+            let duration = started.elapsed();
+            self.notify(Progress::Skipped {
+                migration: &migration,
+                preview: result.as_ref(),
+                duration,
+            });
+            report.outcomes.push(MigrationOutcome {
+                migration,
+                result,
+                applied_at: None,
+                duration,
+                not_applicable: false,
+            });
+            return Ok(true);
+        }
+
+        // An invalid migration file fails the migration like it does with `jobs`, so the
+        // report still has the earlier outcomes
+        let result = match function {
+            Some(f) => Ok(execute_fn(f, &ctx)),
+            None => execute(&migration, &ctx),
+        };
+        let result = result
+            .and_then(|result| verify_result(&migration, &ctx, result))
+            .unwrap_or_else(error_result);
+        self.finish(migration, result, started.elapsed(), run, report)
+    }
+
+    /// Apply migrations on up to `jobs` threads, starting each once its dependencies in this
+    /// run have been applied. After a failure no new migrations are started.
+    fn apply_concurrently(
+        &self,
+        pending: Vec<Migration>,
+        run: &Run,
+        report: &mut ApplyReport,
+    ) -> Result<()> {
+        enum Update {
+            Output(usize, String, Stream),
+            Message(usize, Message),
+            Finished(usize, ExecutionResult, Duration),
+        }
+
+        let mut waiting: Vec<Option<Migration>> = pending.iter().cloned().map(Some).collect();
+        let (sender, receiver) = mpsc::channel();
+        let mut running = 0;
+        let capture = self.on_progress.is_some();
+
+        let outcome = thread::scope(|scope| -> Result<()> {
+            loop {
+                while running < self.jobs && report.failed.is_none() {
+                    let in_run = |id: &String| {
+                        waiting.iter().flatten().any(|m| &m.id == id)
+                            || (pending.iter().any(|m| &m.id == id)
+                                && !report.outcomes.iter().any(|o| &o.migration.id == id))
+                    };
+                    let Some(index) = waiting.iter().position(|m| {
+                        m.as_ref()
+                            .is_some_and(|m| !m.header.depends_on.iter().any(in_run))
+                    }) else {
+                        break;
+                    };
+                    let migration = waiting[index].take().unwrap();
+                    self.notify(Progress::Started(&migration));
+                    let started = Instant::now();
+                    if let Some(blocked) = self.blocked(&migration, run, report)? {
+                        self.settle(migration, blocked, started, run, report)?;
+                        continue;
+                    }
+                    if self.function(&migration.id).is_some() {
+                        self.execute_one(migration, started, run, report)?;
+                        continue;
+                    }
+
+                    let sender = sender.clone();
+                    let (project_root, migrations_dir) =
+                        (self.project_root.clone(), self.migrations_dir.clone());
+                    let variables = run.variables.clone();
+                    let values = params::for_migration(&migration, &run.params);
+                    let outputs = self.earlier_outputs(&migration, run, report);
+                    scope.spawn(move || {
+                        let mut ctx = ExecutionContext::new(
+                            project_root,
+                            migrations_dir,
+                            migration.id.clone(),
+                            false,
+                            variables,
+                        )
+                        .with_params(values)
+                        .with_earlier_outputs(outputs);
+                        if capture {
+                            let output = sender.clone();
+                            ctx = ctx.with_output(OutputSink::new(move |line, stream| {
+                                output
+                                    .send(Update::Output(index, line.to_string(), stream))
+                                    .ok();
+                            }));
+                            let messages = sender.clone();
+                            ctx = ctx.with_messages(MessageSink::new(move |message| {
+                                messages.send(Update::Message(index, message.clone())).ok();
+                            }));
+                        }
+                        // An error still ends in a result, so the main thread keeps receiving
+                        let result = execute(&migration, &ctx)
+                            .and_then(|result| verify_result(&migration, &ctx, result))
+                            .unwrap_or_else(error_result);
+                        sender
+                            .send(Update::Finished(index, result, started.elapsed()))
+                            .ok();
+                    });
+                    running += 1;
+                }
+                if running == 0 {
+                    return Ok(());
+                }
+
+                match receiver.recv()? {
+                    Update::Output(index, line, stream) => self.notify(Progress::Output {
+                        migration: &pending[index],
+                        line: &line,
+                        stream,
+                    }),
+                    Update::Message(index, message) => self.notify(Progress::Message {
+                        migration: &pending[index],
+                        message: &message,
+                    }),
+                    Update::Finished(index, result, duration) => {
+                        running -= 1;
+                        self.finish(pending[index].clone(), result, duration, run, report)?;
+                    }
+                }
+            }
+        });
+        // After an error, record the migrations that were still running: they have already
+        // changed the project
+        for update in receiver.try_iter() {
+            if let Update::Finished(index, result, duration) = update {
+                self.finish(pending[index].clone(), result, duration, run, report)
+                    .ok();
+            }
+        }
+        outcome
+    }
+
+    /// Why a migration can't run: a dependency that is not applied or not applicable, or a
+    /// `RunIf` condition that does not hold
+    fn blocked(
+        &self,
+        migration: &Migration,
+        run: &Run,
+        report: &ApplyReport,
+    ) -> Result<Option<Blocked>> {
+        for dependency in &migration.header.depends_on {
+            let recorded = run.state.applied.iter().rev().find(|a| &a.id == dependency);
+            let outcome = report
+                .outcomes
+                .iter()
+                .find(|o| &o.migration.id == dependency);
+            if recorded.is_some_and(|a| a.not_applicable)
+                || outcome.is_some_and(|o| o.not_applicable)
+            {
+                return Ok(Some(Blocked::NotApplicable(format!(
+                    "depends on {}, which is not applicable",
+                    dependency
+                ))));
+            }
+            if recorded.is_none()
+                && outcome.is_none()
+                && !dag::is_baselined(dependency, run.state.baseline.as_ref())
+            {
+                return Ok(Some(Blocked::Unmet(ExecutionResult {
+                    success: false,
+                    exit_code: -1,
+                    error: Some(format!(
+                        "Migration {} depends on {}, which has not been applied",
+                        migration.id, dependency
+                    )),
+                    touched_files: Vec::new(),
+                    outputs: Outputs::new(),
+                    messages: Vec::new(),
+                })));
+            }
+        }
+
+        let ctx = self.context(migration, run, report);
+        for condition in &migration.header.run_if {
+            if !check_condition(condition, &ctx)? {
+                return Ok(Some(Blocked::NotApplicable(format!(
+                    "RunIf '{}' does not hold",
+                    condition
+                ))));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Record a blocked migration: failed for an unmet dependency, otherwise not applicable
+    fn settle(
+        &self,
+        migration: Migration,
+        blocked: Blocked,
+        started: Instant,
+        run: &Run,
+        report: &mut ApplyReport,
+    ) -> Result<bool> {
+        match blocked {
+            Blocked::Unmet(result) => {
+                self.finish(migration, result, started.elapsed(), run, report)
+            }
+            Blocked::NotApplicable(reason) => {
+                self.not_applicable(migration, &reason, None, started.elapsed(), run, report)?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Record a migration as not applicable (in history unless this is a dry run)
+    fn not_applicable(
+        &self,
+        migration: Migration,
+        reason: &str,
+        result: Option<ExecutionResult>,
+        duration: Duration,
+        run: &Run,
+        report: &mut ApplyReport,
+    ) -> Result<()> {
+        let applied_at = if self.dry_run {
+            None
+        } else {
+            let applied_at = Utc::now();
+            run.store.record_applied(&AppliedMigration {
+                duration: Some(duration),
+                exit_code: result.as_ref().map(|r| r.exit_code),
+                not_applicable: true,
+                outputs: result
+                    .as_ref()
+                    .map(|r| r.outputs.clone())
+                    .unwrap_or_default(),
+                ..run.info.record(&migration.id, applied_at)
+            })?;
+            Some(applied_at)
+        };
+        self.notify(Progress::NotApplicable {
+            migration: &migration,
+            reason,
+            duration,
+        });
+        report.outcomes.push(MigrationOutcome {
+            migration,
+            result,
+            applied_at,
+            duration,
+            not_applicable: true,
+        });
+        Ok(())
+    }
+
+    /// Record a migration's result in history and the report; false if it failed
+    fn finish(
+        &self,
+        migration: Migration,
+        result: ExecutionResult,
+        duration: Duration,
+        run: &Run,
+        report: &mut ApplyReport,
+    ) -> Result<bool> {
+        if !result.success {
+            self.notify(Progress::Failed {
+                migration: &migration,
+                result: &result,
+                duration,
+            });
+            let outcome = MigrationOutcome {
+                migration,
+                result: Some(result),
+                applied_at: None,
+                duration,
+                not_applicable: false,
+            };
+            // With concurrent jobs, later failures are kept with the other outcomes
+            match report.failed {
+                None => report.failed = Some(outcome),
+                Some(_) => report.outcomes.push(outcome),
+            }
+            return Ok(false);
+        }
+        if result.exit_code == NOT_APPLICABLE_EXIT_CODE {
+            let reason = format!("exited with code {}", NOT_APPLICABLE_EXIT_CODE);
+            self.not_applicable(migration, &reason, Some(result), duration, run, report)?;
+            return Ok(true);
+        }
+
+        let applied_at = Utc::now();
+        run.store.record_applied(&AppliedMigration {
+            duration: Some(duration),
+            exit_code: Some(result.exit_code),
+            outputs: result.outputs.clone(),
+            ..run.info.record(&migration.id, applied_at)
+        })?;
+        self.notify(Progress::Completed {
+            migration: &migration,
+            result: &result,
+            duration,
+        });
+        report.outcomes.push(MigrationOutcome {
+            migration,
+            result: Some(result),
+            applied_at: Some(applied_at),
+            duration,
+            not_applicable: false,
+        });
+        Ok(true)
+    }
+
+    /// Outputs of the migrations applied so far, in history or in this run, by migration ID.
+    /// With concurrent jobs, which other migrations have finished depends on timing, so only
+    /// the outputs of this run's dependencies are included.
+    fn earlier_outputs(
+        &self,
+        migration: &Migration,
+        run: &Run,
+        report: &ApplyReport,
+    ) -> BTreeMap<String, Outputs> {
+        let concurrent = self.jobs > 1 && !self.dry_run;
+        let recorded = run
+            .state
+            .applied
+            .iter()
+            .map(|applied| (applied.id.clone(), applied.outputs.clone()));
+        let applied = report
+            .outcomes
+            .iter()
+            .filter(|outcome| outcome.applied_at.is_some())
+            .filter(|outcome| {
+                !concurrent || migration.header.depends_on.contains(&outcome.migration.id)
+            })
+            .filter_map(|outcome| {
+                let result = outcome.result.as_ref()?;
+                Some((outcome.migration.id.clone(), result.outputs.clone()))
+            });
+        recorded
+            .chain(applied)
+            .filter(|(_, outputs)| !outputs.is_empty())
+            .collect()
+    }
+
+    /// Execution context for a migration, sending its output to the progress callback
+    fn context(&self, migration: &Migration, run: &Run, report: &ApplyReport) -> ExecutionContext {
+        let ctx = ExecutionContext::new(
+            self.project_root.clone(),
+            self.migrations_dir.clone(),
+            migration.id.clone(),
+            self.dry_run,
+            run.variables.clone(),
+        )
+        .with_params(params::for_migration(migration, &run.params))
+        .with_earlier_outputs(self.earlier_outputs(migration, run, report));
+        match &self.on_progress {
+            Some(callback) => {
+                let output_callback = Rc::clone(callback);
+                let output_migration = migration.clone();
+                let message_callback = Rc::clone(callback);
+                let message_migration = migration.clone();
+                ctx.with_output(OutputSink::new(move |line, stream| {
+                    output_callback(Progress::Output {
+                        migration: &output_migration,
+                        line,
+                        stream,
+                    })
+                }))
+                .with_messages(MessageSink::new(move |message| {
+                    message_callback(Progress::Message {
+                        migration: &message_migration,
+                        message,
+                    })
+                }))
+            }
+            None => ctx,
+        }
+    }
+
+    fn function(&self, id: &str) -> Option<&MigrationFn> {
+        self.functions
+            .iter()
+            .find(|(function_id, _)| function_id == id)
+            .map(|(_, f)| f)
+    }
+
+    /// Create a baseline at `version`, deleting the migrations it covers unless `keep_files`
+    /// is set. Every covered migration must have been applied.
+    pub fn baseline(&self, version: &str, summary: Option<&str>) -> Result<BaselineReport> {
+        let store = self.open_store()?;
+        let _lock = if self.dry_run {
+            None
+        } else {
+            Some(store.lock()?)
+        };
+        let (available, state) = self.load(&*store)?;
+        validate_baseline(version, &available, &state.applied, state.baseline.as_ref())?;
+        self.write_baseline(&*store, version, summary, &available)
+    }
+
+    fn write_baseline(
+        &self,
+        store: &dyn StateStore,
+        version: &str,
+        summary: Option<&str>,
+        available: &[Migration],
+    ) -> Result<BaselineReport> {
+        let covered: Vec<CoveredMigration> = available
+            .iter()
+            .filter(|m| version_lte(&m.version, version))
+            .map(|m| CoveredMigration {
+                has_asset_dir: m.file_path.parent().is_some_and(|p| p.join(&m.id).is_dir()),
+                migration: m.clone(),
+            })
+            .collect();
+
+        let mut report = BaselineReport {
+            version: version.to_string(),
+            dry_run: self.dry_run,
+            kept_files: self.keep_files,
+            covered,
+            deleted: Vec::new(),
+        };
+        if self.dry_run {
+            return Ok(report);
+        }
+
+        let baseline = Baseline {
+            version: version.to_string(),
+            created: Utc::now(),
+            summary: summary.map(|s| s.to_string()),
+        };
+        store.record_baseline(&baseline)?;
+
+        if !self.keep_files {
+            report.deleted = delete_baselined_migrations(version, available)?;
+        }
+        Ok(report)
+    }
+
+    /// The explicitly configured store, or the one selected by `migrate.toml`
+    fn open_store(&self) -> Result<StoreRef<'_>> {
+        Ok(match &self.state_store {
+            Some(store) => StoreRef::Borrowed(store.as_ref()),
+            None => {
+                let mut config = Config::load(&self.project_root)?.state;
+                if let Some(location) = self.state_location {
+                    config.location = location;
+                }
+                if let Some(path) = &self.state_file {
+                    config = config.with_path(path);
+                }
+                StoreRef::Owned(config.open(&self.project_root, &self.migrations_dir)?)
+            }
+        })
+    }
+
+    fn load(&self, store: &dyn StateStore) -> Result<(Vec<Migration>, HistoryState)> {
+        // Migrations registered as functions don't need a directory
+        if !self.migrations_dir.exists() && self.functions.is_empty() {
+            bail!(
+                "No migrations directory found at: {}",
+                self.migrations_dir.display()
+            );
+        }
+        let mut migrations = if self.migrations_dir.exists() {
+            discover_migrations(&self.migrations_dir)?
+        } else {
+            Vec::new()
+        };
+        for (id, _) in &self.functions {
+            // Versions are generated lowercase; uppercase ones would sort before every
+            // file migration and the baseline
+            let version =
+                extract_version(id).filter(|v| !v.bytes().any(|b| b.is_ascii_uppercase()));
+            let Some(version) = version else {
+                bail!(
+                    "Invalid migration ID '{}': expected <version>-<name>, e.g. 1f72f-init",
+                    id
+                );
+            };
+            if migrations.iter().any(|m| m.id == *id) {
+                bail!("Duplicate migration ID: {}", id);
+            }
+            migrations.push(Migration {
+                id: id.clone(),
+                version,
+                kind: MigrationKind::Function,
+                ..Default::default()
+            });
+        }
+        migrations.sort_by(|a, b| a.version.cmp(&b.version));
+        Ok((migrations, store.read()?))
+    }
+
+    fn notify(&self, progress: Progress<'_>) {
+        if let Some(callback) = &self.on_progress {
+            callback(progress);
+        }
+    }
+}
+
+enum StoreRef<'a> {
+    Borrowed(&'a dyn StateStore),
+    Owned(Box<dyn StateStore>),
+}
+
+impl<'a> std::ops::Deref for StoreRef<'a> {
+    type Target = dyn StateStore + 'a;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            StoreRef::Borrowed(store) => *store,
+            StoreRef::Owned(store) => store.as_ref(),
+        }
+    }
+}
+
+/// Why a migration was not executed
+enum Blocked {
+    /// A dependency has not been applied; the migration fails with this result
+    Unmet(ExecutionResult),
+    /// The migration is recorded as not applicable, for this reason
+    NotApplicable(String),
+}
+
+/// What `apply` needs while running migrations
+struct Run<'a> {
+    store: &'a dyn StateStore,
+    state: &'a HistoryState,
+    variables: Variables,
+    /// Resolved parameter values, by name
+    params: BTreeMap<String, String>,
+    info: RunInfo,
+}
+
+/// A failed result for a migration that could not be run or verified at all
+fn error_result(error: anyhow::Error) -> ExecutionResult {
+    ExecutionResult {
+        success: false,
+        exit_code: 1,
+        error: Some(format!("{:#}", error)),
+        touched_files: Vec::new(),
+        outputs: Outputs::new(),
+        messages: Vec::new(),
+    }
+}
+
+/// Audit details shared by every migration applied in one run
+struct RunInfo {
+    run_id: String,
+    user: Option<String>,
+    host: Option<String>,
+    commit: Option<String>,
+}
+
+impl RunInfo {
+    fn collect(project_root: &Path) -> Self {
+        Self {
+            run_id: format!(
+                "{}-{:x}",
+                Utc::now().format("%Y%m%dT%H%M%SZ"),
+                std::process::id()
+            ),
+            user: ["USER", "USERNAME", "LOGNAME"]
+                .iter()
+                .find_map(|name| std::env::var(name).ok().filter(|v| !v.is_empty())),
+            host: hostname(),
+            commit: git_commit(project_root),
+        }
+    }
+
+    fn record(&self, id: &str, applied_at: DateTime<Utc>) -> AppliedMigration {
+        AppliedMigration {
+            migrate_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            user: self.user.clone(),
+            host: self.host.clone(),
+            run_id: Some(self.run_id.clone()),
+            commit: self.commit.clone(),
+            ..AppliedMigration::new(id, applied_at)
+        }
+    }
+}
+
+fn hostname() -> Option<String> {
+    let non_empty = |value: String| Some(value.trim().to_string()).filter(|v| !v.is_empty());
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .and_then(non_empty)
+        .or_else(|| {
+            std::fs::read_to_string("/etc/hostname")
+                .ok()
+                .and_then(non_empty)
+        })
+        .or_else(|| {
+            let output = Command::new("hostname").output().ok()?;
+            non_empty(String::from_utf8_lossy(&output.stdout).into_owned())
+        })
+}
+
+/// The project's current git commit, if it is a git working tree
+fn git_commit(project_root: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(project_root)
+        .args(["rev-parse", "HEAD"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !commit.is_empty()).then_some(commit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn project_with_migrations(migrations: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let migrations_dir = dir.path().join(DEFAULT_MIGRATIONS_DIR);
+        fs::create_dir(&migrations_dir).unwrap();
+        for (name, content) in migrations {
+            fs::write(migrations_dir.join(name), content).unwrap();
+        }
+        dir
+    }
+
+    const WRITE_NOTES: &str = r#"
+[[steps]]
+action = "write"
+path = "notes.txt"
+content = "hello"
+"#;
+
+    #[test]
+    fn test_apply_records_history() {
+        let dir = project_with_migrations(&[("1f700-notes.toml", WRITE_NOTES)]);
+        let migrator = Migrator::new(dir.path());
+
+        assert_eq!(migrator.plan().unwrap().pending.len(), 1);
+
+        let report = migrator.apply().unwrap();
+        assert!(report.is_success());
+        assert_eq!(report.outcomes.len(), 1);
+        assert!(report.outcomes[0].applied_at.is_some());
+        assert_eq!(
+            report.touched_files(),
+            vec![("1f700-notes", dir.path().join("notes.txt").as_path())]
+        );
+
+        let status = migrator.status().unwrap();
+        assert!(status.pending.is_empty());
+        assert_eq!(status.current_version.as_deref(), Some("1f700"));
+    }
+
+    #[test]
+    fn test_dry_run_leaves_project_untouched() {
+        let dir = project_with_migrations(&[("1f700-notes.toml", WRITE_NOTES)]);
+        let report = Migrator::new(dir.path()).dry_run(true).apply().unwrap();
+
+        assert!(report.outcomes[0].applied_at.is_none());
+        assert!(!dir.path().join("notes.txt").exists());
+        assert_eq!(Migrator::new(dir.path()).plan().unwrap().pending.len(), 1);
+    }
+
+    #[test]
+    fn test_dry_run_skips_steps_that_depend_on_earlier_steps() {
+        let dir = project_with_migrations(&[(
+            "1f700-config.toml",
+            r#"
+[[steps]]
+action = "write"
+path = "config/app.json"
+content = "{}"
+
+[[steps]]
+action = "edit"
+path = "config/app.json"
+edits = [{ op = "set", key = "name", value = "app" }]
+
+[[steps]]
+action = "chmod"
+path = "config"
+mode = 0o755
+"#,
+        )]);
+        let report = Migrator::new(dir.path()).dry_run(true).apply().unwrap();
+
+        let preview = report.outcomes[0].result.as_ref().unwrap();
+        assert!(preview.success, "{:?}", preview.error);
+        assert!(!dir.path().join("config").exists());
+    }
+
+    #[test]
+    fn test_failure_stops_apply() {
+        let dir = project_with_migrations(&[
+            (
+                "1f700-broken.toml",
+                "[[steps]]\naction = \"delete\"\npath = \"../outside\"\n",
+            ),
+            ("1f710-notes.toml", WRITE_NOTES),
+        ]);
+        let report = Migrator::new(dir.path()).apply().unwrap();
+
+        assert!(!report.is_success());
+        assert_eq!(report.failed.unwrap().migration.id, "1f700-broken");
+        assert!(!dir.path().join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_baseline_deletes_covered_migrations() {
+        let dir = project_with_migrations(&[("1f700-notes.toml", WRITE_NOTES)]);
+        let migrator = Migrator::new(dir.path());
+
+        assert!(migrator.baseline("1f700", None).is_err());
+
+        migrator.apply().unwrap();
+        let report = migrator.baseline("1f700", Some("initial")).unwrap();
+        assert_eq!(report.covered.len(), 1);
+        assert_eq!(report.deleted.len(), 1);
+
+        let status = migrator.status().unwrap();
+        assert!(status.available.is_empty());
+        assert!(status.is_baselined(&status.applied[0]));
+    }
+
+    #[test]
+    fn test_function_migrations_run_in_version_order() {
+        let dir = project_with_migrations(&[("1f710-notes.toml", WRITE_NOTES)]);
+        let migrator = Migrator::new(dir.path())
+            .migration("1f720-append", |ctx| {
+                let notes = std::fs::read_to_string(ctx.path("notes.txt")?)?;
+                ctx.write("notes.txt", format!("{} world", notes))
+            })
+            .migration("1f700-setup", |ctx| ctx.write("setup.txt", "ok"));
+
+        let ids: Vec<_> = migrator
+            .plan()
+            .unwrap()
+            .pending
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(ids, ["1f700-setup", "1f710-notes", "1f720-append"]);
+
+        let report = migrator.apply().unwrap();
+        assert!(report.is_success());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("notes.txt")).unwrap(),
+            "hello world"
+        );
+        assert!(report
+            .touched_files()
+            .contains(&("1f700-setup", dir.path().join("setup.txt").as_path())));
+        assert!(migrator.status().unwrap().pending.is_empty());
+    }
+
+    #[test]
+    fn test_function_migration_errors() {
+        let dir = project_with_migrations(&[]);
+        let report = Migrator::new(dir.path())
+            .migration("1f700-broken", |_| bail!("no database"))
+            .apply()
+            .unwrap();
+        let failed = report.failed.unwrap();
+        assert_eq!(failed.result.unwrap().error.as_deref(), Some("no database"));
+
+        for id in ["setup", "ABCDE-upper", "1_2!3-symbols", "abcdé-accent"] {
+            let migrator = Migrator::new(dir.path()).migration(id, |_| Ok(()));
+            assert!(migrator.plan().is_err(), "{} should be rejected", id);
+        }
+
+        let dry_run = Migrator::new(dir.path())
+            .dry_run(true)
+            .migration("1f700-setup", |ctx| ctx.write("setup.txt", "ok"));
+        assert!(dry_run.apply().unwrap().is_success());
+        assert!(!dir.path().join("setup.txt").exists());
+    }
+
+    #[test]
+    fn test_invalid_declarative_migration_fails_in_the_report() {
+        let broken = [
+            ("1f700-notes.toml", WRITE_NOTES),
+            ("1f710-broken.toml", "[[steps]]\naction = \"unknown\"\n"),
+        ];
+        for jobs in [1, 2] {
+            let dir = project_with_migrations(&broken);
+            let report = Migrator::new(dir.path()).jobs(jobs).apply().unwrap();
+            let failed = report.failed.unwrap();
+            assert_eq!(failed.migration.id, "1f710-broken");
+            assert!(failed.result.unwrap().error.is_some());
+            assert_eq!(report.outcomes[0].migration.id, "1f700-notes");
+        }
+
+        let dir = project_with_migrations(&broken);
+        let preview = Migrator::new(dir.path()).dry_run(true).apply().unwrap();
+        assert!(preview.outcomes[1].result.as_ref().unwrap().error.is_some());
+    }
+
+    #[test]
+    fn test_function_migrations_need_no_migrations_directory() {
+        let dir = TempDir::new().unwrap();
+        assert!(Migrator::new(dir.path()).plan().is_err());
+
+        let migrator = Migrator::new(dir.path())
+            .state_location(Location::Project)
+            .migration("1f700-setup", |ctx| ctx.write("setup.txt", "ok"));
+        let report = migrator.apply().unwrap();
+        assert!(report.is_success());
+        assert!(report.outcomes[0].migration.is_function());
+        assert!(dir.path().join("setup.txt").exists());
+        assert!(migrator.plan().unwrap().pending.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_dependencies_stop_apply() {
+        let dir = project_with_migrations(&[(
+            "1f700-notes.toml",
+            &format!("# DependsOn: 1f710-later\n{}", WRITE_NOTES),
+        )]);
+        fs::write(dir.path().join("migrations/1f710-later.toml"), WRITE_NOTES).unwrap();
+        let error = Migrator::new(dir.path()).apply().unwrap_err();
+
+        assert_eq!(crate::error::exit_code(&error), 9);
+        assert!(error
+            .to_string()
+            .contains("1f700-notes depends on 1f710-later"));
+        assert!(!dir.path().join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_jobs_run_independent_migrations_concurrently() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // `waits` only succeeds if `signals` runs at the same time; `joins` needs both
+        let dir = project_with_migrations(&[
+            (
+                "1f700-waits.sh",
+                "#!/bin/sh\nfor i in $(seq 50); do [ -f signal ] && exit 0; sleep 0.1; done\nexit 1\n",
+            ),
+            ("1f700-signals.sh", "#!/bin/sh\ntouch signal\nsleep 0.3\n"),
+            (
+                "1f710-joins.sh",
+                "#!/bin/sh\n# DependsOn: 1f700-waits, 1f700-signals\n\necho done > joined\n",
+            ),
+        ]);
+        for entry in fs::read_dir(dir.path().join(DEFAULT_MIGRATIONS_DIR)).unwrap() {
+            fs::set_permissions(entry.unwrap().path(), fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let report = Migrator::new(dir.path())
+            .jobs(2)
+            .on_progress(|_| {})
+            .apply()
+            .unwrap();
+
+        assert!(report.is_success(), "{:?}", report.failed);
+        assert_eq!(report.outcomes.len(), 3);
+        assert_eq!(report.outcomes[2].migration.id, "1f710-joins");
+        assert!(dir.path().join("joined").exists());
+    }
+
+    #[test]
+    fn test_jobs_record_running_migrations_after_an_error() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = project_with_migrations(&[
+            ("1f700-slow.sh", "#!/bin/sh\nsleep 0.3\ntouch slow\n"),
+            ("1f700-broken.sh", "#!/bin/sh\n"),
+            // Not executable, so it can't be run
+            ("1f700-broken.verify.sh", "#!/bin/sh\n"),
+        ]);
+        let migrations_dir = dir.path().join(DEFAULT_MIGRATIONS_DIR);
+        for name in ["1f700-slow.sh", "1f700-broken.sh"] {
+            fs::set_permissions(migrations_dir.join(name), fs::Permissions::from_mode(0o755))
+                .unwrap();
+        }
+        let report = Migrator::new(dir.path())
+            .jobs(2)
+            .on_progress(|_| {})
+            .apply()
+            .unwrap();
+
+        let failed = report.failed.as_ref().unwrap();
+        assert_eq!(failed.migration.id, "1f700-broken");
+        assert!(failed
+            .result
+            .as_ref()
+            .and_then(|r| r.error.as_deref())
+            .unwrap()
+            .starts_with("Failed to run verify script"));
+        let applied = Migrator::new(dir.path()).status().unwrap().applied;
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].id, "1f700-slow");
+    }
+
+    #[test]
+    fn test_jobs_only_pass_outputs_of_dependencies() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // `independent` starts once `first` has finished, but only `dependent` declares it
+        let dir = project_with_migrations(&[
+            ("1f700-first.sh", "#!/bin/sh\necho v=a >> \"$MIGRATE_OUTPUT\"\n"),
+            ("1f700-slow.sh", "#!/bin/sh\nsleep 0.5\n"),
+            (
+                "1f710-independent.sh",
+                "#!/bin/sh\necho \"[$MIGRATE_OUT_1F700_FIRST_V]\" > independent\n",
+            ),
+            (
+                "1f720-dependent.sh",
+                "#!/bin/sh\n# DependsOn: 1f700-first\n\necho \"[$MIGRATE_OUT_1F700_FIRST_V]\" > dependent\n",
+            ),
+        ]);
+        for entry in fs::read_dir(dir.path().join(DEFAULT_MIGRATIONS_DIR)).unwrap() {
+            fs::set_permissions(entry.unwrap().path(), fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let report = Migrator::new(dir.path()).jobs(2).apply().unwrap();
+        assert!(report.is_success(), "{:?}", report.failed);
+
+        let read = |name: &str| fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("independent"), "[]\n");
+        assert_eq!(read("dependent"), "[a]\n");
+    }
+
+    #[test]
+    fn test_tag_filter_leaves_migrations_pending() {
+        let write = |path: &str| {
+            format!(
+                "[[steps]]\naction = \"write\"\npath = \"{}\"\ncontent = \"x\"\n",
+                path
+            )
+        };
+        let dir = project_with_migrations(&[
+            (
+                "1f700-web.toml",
+                &format!("# Tags: frontend\n\n{}", write("web.txt")),
+            ),
+            (
+                "1f710-ci.toml",
+                &format!("# Tags: ci-only\n\n{}", write("ci.txt")),
+            ),
+            (
+                "1f720-after-ci.toml",
+                &format!(
+                    "# Tags: frontend\n# DependsOn: 1f710-ci\n\n{}",
+                    write("after.txt")
+                ),
+            ),
+        ]);
+        let filter = TagFilter {
+            include: vec!["frontend".to_string()],
+            exclude: Vec::new(),
+        };
+        let migrator = Migrator::new(dir.path()).tag_filter(filter);
+
+        let status = migrator.status().unwrap();
+        assert_eq!(status.pending.len(), 2);
+        assert_eq!(status.filtered[0].id, "1f710-ci");
+
+        let report = migrator.apply().unwrap();
+        assert_eq!(report.outcomes[0].migration.id, "1f700-web");
+        let failed = report.failed.unwrap();
+        assert_eq!(failed.migration.id, "1f720-after-ci");
+        assert!(failed
+            .result
+            .unwrap()
+            .error
+            .unwrap()
+            .contains("depends on 1f710-ci, which has not been applied"));
+        assert!(!dir.path().join("ci.txt").exists());
+
+        // Unfiltered, the skipped migration is still pending
+        let pending = Migrator::new(dir.path()).status().unwrap().pending;
+        let ids: Vec<_> = pending.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["1f710-ci", "1f720-after-ci"]);
+    }
+
+    #[test]
+    fn test_not_applicable_migrations_are_recorded() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = project_with_migrations(&[
+            (
+                "1f700-react.toml",
+                &format!("# RunIf: file-exists package.json
+{}", WRITE_NOTES),
+            ),
+            (
+                "1f710-after-react.toml",
+                &format!("# DependsOn: 1f700-react
+{}", WRITE_NOTES),
+            ),
+            (
+                "1f720-opts-out.sh",
+                "#!/bin/sh\nexit \"$MIGRATE_NOT_APPLICABLE\"\n",
+            ),
+            (
+                "1f730-runs.sh",
+                "#!/bin/sh\n# RunIf: test -d migrations\n# RunIf: glob-matches migrations/*.sh\n\ntouch ran\n",
+            ),
+        ]);
+        for name in ["1f720-opts-out.sh", "1f730-runs.sh"] {
+            let path = dir.path().join(DEFAULT_MIGRATIONS_DIR).join(name);
+            fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let migrator = Migrator::new(dir.path());
+
+        let report = migrator.apply().unwrap();
+        assert!(report.is_success(), "{:?}", report.failed);
+        let not_applicable: Vec<bool> = report.outcomes.iter().map(|o| o.not_applicable).collect();
+        assert_eq!(not_applicable, [true, true, true, false]);
+        assert_eq!(
+            report.outcomes[2].result.as_ref().unwrap().exit_code,
+            NOT_APPLICABLE_EXIT_CODE
+        );
+        assert!(!dir.path().join("notes.txt").exists());
+        assert!(dir.path().join("ran").exists());
+
+        // Recorded, so nothing runs again
+        let status = migrator.status().unwrap();
+        assert!(status.pending.is_empty());
+        let recorded: Vec<bool> = status.applied.iter().map(|a| a.not_applicable).collect();
+        assert_eq!(recorded, [true, true, true, false]);
+    }
+
+    #[test]
+    fn test_failed_verification_is_not_recorded() {
+        let dir = project_with_migrations(&[
+            (
+                "1f700-notes.toml",
+                &format!(
+                    "{}\n[[verify]]\nassert = \"file-contains\"\npath = \"notes.txt\"\ntext = \"hello\"\n",
+                    WRITE_NOTES
+                ),
+            ),
+            (
+                "1f710-more.toml",
+                &format!(
+                    "{}\n[[verify]]\nassert = \"file-exists\"\npath = \"missing.txt\"\n",
+                    WRITE_NOTES
+                ),
+            ),
+        ]);
+        let migrator = Migrator::new(dir.path()).on_progress(|_| {});
+
+        let report = migrator.apply().unwrap();
+        assert_eq!(report.outcomes.len(), 1);
+        let failed = report.failed.unwrap();
+        assert_eq!(failed.migration.id, "1f710-more");
+        assert_eq!(
+            failed.result.unwrap().error.as_deref(),
+            Some("Migration 1f710-more failed verification: missing.txt does not exist")
+        );
+        let status = migrator.status().unwrap();
+        assert_eq!(status.applied.len(), 1);
+        assert_eq!(status.pending[0].id, "1f710-more");
+    }
+
+    #[test]
+    fn test_params_are_resolved_before_running() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = project_with_migrations(&[
+            ("1f700-notes.toml", WRITE_NOTES),
+            (
+                "1f710-api.sh",
+                "#!/bin/sh\n# Param: api_host required\n# Param: team=platform\n\necho \"$MIGRATE_PARAM_API_HOST $MIGRATE_PARAM_TEAM\" > api.txt\n",
+            ),
+        ]);
+        let script = dir.path().join(DEFAULT_MIGRATIONS_DIR).join("1f710-api.sh");
+        fs::set_permissions(script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        // Nothing runs while a required parameter is missing
+        let error = Migrator::new(dir.path()).apply().unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<MigrateError>(),
+            Some(MigrateError::MissingParameters(_))
+        ));
+        assert!(!dir.path().join("notes.txt").exists());
+
+        let report = Migrator::new(dir.path())
+            .param("api_host", "api.example.com")
+            .apply()
+            .unwrap();
+        assert!(report.is_success(), "{:?}", report.failed);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("api.txt")).unwrap(),
+            "api.example.com platform\n"
+        );
+    }
+
+    #[test]
+    fn test_outputs_reach_later_migrations() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = project_with_migrations(&[(
+            "1f710-config.sh",
+            "#!/bin/sh\necho \"$MIGRATE_OUT_1F700_CREATE_DB_DB_URL\" > db.txt\necho region=eu >> \"$MIGRATE_OUTPUT\"\n",
+        )]);
+        let script = dir
+            .path()
+            .join(DEFAULT_MIGRATIONS_DIR)
+            .join("1f710-config.sh");
+        fs::set_permissions(script, fs::Permissions::from_mode(0o755)).unwrap();
+        let create_db = |ctx: &ExecutionContext| ctx.set_output("db_url", "postgres://db/app");
+
+        let report = Migrator::new(dir.path())
+            .migration("1f700-create-db", create_db)
+            .apply()
+            .unwrap();
+        assert!(report.is_success(), "{:?}", report.failed);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("db.txt")).unwrap(),
+            "postgres://db/app\n"
+        );
+
+        // Outputs are recorded, so a later run still sees them
+        let report = Migrator::new(dir.path())
+            .migration("1f700-create-db", create_db)
+            .migration("1f720-read", |ctx| {
+                let region = ctx.output_of("1f710-config", "region").unwrap_or("none");
+                ctx.write("region.txt", region)
+            })
+            .apply()
+            .unwrap();
+        assert!(report.is_success(), "{:?}", report.failed);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("region.txt")).unwrap(),
+            "eu"
+        );
+        let applied = Migrator::new(dir.path()).status().unwrap().applied;
+        assert_eq!(applied[0].outputs["db_url"], "postgres://db/app");
+    }
+
+    #[test]
+    fn test_background_processes_do_not_hold_up_the_run() {
+        use std::os::unix::fs::PermissionsExt;
+
+        for capture in [false, true] {
+            let dir = project_with_migrations(&[(
+                "1f700-server.sh",
+                "#!/bin/sh\nsleep 5 &\necho started\necho '{\"type\": \"note\", \"message\": \"server running\"}' >&3\n",
+            )]);
+            let script = dir
+                .path()
+                .join(DEFAULT_MIGRATIONS_DIR)
+                .join("1f700-server.sh");
+            fs::set_permissions(script, fs::Permissions::from_mode(0o755)).unwrap();
+
+            let mut migrator = Migrator::new(dir.path());
+            if capture {
+                migrator = migrator.on_progress(|_| {});
+            }
+            let started = Instant::now();
+            let report = migrator.apply().unwrap();
+            assert!(started.elapsed() < Duration::from_secs(3));
+            assert!(report.is_success(), "{:?}", report.failed);
+            // Lines written before the script exited are still read
+            assert_eq!(report.messages().len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_chatty_background_processes_do_not_hold_up_the_run() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = project_with_migrations(&[(
+            "1f700-server.sh",
+            "#!/bin/sh
+(while true; do echo tick; echo '{\"type\": \"progress\"}' >&3; sleep 0.01; done) &
+echo started
+",
+        )]);
+        let script = dir
+            .path()
+            .join(DEFAULT_MIGRATIONS_DIR)
+            .join("1f700-server.sh");
+        fs::set_permissions(script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let started = Instant::now();
+        let report = Migrator::new(dir.path())
+            .on_progress(|_| {})
+            .apply()
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert!(report.is_success(), "{:?}", report.failed);
+    }
+
+    #[test]
+    fn test_migrations_send_messages() {
+        use std::cell::RefCell;
+        use std::os::unix::fs::PermissionsExt;
+
+        for jobs in [1, 2] {
+            let dir = project_with_migrations(&[(
+                "1f710-deps.sh",
+                "#!/bin/sh\n\
+                 echo '{\"type\": \"progress\", \"percent\": 50}' >&$MIGRATE_MESSAGE_FD\n\
+                 echo '{\"type\": \"touched\", \"path\": \"package.json\"}' >&3\n\
+                 echo 'oops' >&3\n\
+                 echo '{\"type\": \"follow-up\", \"message\": \"update CI\"}' >&3\n",
+            )]);
+            let script = dir
+                .path()
+                .join(DEFAULT_MIGRATIONS_DIR)
+                .join("1f710-deps.sh");
+            fs::set_permissions(script, fs::Permissions::from_mode(0o755)).unwrap();
+
+            let received = Rc::new(RefCell::new(Vec::new()));
+            let progress = Rc::clone(&received);
+            let report = Migrator::new(dir.path())
+                .jobs(jobs)
+                .migration("1f700-note", |ctx| {
+                    ctx.message(Message::Note {
+                        message: "restart the dev server".to_string(),
+                    });
+                    Ok(())
+                })
+                .on_progress(move |p| {
+                    if let Progress::Message { migration, message } = p {
+                        progress
+                            .borrow_mut()
+                            .push(format!("{}: {}", migration.id, message));
+                    }
+                })
+                .apply()
+                .unwrap();
+            assert!(report.is_success(), "{:?}", report.failed);
+
+            let received = received.borrow();
+            assert_eq!(received.len(), 4, "{:?}", received);
+            assert_eq!(received[0], "1f700-note: note: restart the dev server");
+            assert_eq!(received[1], "1f710-deps: [ 50%]");
+            assert!(received[2].starts_with("1f710-deps: warning: invalid message 'oops'"));
+            assert_eq!(received[3], "1f710-deps: follow-up: update CI");
+
+            let summary: Vec<_> = report
+                .messages()
+                .into_iter()
+                .map(|(id, m)| format!("{} {}", id, m.kind()))
+                .collect();
+            assert_eq!(
+                summary,
+                [
+                    "1f700-note note",
+                    "1f710-deps warning",
+                    "1f710-deps follow-up"
+                ]
+            );
+            assert_eq!(
+                report.touched_files(),
+                [("1f710-deps", dir.path().join("package.json").as_path())]
+            );
+        }
+    }
+}
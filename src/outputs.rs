@@ -0,0 +1,161 @@
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::params;
+
+/// Environment variable naming the file where a script writes its outputs
+pub const OUTPUT_ENV: &str = "MIGRATE_OUTPUT";
+
+/// Values a migration passes on to later migrations, by key
+pub type Outputs = BTreeMap<String, String>;
+
+/// Environment variable exposing an earlier migration's output, e.g.
+/// `MIGRATE_OUT_1F700_CREATE_DB_URL`
+pub fn env_name(id: &str, key: &str) -> String {
+    format!(
+        "MIGRATE_OUT_{}_{}",
+        params::env_part(id),
+        params::env_part(key)
+    )
+}
+
+/// Outputs of earlier migrations that map to the same environment variable, e.g. key `c` of
+/// `a-b` and key `b_c` of `a`. The variable holds the last of them in migration ID order.
+pub fn env_collisions(earlier: &BTreeMap<String, Outputs>) -> Vec<String> {
+    let mut sources: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (id, outputs) in earlier {
+        for key in outputs.keys() {
+            sources
+                .entry(env_name(id, key))
+                .or_default()
+                .push(format!("{}.{}", id, key));
+        }
+    }
+    sources
+        .into_iter()
+        .filter_map(|(name, sources)| {
+            let (last, others) = sources.split_last()?;
+            (!others.is_empty()).then(|| {
+                format!(
+                    "{} is set by several outputs ({}, {}) and holds the value of {}",
+                    name,
+                    others.join(", "),
+                    last,
+                    last
+                )
+            })
+        })
+        .collect()
+}
+
+/// Whether `key` can name an output: a letter or `_` followed by letters, digits, `_` or `-`
+pub fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Parse an output file: `key=value` lines, or `key<<DELIMITER` followed by the lines of a
+/// multi-line value and a line holding just `DELIMITER`. Later values replace earlier ones.
+pub fn parse(content: &str) -> Result<Outputs> {
+    let mut outputs = Outputs::new();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (key, value) = match (line.split_once('='), line.split_once("<<")) {
+            (Some((key, value)), heredoc)
+                if heredoc.is_none_or(|(before, _)| key.len() < before.len()) =>
+            {
+                (key, value.to_string())
+            }
+            (_, Some((key, delimiter))) => {
+                let mut value = Vec::new();
+                loop {
+                    match lines.next() {
+                        Some(line) if line == delimiter => break,
+                        Some(line) => value.push(line),
+                        None => bail!("Output '{}' is missing its delimiter {}", key, delimiter),
+                    }
+                }
+                (key, value.join("\n"))
+            }
+            _ => bail!("Invalid output line '{}' (expected key=value)", line),
+        };
+        if !is_valid_key(key) {
+            bail!("Invalid output key '{}'", key);
+        }
+        outputs.insert(key.to_string(), value);
+    }
+    Ok(outputs)
+}
+
+/// Read an output file; a script that wrote nothing has no outputs
+pub fn read(path: &Path) -> Result<Outputs> {
+    parse(&fs::read_to_string(path).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let outputs = parse(
+            "db_url=postgres://localhost/app?sslmode=disable\n\
+             \n\
+             notes<<EOF\n\
+             first line\n\
+             a=b\n\
+             EOF\n\
+             db_url=postgres://db/app\n\
+             empty=\n",
+        )
+        .unwrap();
+        assert_eq!(
+            outputs.into_iter().collect::<Vec<_>>(),
+            [
+                ("db_url".to_string(), "postgres://db/app".to_string()),
+                ("empty".to_string(), String::new()),
+                ("notes".to_string(), "first line\na=b".to_string()),
+            ]
+        );
+
+        assert!(parse("no value\n").is_err());
+        assert!(parse("1st=x\n").is_err());
+        assert!(parse("notes<<EOF\nunterminated\n").is_err());
+    }
+
+    #[test]
+    fn test_env_name() {
+        assert_eq!(
+            env_name("1f700-create-db", "db_url"),
+            "MIGRATE_OUT_1F700_CREATE_DB_DB_URL"
+        );
+    }
+
+    #[test]
+    fn test_env_collisions() {
+        let outputs = |pairs: &[(&str, &str)]| -> Outputs {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let mut earlier = BTreeMap::new();
+        earlier.insert("a".to_string(), outputs(&[("b_c", "1"), ("d", "2")]));
+        earlier.insert("a-b".to_string(), outputs(&[("c", "3")]));
+        assert_eq!(
+            env_collisions(&earlier),
+            ["MIGRATE_OUT_A_B_C is set by several outputs (a.b_c, a-b.c) and holds the value of a-b.c"]
+        );
+
+        earlier.remove("a-b");
+        assert!(env_collisions(&earlier).is_empty());
+    }
+}
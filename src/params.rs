@@ -0,0 +1,249 @@
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use toml::Value;
+
+use crate::header::Param;
+use crate::{MigrateError, Migration};
+
+/// Asks for a parameter's value; `None` (or an empty answer) falls back to the default
+pub type Prompt<'a> = dyn Fn(&Param) -> Result<Option<String>> + 'a;
+
+/// Environment variable passing a parameter to scripts, e.g. `MIGRATE_PARAM_API_HOST`
+pub fn env_name(name: &str) -> String {
+    format!("MIGRATE_PARAM_{}", env_part(name))
+}
+
+/// A name as part of an environment variable: uppercase, with `_` for anything but letters
+/// and digits
+pub(crate) fn env_part(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Read a params file: a TOML table of strings, numbers or booleans
+pub fn load_file(path: &Path) -> Result<Vec<(String, String)>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read params file: {}", path.display()))?;
+    let table: toml::Table = toml::from_str(&content)
+        .with_context(|| format!("Invalid params file: {}", path.display()))?;
+    let mut params = Vec::new();
+    for (name, value) in table {
+        let value = match value {
+            Value::String(s) => s,
+            Value::Integer(_) | Value::Float(_) | Value::Boolean(_) => value.to_string(),
+            _ => bail!(
+                "Invalid params file {}: '{}' must be a string, number or boolean",
+                path.display(),
+                name
+            ),
+        };
+        params.push((name, value));
+    }
+    Ok(params)
+}
+
+/// Values for every parameter declared by `migrations`: the supplied value, else the
+/// prompt's answer, else the default. Each name is resolved (and prompted for) once, using
+/// all of its declarations merged (see [`merge`]).
+/// Fails with [`MigrateError::MissingParameters`] if a required parameter has no value,
+/// naming every migration that requires it.
+pub fn resolve(
+    migrations: &[Migration],
+    supplied: &BTreeMap<String, String>,
+    prompt: Option<&Prompt<'_>>,
+) -> Result<BTreeMap<String, String>> {
+    let mut values = BTreeMap::new();
+    let mut missing = Vec::new();
+    for (param, required_by) in merge(migrations) {
+        let value = match (supplied.get(&param.name), prompt) {
+            (Some(value), _) => Some(value.clone()),
+            (None, Some(prompt)) => prompt(&param)?.filter(|v| !v.is_empty()),
+            (None, None) => None,
+        };
+        match value.or_else(|| param.default.clone()) {
+            Some(value) => {
+                values.insert(param.name.clone(), value);
+            }
+            None => {
+                for id in required_by {
+                    missing.push(match &param.description {
+                        Some(description) => {
+                            format!("{} needs {} ({})", id, param.name, description)
+                        }
+                        None => format!("{} needs {}", id, param.name),
+                    });
+                }
+            }
+        }
+    }
+    if !missing.is_empty() {
+        return Err(MigrateError::MissingParameters(missing).into());
+    }
+    Ok(values)
+}
+
+/// One parameter per name, in order of first declaration, with the migrations that declare
+/// it required. A name is required if any declaration says so; the description and default
+/// come from the first declaration that has one, i.e. the earliest migration to use it.
+fn merge(migrations: &[Migration]) -> Vec<(Param, Vec<&str>)> {
+    let mut merged: Vec<(Param, Vec<&str>)> = Vec::new();
+    for migration in migrations {
+        for param in &migration.header.params {
+            let index = match merged.iter().position(|(p, _)| p.name == param.name) {
+                Some(index) => index,
+                None => {
+                    merged.push((
+                        Param {
+                            required: false,
+                            ..param.clone()
+                        },
+                        Vec::new(),
+                    ));
+                    merged.len() - 1
+                }
+            };
+            let (existing, required_by) = &mut merged[index];
+            existing.default = existing.default.take().or_else(|| param.default.clone());
+            existing.description = existing
+                .description
+                .take()
+                .or_else(|| param.description.clone());
+            if param.required {
+                existing.required = true;
+                required_by.push(migration.id.as_str());
+            }
+        }
+    }
+    merged
+}
+
+/// The values of the parameters a migration declares
+pub fn for_migration(
+    migration: &Migration,
+    values: &BTreeMap<String, String>,
+) -> Vec<(String, String)> {
+    migration
+        .header
+        .params
+        .iter()
+        .filter_map(|p| Some((p.name.clone(), values.get(&p.name)?.clone())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Header;
+
+    fn migration(id: &str, params: &str) -> Migration {
+        Migration {
+            id: id.to_string(),
+            version: id[..5].to_string(),
+            header: Header::parse(params),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let migrations = [
+            migration(
+                "1f700-api",
+                "# Param: api_host required New API hostname\n# Param: team=platform\n",
+            ),
+            migration(
+                "1f710-more",
+                "# Param: api_host required\n# Param: region\n# Param: owner required\n",
+            ),
+        ];
+        let supplied = BTreeMap::from([("owner".to_string(), "jane".to_string())]);
+
+        let error = resolve(&migrations, &supplied, None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Missing required migration parameters:\n  1f700-api needs api_host (New API hostname)\n  1f710-more needs api_host (New API hostname)"
+        );
+
+        let asked = std::cell::RefCell::new(Vec::new());
+        let prompt = |param: &Param| {
+            asked.borrow_mut().push(param.name.clone());
+            Ok((param.name == "api_host").then(|| "api.example.com".to_string()))
+        };
+        let values = resolve(&migrations, &supplied, Some(&prompt)).unwrap();
+        assert_eq!(*asked.borrow(), ["api_host", "team", "region"]);
+        assert_eq!(
+            values,
+            BTreeMap::from([
+                ("api_host".to_string(), "api.example.com".to_string()),
+                ("owner".to_string(), "jane".to_string()),
+                ("team".to_string(), "platform".to_string()),
+            ])
+        );
+        assert_eq!(
+            for_migration(&migrations[1], &values),
+            [
+                ("api_host".to_string(), "api.example.com".to_string()),
+                ("owner".to_string(), "jane".to_string()),
+            ]
+        );
+
+        // A later migration can require a parameter an earlier one made optional
+        let migrations = [
+            migration("1f700-setup", "# Param: region\n"),
+            migration("1f710-deploy", "# Param: region required Cloud region\n"),
+        ];
+        let error = resolve(&migrations, &BTreeMap::new(), None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Missing required migration parameters:\n  1f710-deploy needs region (Cloud region)"
+        );
+
+        let asked = std::cell::RefCell::new(Vec::new());
+        let prompt = |param: &Param| {
+            asked.borrow_mut().push(param.clone());
+            Ok(Some("eu".to_string()))
+        };
+        let values = resolve(&migrations, &BTreeMap::new(), Some(&prompt)).unwrap();
+        assert_eq!(values["region"], "eu");
+        assert!(asked.borrow()[0].required);
+        assert_eq!(
+            asked.borrow()[0].description.as_deref(),
+            Some("Cloud region")
+        );
+
+        // A default from any declaration satisfies a required one
+        let migrations = [
+            migration("1f700-setup", "# Param: region required\n"),
+            migration("1f710-deploy", "# Param: region=us\n"),
+        ];
+        let values = resolve(&migrations, &BTreeMap::new(), None).unwrap();
+        assert_eq!(values["region"], "us");
+    }
+
+    #[test]
+    fn test_env_name_and_file() {
+        assert_eq!(env_name("api-host"), "MIGRATE_PARAM_API_HOST");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("params.toml");
+        fs::write(&path, "api_host = \"api.example.com\"\nreplicas = 3\n").unwrap();
+        assert_eq!(
+            load_file(&path).unwrap(),
+            [
+                ("api_host".to_string(), "api.example.com".to_string()),
+                ("replicas".to_string(), "3".to_string()),
+            ]
+        );
+        fs::write(&path, "[nested]\nkey = 1\n").unwrap();
+        assert!(load_file(&path).is_err());
+    }
+}
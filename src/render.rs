@@ -0,0 +1,450 @@
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::declarative::resolve;
+use crate::structured::{get_path, parse_key, Document, Format};
+
+/// Name of the project variables file, looked up in the migrations directory
+pub const VARIABLES_FILE: &str = "variables.toml";
+
+/// Extension marking asset files that are rendered (and stripped) when rendering a directory
+pub const TEMPLATE_EXTENSION: &str = "tmpl";
+
+/// Template variables, keyed by name
+pub type Variables = Map<String, Value>;
+
+/// Collect template variables for a project. Later sources override earlier ones:
+/// 1. `<migrations>/variables.toml` — literal values, or `{ file = "package.json", key = "name" }`
+///    to read a value from a JSON, YAML or TOML file in the project
+/// 2. `overrides` given on the command line (`--set key=value`)
+pub fn load_variables(
+    project_root: &Path,
+    migrations_dir: &Path,
+    overrides: &[(String, Value)],
+) -> Result<Variables> {
+    let mut variables = Variables::new();
+
+    let variables_path = migrations_dir.join(VARIABLES_FILE);
+    if variables_path.exists() {
+        let content = fs::read_to_string(&variables_path)
+            .with_context(|| format!("Failed to read {}", variables_path.display()))?;
+        let declared: Variables = toml::from_str(&content)
+            .with_context(|| format!("Invalid variables file: {}", variables_path.display()))?;
+        for (name, value) in declared {
+            let value = match file_reference(&value) {
+                Some((file, key)) => read_project_value(project_root, file, key)
+                    .with_context(|| format!("Failed to resolve variable '{}'", name))?,
+                None => value,
+            };
+            variables.insert(name, value);
+        }
+    }
+
+    for (name, value) in overrides {
+        variables.insert(name.clone(), value.clone());
+    }
+
+    Ok(variables)
+}
+
+/// A `{ file = "...", key = "..." }` table referring to a value in a project file
+fn file_reference(value: &Value) -> Option<(&str, &str)> {
+    let table = value.as_object()?;
+    if table.len() != 2 {
+        return None;
+    }
+    Some((table.get("file")?.as_str()?, table.get("key")?.as_str()?))
+}
+
+/// Read a value the way declarative steps do: the file must be inside the project, and
+/// the key is a dotted path or JSON pointer
+fn read_project_value(project_root: &Path, file: &str, key: &str) -> Result<Value> {
+    let path = resolve(project_root, file)?;
+    let source =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let document = Document::parse(Format::from_path(&path)?, &source)?;
+    get_path(document.value(), &parse_key(key))
+        .cloned()
+        .with_context(|| format!("Key '{}' not found in {}", key, file))
+}
+
+/// Render a template file or directory to `dest`, returning the files written
+/// (or that would be written in dry-run mode). In a directory, `*.tmpl` files are
+/// rendered with the extension removed and every other file is copied verbatim.
+pub fn render_path(
+    source: &Path,
+    dest: &Path,
+    variables: &Variables,
+    dry_run: bool,
+) -> Result<Vec<PathBuf>> {
+    if !source.exists() {
+        bail!("Template not found: {}", source.display());
+    }
+
+    if source.is_file() {
+        let template = fs::read_to_string(source)
+            .with_context(|| format!("Failed to read template: {}", source.display()))?;
+        let rendered = render(&template, variables)
+            .with_context(|| format!("Failed to render {}", source.display()))?;
+        if !dry_run {
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(dest, rendered)
+                .with_context(|| format!("Failed to write {}", dest.display()))?;
+        }
+        return Ok(vec![dest.to_path_buf()]);
+    }
+
+    let mut written = Vec::new();
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+        if path.is_dir() {
+            written.extend(render_path(&path, &dest.join(&name), variables, dry_run)?);
+        } else if path.extension().is_some_and(|e| e == TEMPLATE_EXTENSION) {
+            let target = dest.join(path.file_stem().unwrap_or(&name));
+            written.extend(render_path(&path, &target, variables, dry_run)?);
+        } else {
+            let target = dest.join(&name);
+            if !dry_run {
+                fs::create_dir_all(dest)?;
+                fs::copy(&path, &target)
+                    .with_context(|| format!("Failed to copy {}", path.display()))?;
+            }
+            written.push(target);
+        }
+    }
+    written.sort();
+    Ok(written)
+}
+
+/// A parsed template node
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Var(String),
+    If {
+        condition: String,
+        negate: bool,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    Each {
+        list: String,
+        body: Vec<Node>,
+    },
+}
+
+#[derive(Debug)]
+enum Token {
+    Text(String),
+    Tag(String),
+}
+
+/// Render a template string.
+///
+/// Supports `{{name}}` (dotted paths like `{{package.name}}`), `{{#if name}}...{{else}}...{{/if}}`,
+/// `{{#unless name}}...{{/unless}}` and `{{#each list}}...{{/each}}`, where the loop body can use
+/// `{{this}}`, `{{@index}}` and fields of the current item. Unknown variables are an error.
+pub fn render(template: &str, variables: &Variables) -> Result<String> {
+    let tokens = tokenize(template)?;
+    let mut tokens = tokens.into_iter().peekable();
+    let nodes = parse(&mut tokens, None)?;
+
+    let root = Value::Object(variables.clone());
+    let mut output = String::new();
+    render_nodes(&nodes, &mut vec![Scope::new(&root)], &mut output)?;
+    Ok(output)
+}
+
+fn tokenize(template: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..]
+            .find("}}")
+            .context("Unclosed '{{' in template")?;
+        if start > 0 {
+            tokens.push(Token::Text(rest[..start].to_string()));
+        }
+        tokens.push(Token::Tag(rest[start + 2..start + end].trim().to_string()));
+        rest = &rest[start + end + 2..];
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest.to_string()));
+    }
+
+    // Block tags on a line of their own don't leave blank lines behind
+    for i in 0..tokens.len() {
+        let is_block =
+            matches!(&tokens[i], Token::Tag(t) if t.starts_with(['#', '/']) || t == "else");
+        if !is_block {
+            continue;
+        }
+        let before_ok = match i.checked_sub(1).map(|j| &tokens[j]) {
+            None => true,
+            Some(Token::Text(t)) => {
+                t.rsplit('\n')
+                    .next()
+                    .is_some_and(|tail| tail.trim().is_empty())
+                    && (t.contains('\n') || i == 1)
+            }
+            Some(Token::Tag(_)) => false,
+        };
+        let after_ok = match tokens.get(i + 1) {
+            None => true,
+            Some(Token::Text(t)) => t
+                .split('\n')
+                .next()
+                .is_some_and(|head| head.trim().is_empty()),
+            Some(Token::Tag(_)) => false,
+        };
+        if !(before_ok && after_ok) {
+            continue;
+        }
+        if let Some(Token::Text(t)) = i.checked_sub(1).and_then(|j| tokens.get_mut(j)) {
+            let keep = t.rfind('\n').map(|pos| pos + 1).unwrap_or(0);
+            t.truncate(keep);
+        }
+        if let Some(Token::Text(t)) = tokens.get_mut(i + 1) {
+            *t = match t.find('\n') {
+                Some(pos) => t[pos + 1..].to_string(),
+                None => String::new(),
+            };
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse(
+    tokens: &mut std::iter::Peekable<std::vec::IntoIter<Token>>,
+    closing: Option<&str>,
+) -> Result<Vec<Node>> {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.peek() {
+        if let Token::Tag(tag) = token {
+            if tag == "else" || tag.starts_with('/') {
+                let tag = tag.clone();
+                return match closing {
+                    Some(expected) if tag == "else" || tag == format!("/{}", expected) => Ok(nodes),
+                    _ => bail!("Unexpected '{{{{{}}}}}' in template", tag),
+                };
+            }
+        }
+
+        match tokens.next() {
+            Some(Token::Text(text)) => nodes.push(Node::Text(text)),
+            Some(Token::Tag(tag)) => {
+                if let Some(block) = tag.strip_prefix('#') {
+                    let (kind, argument) = block
+                        .split_once(char::is_whitespace)
+                        .with_context(|| format!("Missing argument in '{{{{{}}}}}'", tag))?;
+                    let argument = argument.trim().to_string();
+                    let body = parse(tokens, Some(kind))?;
+                    let otherwise = match tokens.next() {
+                        Some(Token::Tag(t)) if t == "else" => {
+                            let otherwise = parse(tokens, Some(kind))?;
+                            match tokens.next() {
+                                Some(Token::Tag(t)) if t == format!("/{}", kind) => {}
+                                _ => bail!("Missing '{{{{/{}}}}}' in template", kind),
+                            }
+                            otherwise
+                        }
+                        Some(Token::Tag(t)) if t == format!("/{}", kind) => Vec::new(),
+                        _ => bail!("Missing '{{{{/{}}}}}' in template", kind),
+                    };
+                    nodes.push(match kind {
+                        "if" | "unless" => Node::If {
+                            condition: argument,
+                            negate: kind == "unless",
+                            then: body,
+                            otherwise,
+                        },
+                        "each" => Node::Each {
+                            list: argument,
+                            body,
+                        },
+                        other => bail!("Unknown block '#{}' in template", other),
+                    });
+                } else {
+                    nodes.push(Node::Var(tag));
+                }
+            }
+            None => break,
+        }
+    }
+
+    match closing {
+        Some(expected) => bail!("Missing '{{{{/{}}}}}' in template", expected),
+        None => Ok(nodes),
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Scope<'a> {
+    value: &'a Value,
+    index: Option<usize>,
+}
+
+impl<'a> Scope<'a> {
+    fn new(value: &'a Value) -> Self {
+        Self { value, index: None }
+    }
+}
+
+fn resolve_name(scopes: &[Scope], name: &str) -> Option<Value> {
+    let current = scopes.last()?;
+    match name {
+        "this" | "." => return Some(current.value.clone()),
+        "@index" => return current.index.map(Value::from),
+        _ => {}
+    }
+    let segments = parse_key(name.strip_prefix("this.").unwrap_or(name));
+    scopes
+        .iter()
+        .rev()
+        .find_map(|scope| get_path(scope.value, &segments))
+        .cloned()
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+fn render_nodes<'a>(
+    nodes: &[Node],
+    scopes: &mut Vec<Scope<'a>>,
+    output: &mut String,
+) -> Result<()> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Var(name) => {
+                let value = resolve_name(scopes, name)
+                    .with_context(|| format!("Unknown template variable '{}'", name))?;
+                match value {
+                    Value::String(s) => output.push_str(&s),
+                    Value::Null => {}
+                    other => output.push_str(&other.to_string()),
+                }
+            }
+            Node::If {
+                condition,
+                negate,
+                then,
+                otherwise,
+            } => {
+                let truthy = resolve_name(scopes, condition).is_some_and(|v| is_truthy(&v));
+                let branch = if truthy != *negate { then } else { otherwise };
+                render_nodes(branch, scopes, output)?;
+            }
+            Node::Each { list, body } => {
+                let items = match resolve_name(scopes, list) {
+                    Some(Value::Array(items)) => items,
+                    Some(Value::Null) | None => Vec::new(),
+                    Some(_) => bail!("'{}' is not a list", list),
+                };
+                for (index, item) in items.iter().enumerate() {
+                    let mut inner = scopes.clone();
+                    inner.push(Scope {
+                        value: item,
+                        index: Some(index),
+                    });
+                    render_nodes(body, &mut inner, output)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn vars(value: Value) -> Variables {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_render_variables() {
+        let variables = vars(json!({"name": "app", "port": 8080, "package": {"name": "pkg"}}));
+        assert_eq!(
+            render("{{name}}:{{ port }} ({{package.name}})", &variables).unwrap(),
+            "app:8080 (pkg)"
+        );
+        assert!(render("{{missing}}", &variables).is_err());
+        assert!(render("{{name", &variables).is_err());
+    }
+
+    #[test]
+    fn test_render_conditionals() {
+        let variables = vars(json!({"tls": true, "debug": false}));
+        let template = "{{#if tls}}https{{else}}http{{/if}}{{#unless debug}}!{{/unless}}";
+        assert_eq!(render(template, &variables).unwrap(), "https!");
+        assert!(render("{{#if tls}}unclosed", &variables).is_err());
+    }
+
+    #[test]
+    fn test_render_loops_strip_standalone_tags() {
+        let variables = vars(json!({
+            "org": "glide",
+            "services": [{"name": "api", "port": 80}, {"name": "web", "port": 81}]
+        }));
+        let template = "services:\n{{#each services}}\n  - {{@index}} {{org}}/{{name}}:{{port}}\n{{/each}}\ndone\n";
+        assert_eq!(
+            render(template, &variables).unwrap(),
+            "services:\n  - 0 glide/api:80\n  - 1 glide/web:81\ndone\n"
+        );
+    }
+
+    #[test]
+    fn test_load_variables_sources() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let migrations = root.join("migrations");
+        fs::create_dir(&migrations).unwrap();
+        fs::write(
+            root.join("package.json"),
+            r#"{"name": "sample", "version": "1.0.0"}"#,
+        )
+        .unwrap();
+        fs::write(
+            migrations.join(VARIABLES_FILE),
+            "org = \"glide\"\nport = 3000\npackage_name = { file = \"package.json\", key = \"name\" }\n",
+        )
+        .unwrap();
+
+        let variables =
+            load_variables(root, &migrations, &[("port".to_string(), json!(8080))]).unwrap();
+        assert_eq!(variables["org"], "glide");
+        assert_eq!(variables["package_name"], "sample");
+        assert_eq!(variables["port"], 8080);
+
+        // Files outside the project can't be read
+        fs::write(
+            migrations.join(VARIABLES_FILE),
+            "secret = { file = \"../outside.json\", key = \"token\" }\n",
+        )
+        .unwrap();
+        let error = load_variables(root, &migrations, &[]).unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("must be relative and name something inside"),
+            "{:#}",
+            error
+        );
+    }
+}
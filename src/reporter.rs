@@ -0,0 +1,632 @@
+use serde::{Serialize, Serializer};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::migrator::{BaselineReport, Progress};
+use crate::{Message, Stream};
+
+/// Something that happened while applying migrations, as passed to a [`Reporter`]
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event<'a> {
+    /// Pending migrations are about to be applied (or previewed)
+    RunStarted {
+        pending: usize,
+        dry_run: bool,
+    },
+    MigrationStarted {
+        id: &'a str,
+    },
+    /// A line of output from the running migration
+    Output {
+        id: &'a str,
+        stream: Stream,
+        line: &'a str,
+    },
+    /// A structured message from the running migration, with its fields inlined
+    Message {
+        id: &'a str,
+        #[serde(flatten)]
+        message: &'a Message,
+    },
+    MigrationFinished {
+        id: &'a str,
+        status: MigrationStatus,
+        #[serde(rename = "duration_ms", serialize_with = "as_millis")]
+        duration: Duration,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        exit_code: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<&'a str>,
+        /// Why a migration was not applicable
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reason: Option<&'a str>,
+    },
+    RunFinished {
+        success: bool,
+        /// Number of migrations recorded in history
+        applied: usize,
+        touched_files: Vec<TouchedFile<'a>>,
+        /// Warnings, notes and follow-ups sent during the run
+        messages: Vec<MigrationMessage<'a>>,
+    },
+    BaselineCreated {
+        version: &'a str,
+        dry_run: bool,
+        kept_files: bool,
+        /// Migrations at or before the baseline version
+        covered: usize,
+        /// Covered migrations that have an asset directory
+        asset_dirs: usize,
+    },
+    /// Migration files and asset directories removed by a baseline
+    FilesDeleted {
+        files: Vec<&'a str>,
+        directories: Vec<&'a str>,
+    },
+    /// Informational message that isn't tied to a migration
+    Notice {
+        message: &'a str,
+    },
+    /// A command is about to run in one workspace of a `--workspaces` run
+    WorkspaceStarted {
+        name: &'a str,
+    },
+    WorkspaceFinished {
+        name: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<&'a str>,
+    },
+    /// Every workspace has been run
+    WorkspacesFinished {
+        total: usize,
+        failed: Vec<&'a str>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MigrationStatus {
+    Applied,
+    /// Dry run of a migration that can preview its changes
+    Previewed,
+    /// Dry run of a script migration, which is not run
+    Skipped,
+    /// Recorded without effect: a `RunIf` condition did not hold or the script said so
+    NotApplicable,
+    Failed,
+}
+
+/// A file changed by a migration, relative to the project root
+#[derive(Debug, Serialize)]
+pub struct TouchedFile<'a> {
+    pub id: &'a str,
+    pub path: String,
+}
+
+/// A warning, note or follow-up sent by a migration
+#[derive(Debug, Serialize)]
+pub struct MigrationMessage<'a> {
+    pub id: &'a str,
+    #[serde(flatten)]
+    pub message: &'a Message,
+}
+
+fn as_millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis() as u64)
+}
+
+impl<'a> From<Progress<'a>> for Event<'a> {
+    fn from(progress: Progress<'a>) -> Self {
+        match progress {
+            Progress::Started(migration) => Event::MigrationStarted { id: &migration.id },
+            Progress::Output {
+                migration,
+                line,
+                stream,
+            } => Event::Output {
+                id: &migration.id,
+                stream,
+                line,
+            },
+            Progress::Message { migration, message } => Event::Message {
+                id: &migration.id,
+                message,
+            },
+            Progress::Skipped {
+                migration,
+                preview,
+                duration,
+            } => Event::MigrationFinished {
+                id: &migration.id,
+                status: if preview.is_some() {
+                    MigrationStatus::Previewed
+                } else {
+                    MigrationStatus::Skipped
+                },
+                duration,
+                exit_code: None,
+                error: preview.and_then(|r| r.error.as_deref()),
+                reason: None,
+            },
+            Progress::Completed {
+                migration,
+                duration,
+                ..
+            } => Event::MigrationFinished {
+                id: &migration.id,
+                status: MigrationStatus::Applied,
+                duration,
+                exit_code: None,
+                error: None,
+                reason: None,
+            },
+            Progress::NotApplicable {
+                migration,
+                reason,
+                duration,
+            } => Event::MigrationFinished {
+                id: &migration.id,
+                status: MigrationStatus::NotApplicable,
+                duration,
+                exit_code: None,
+                error: None,
+                reason: Some(reason),
+            },
+            Progress::Failed {
+                migration,
+                result,
+                duration,
+            } => Event::MigrationFinished {
+                id: &migration.id,
+                status: MigrationStatus::Failed,
+                duration,
+                exit_code: Some(result.exit_code),
+                error: result.error.as_deref(),
+                reason: None,
+            },
+        }
+    }
+}
+
+impl<'a> From<&'a BaselineReport> for Event<'a> {
+    fn from(report: &'a BaselineReport) -> Self {
+        Event::BaselineCreated {
+            version: &report.version,
+            dry_run: report.dry_run,
+            kept_files: report.kept_files,
+            covered: report.covered.len(),
+            asset_dirs: report.covered.iter().filter(|c| c.has_asset_dir).count(),
+        }
+    }
+}
+
+/// Presents migration events to the user (or to another program)
+pub trait Reporter {
+    fn report(&self, event: &Event<'_>);
+}
+
+/// Output format for `migrate up`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Progress for people at a terminal
+    #[default]
+    Human,
+    /// Nothing unless a migration fails, then its output on stderr
+    Quiet,
+    /// One JSON object per event on stdout
+    Json,
+    /// Human output with each migration in a collapsible GitHub Actions group
+    Github,
+}
+
+impl Format {
+    pub fn reporter(self) -> Box<dyn Reporter> {
+        match self {
+            Format::Human => Box::new(HumanReporter::default()),
+            Format::Quiet => Box::new(QuietReporter::default()),
+            Format::Json => Box::new(JsonReporter),
+            Format::Github => Box::new(GithubReporter::default()),
+        }
+    }
+}
+
+/// Progress for people at a terminal. While several migrations run at once (`--jobs`),
+/// their lines are tagged with the migration they belong to.
+#[derive(Default)]
+pub struct HumanReporter {
+    running: Running,
+}
+
+/// The migrations that have started and not finished, to attribute their lines
+#[derive(Default)]
+struct Running {
+    ids: RefCell<Vec<String>>,
+    /// The migration whose header was printed last
+    last_started: RefCell<Option<String>>,
+}
+
+impl Running {
+    fn start(&self, id: &str) {
+        self.ids.borrow_mut().push(id.to_string());
+        *self.last_started.borrow_mut() = Some(id.to_string());
+    }
+
+    fn finish(&self, id: &str) {
+        self.ids.borrow_mut().retain(|running| running != id);
+    }
+
+    /// Nothing for a line that belongs under the last header printed, otherwise `[id] `
+    fn tag(&self, id: &str) -> String {
+        let alone = self.ids.borrow().len() <= 1;
+        if alone && self.last_started.borrow().as_deref() == Some(id) {
+            String::new()
+        } else {
+            format!("[{}] ", id)
+        }
+    }
+}
+
+impl Reporter for HumanReporter {
+    fn report(&self, event: &Event<'_>) {
+        match event {
+            Event::RunStarted { pending: 0, .. } => println!("No pending migrations."),
+            Event::RunStarted { pending, dry_run } => {
+                println!(
+                    "{} {} migration(s)...",
+                    if *dry_run { "Would apply" } else { "Applying" },
+                    pending
+                );
+                println!();
+            }
+            Event::MigrationStarted { id } => {
+                self.running.start(id);
+                println!("→ {}", id)
+            }
+            Event::Output {
+                id,
+                line,
+                stream: Stream::Stdout,
+            } => println!("  {}{}", self.running.tag(id), line),
+            Event::Output { id, line, .. } => eprintln!("  {}{}", self.running.tag(id), line),
+            Event::Message { id, message } => println!("  {}{}", self.running.tag(id), message),
+            Event::MigrationFinished {
+                id,
+                status,
+                duration,
+                exit_code,
+                error,
+                reason,
+            } => {
+                let tag = self.running.tag(id);
+                self.running.finish(id);
+                match status {
+                    MigrationStatus::Applied => {
+                        println!("  {}✓ completed ({})", tag, format_duration(*duration))
+                    }
+                    MigrationStatus::NotApplicable => {
+                        println!("  {}○ not applicable: {}", tag, reason.unwrap_or("skipped"))
+                    }
+                    MigrationStatus::Previewed | MigrationStatus::Skipped => {
+                        if let Some(error) = error {
+                            println!("  {}✗ {}", tag, error);
+                        }
+                        println!("  {}(dry run - skipped)", tag);
+                    }
+                    MigrationStatus::Failed => {
+                        println!("  {}✗ failed (exit code {})", tag, exit_code.unwrap_or(-1));
+                        if let Some(error) = error {
+                            println!("    {}{}", tag, error);
+                        }
+                    }
+                }
+            }
+            Event::RunFinished {
+                success,
+                touched_files,
+                messages,
+                ..
+            } => {
+                if *success {
+                    println!();
+                    println!("All migrations applied successfully.");
+                }
+                print_messages(messages);
+                if *success && !touched_files.is_empty() {
+                    println!();
+                    println!("Files touched ({}):", touched_files.len());
+                    for file in touched_files {
+                        println!("  {}  ({})", file.path, file.id);
+                    }
+                }
+            }
+            Event::BaselineCreated {
+                version,
+                dry_run: true,
+                kept_files,
+                covered,
+                asset_dirs,
+            } => {
+                println!();
+                println!("Would create baseline at version '{}'", version);
+                if !kept_files && *covered > 0 {
+                    if *asset_dirs > 0 {
+                        println!(
+                            "Would delete {} migration file(s) and {} asset directory(ies)",
+                            covered, asset_dirs
+                        );
+                    } else {
+                        println!("Would delete {} migration file(s)", covered);
+                    }
+                }
+            }
+            Event::BaselineCreated { version, .. } => {
+                println!();
+                println!("Created baseline at version '{}'", version);
+            }
+            Event::FilesDeleted { files, directories } => {
+                if !files.is_empty() {
+                    println!("Deleted {} migration file(s)", files.len());
+                }
+                if !directories.is_empty() {
+                    println!("Deleted {} asset directory(ies)", directories.len());
+                }
+            }
+            Event::Notice { message } => println!("{}", message),
+            Event::WorkspaceStarted { .. }
+            | Event::WorkspaceFinished { .. }
+            | Event::WorkspacesFinished { .. } => print!("{}", workspace_text(event)),
+        }
+    }
+}
+
+/// Human-readable text for the workspace events, ending in a newline
+fn workspace_text(event: &Event<'_>) -> String {
+    match event {
+        Event::WorkspaceStarted { name } => format!("=== {} ===\n", name),
+        // A blank line separates each workspace from the next one or the summary
+        Event::WorkspaceFinished {
+            error: Some(error), ..
+        } => format!("Error: {}\n\n", error),
+        Event::WorkspaceFinished { .. } => "\n".to_string(),
+        Event::WorkspacesFinished { total, failed } if failed.is_empty() => {
+            format!("{} workspace(s) OK\n", total)
+        }
+        Event::WorkspacesFinished { total, failed } => format!(
+            "{} of {} workspace(s) failed: {}\n",
+            failed.len(),
+            total,
+            failed.join(", ")
+        ),
+        _ => String::new(),
+    }
+}
+
+/// Repeat the warnings, notes and follow-ups sent during a run, grouped by kind
+fn print_messages(messages: &[MigrationMessage<'_>]) {
+    for (kind, title) in [
+        ("warning", "Warnings"),
+        ("note", "Notes"),
+        ("follow-up", "Manual follow-up needed"),
+    ] {
+        let matching: Vec<_> = messages
+            .iter()
+            .filter(|m| m.message.kind() == kind)
+            .collect();
+        if matching.is_empty() {
+            continue;
+        }
+        println!();
+        println!("{} ({}):", title, matching.len());
+        for m in matching {
+            println!("  {}  ({})", m.message.text().unwrap_or_default(), m.id);
+        }
+    }
+}
+
+/// Keeps each migration's output and only prints it (to stderr) if the migration fails
+#[derive(Default)]
+pub struct QuietReporter {
+    /// Output of the migrations still running, by ID
+    output: RefCell<HashMap<String, Vec<String>>>,
+}
+
+impl Reporter for QuietReporter {
+    fn report(&self, event: &Event<'_>) {
+        match event {
+            Event::MigrationStarted { id } => {
+                self.output.borrow_mut().remove(*id);
+            }
+            Event::Output { id, line, .. } => self
+                .output
+                .borrow_mut()
+                .entry(id.to_string())
+                .or_default()
+                .push(line.to_string()),
+            Event::MigrationFinished {
+                id,
+                status: MigrationStatus::Failed,
+                ..
+            } => {
+                eprintln!("✗ {}", id);
+                for line in self.output.borrow_mut().remove(*id).unwrap_or_default() {
+                    eprintln!("  {}", line);
+                }
+            }
+            Event::MigrationFinished { id, .. } => {
+                self.output.borrow_mut().remove(*id);
+            }
+            Event::WorkspaceFinished {
+                name,
+                error: Some(error),
+            } => eprintln!("✗ {}: {}", name, error),
+            _ => {}
+        }
+    }
+}
+
+/// Newline-delimited JSON, one object per event with its kind in `"event"`
+pub struct JsonReporter;
+
+impl Reporter for JsonReporter {
+    fn report(&self, event: &Event<'_>) {
+        if let Ok(line) = serde_json::to_string(event) {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Human output using GitHub Actions workflow commands: a log group per migration and an
+/// error annotation for a failure
+#[derive(Default)]
+pub struct GithubReporter {
+    human: HumanReporter,
+}
+
+impl Reporter for GithubReporter {
+    fn report(&self, event: &Event<'_>) {
+        match event {
+            Event::MigrationStarted { id } => {
+                self.human.running.start(id);
+                println!("::group::{}", id)
+            }
+            Event::Output {
+                id,
+                line,
+                stream: Stream::Stdout,
+            } => println!("{}{}", self.human.running.tag(id), line),
+            Event::Output { id, line, .. } => eprintln!("{}{}", self.human.running.tag(id), line),
+            Event::Message { id, message } => {
+                self.human.report(event);
+                let text = escape_workflow_data(message.text().unwrap_or_default());
+                match message {
+                    Message::Warning { .. } => {
+                        println!("::warning title=Migration {}::{}", id, text)
+                    }
+                    Message::FollowUp { .. } => {
+                        println!("::notice title=Manual follow-up for {}::{}", id, text)
+                    }
+                    _ => {}
+                }
+            }
+            Event::MigrationFinished {
+                id, status, error, ..
+            } => {
+                self.human.report(event);
+                println!("::endgroup::");
+                if *status == MigrationStatus::Failed {
+                    println!(
+                        "::error title=Migration {} failed::{}",
+                        id,
+                        escape_workflow_data(error.unwrap_or("Migration failed"))
+                    );
+                }
+            }
+            // Keep stdout to migration output and workflow commands
+            Event::WorkspaceStarted { .. }
+            | Event::WorkspaceFinished { .. }
+            | Event::WorkspacesFinished { .. } => eprint!("{}", workspace_text(event)),
+            _ => self.human.report(event),
+        }
+    }
+}
+
+/// Escape a workflow command message so it stays on one line
+fn escape_workflow_data(message: &str) -> String {
+    message
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+pub(crate) fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{}ms", duration.as_millis())
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_events() {
+        let event = Event::MigrationFinished {
+            id: "1f700-init",
+            status: MigrationStatus::Failed,
+            duration: Duration::from_millis(1500),
+            exit_code: Some(2),
+            error: None,
+            reason: None,
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"migration-finished","id":"1f700-init","status":"failed","duration_ms":1500,"exit_code":2}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Event::RunStarted {
+                pending: 2,
+                dry_run: false
+            })
+            .unwrap(),
+            r#"{"event":"run-started","pending":2,"dry_run":false}"#
+        );
+        let event = Event::MigrationFinished {
+            id: "1f710-react",
+            status: MigrationStatus::NotApplicable,
+            duration: Duration::from_millis(3),
+            exit_code: None,
+            error: None,
+            reason: Some("RunIf 'file-exists package.json' does not hold"),
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"migration-finished","id":"1f710-react","status":"not-applicable","duration_ms":3,"reason":"RunIf 'file-exists package.json' does not hold"}"#
+        );
+        let message = Message::FollowUp {
+            message: "rotate the API key".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_string(&Event::Message {
+                id: "1f720-keys",
+                message: &message
+            })
+            .unwrap(),
+            r#"{"event":"message","id":"1f720-keys","type":"follow-up","message":"rotate the API key"}"#
+        );
+    }
+
+    #[test]
+    fn test_workspace_events() {
+        assert_eq!(
+            serde_json::to_string(&Event::WorkspaceFinished {
+                name: "packages/api",
+                error: Some("Migration 00001-init failed with exit code 3"),
+            })
+            .unwrap(),
+            r#"{"event":"workspace-finished","name":"packages/api","error":"Migration 00001-init failed with exit code 3"}"#
+        );
+        let summary = Event::WorkspacesFinished {
+            total: 2,
+            failed: vec!["packages/api"],
+        };
+        assert_eq!(
+            serde_json::to_string(&summary).unwrap(),
+            r#"{"event":"workspaces-finished","total":2,"failed":["packages/api"]}"#
+        );
+        assert_eq!(
+            workspace_text(&summary),
+            "1 of 2 workspace(s) failed: packages/api\n"
+        );
+    }
+
+    #[test]
+    fn test_format_duration_and_escape() {
+        assert_eq!(format_duration(Duration::from_millis(42)), "42ms");
+        assert_eq!(format_duration(Duration::from_millis(2340)), "2.3s");
+        assert_eq!(escape_workflow_data("50%\nfailed"), "50%25%0Afailed");
+    }
+}
@@ -0,0 +1,547 @@
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::reporter::{format_duration, Event, MigrationStatus, Reporter};
+use crate::{Message, Stream};
+
+/// A report file requested with `--report <kind>=<path>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportSpec {
+    pub kind: ReportKind,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportKind {
+    /// JUnit XML, one test case per migration
+    Junit,
+    /// A Markdown table, e.g. for a pull request comment
+    Markdown,
+}
+
+/// Parse `junit=path.xml` or `markdown=path.md` (clap value parser)
+pub fn parse_report_spec(raw: &str) -> Result<ReportSpec, String> {
+    let (kind, path) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("Expected <kind>=<path>, got '{}'", raw))?;
+    let kind = match kind {
+        "junit" => ReportKind::Junit,
+        "markdown" | "md" => ReportKind::Markdown,
+        other => {
+            return Err(format!(
+                "Unknown report kind '{}' (expected junit or markdown)",
+                other
+            ))
+        }
+    };
+    if path.is_empty() {
+        return Err("Report path is empty".to_string());
+    }
+    Ok(ReportSpec {
+        kind,
+        path: PathBuf::from(path),
+    })
+}
+
+/// What happened to one migration in a run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaseOutcome {
+    Passed,
+    Failed { exit_code: i32, message: String },
+    Skipped { reason: String },
+}
+
+/// One migration as a test case
+#[derive(Debug, Clone)]
+pub struct TestCase {
+    pub id: String,
+    pub outcome: CaseOutcome,
+    pub duration: Duration,
+    /// Captured output lines, in the order they arrived
+    pub output: Vec<(Stream, String)>,
+    /// Warnings, notes and follow-ups the migration sent
+    pub messages: Vec<Message>,
+}
+
+impl TestCase {
+    fn lines(&self, stream: Stream) -> Vec<&str> {
+        self.output
+            .iter()
+            .filter(|(s, _)| *s == stream)
+            .map(|(_, line)| line.as_str())
+            .collect()
+    }
+}
+
+/// Collects test cases from reporter events while migrations run
+#[derive(Default)]
+pub struct RunRecorder {
+    cases: RefCell<Vec<TestCase>>,
+    /// Output and messages of the migrations still running, by ID; with `--jobs` their
+    /// events arrive interleaved
+    output: RefCell<HashMap<String, Vec<(Stream, String)>>>,
+    messages: RefCell<HashMap<String, Vec<Message>>>,
+}
+
+impl Reporter for RunRecorder {
+    fn report(&self, event: &Event<'_>) {
+        match event {
+            Event::MigrationStarted { id } => {
+                self.output.borrow_mut().remove(*id);
+                self.messages.borrow_mut().remove(*id);
+            }
+            Event::Output { id, stream, line } => self
+                .output
+                .borrow_mut()
+                .entry(id.to_string())
+                .or_default()
+                .push((*stream, line.to_string())),
+            Event::Message { id, message } if message.is_summarized() => self
+                .messages
+                .borrow_mut()
+                .entry(id.to_string())
+                .or_default()
+                .push((*message).clone()),
+            Event::MigrationFinished {
+                id,
+                status,
+                duration,
+                exit_code,
+                error,
+                reason,
+            } => {
+                let outcome = match status {
+                    MigrationStatus::Applied => CaseOutcome::Passed,
+                    MigrationStatus::Failed => CaseOutcome::Failed {
+                        exit_code: exit_code.unwrap_or(-1),
+                        message: error
+                            .map(str::to_string)
+                            .unwrap_or_else(|| format!("Migration {} failed", id)),
+                    },
+                    MigrationStatus::NotApplicable => CaseOutcome::Skipped {
+                        reason: format!("not applicable: {}", reason.unwrap_or("skipped")),
+                    },
+                    MigrationStatus::Previewed | MigrationStatus::Skipped => CaseOutcome::Skipped {
+                        reason: match error {
+                            Some(error) => format!("dry run (preview failed: {})", error),
+                            None => "dry run".to_string(),
+                        },
+                    },
+                };
+                self.cases.borrow_mut().push(TestCase {
+                    id: id.to_string(),
+                    outcome,
+                    duration: *duration,
+                    output: self.output.borrow_mut().remove(*id).unwrap_or_default(),
+                    messages: self.messages.borrow_mut().remove(*id).unwrap_or_default(),
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+impl RunRecorder {
+    /// Record a migration that did not run
+    pub fn skip(&self, id: &str, reason: &str) {
+        self.cases.borrow_mut().push(TestCase {
+            id: id.to_string(),
+            outcome: CaseOutcome::Skipped {
+                reason: reason.to_string(),
+            },
+            duration: Duration::ZERO,
+            output: Vec::new(),
+            messages: Vec::new(),
+        });
+    }
+
+    pub fn has_case(&self, id: &str) -> bool {
+        self.cases.borrow().iter().any(|c| c.id == id)
+    }
+
+    /// Recorded cases, sorted by migration ID (i.e. version order)
+    pub fn cases(&self) -> Vec<TestCase> {
+        let mut cases = self.cases.borrow().clone();
+        cases.sort_by(|a, b| a.id.cmp(&b.id));
+        cases
+    }
+}
+
+/// The test cases of one project's run
+#[derive(Debug, Clone)]
+pub struct Suite {
+    pub name: String,
+    pub cases: Vec<TestCase>,
+}
+
+/// Write a report of `suites` in the requested format
+pub fn write_report(spec: &ReportSpec, dry_run: bool, suites: &[Suite]) -> Result<()> {
+    let content = match spec.kind {
+        ReportKind::Junit => junit(suites),
+        ReportKind::Markdown => suites
+            .iter()
+            .map(|suite| markdown(&suite.name, dry_run, &suite.cases))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+    if let Some(parent) = spec.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&spec.path, content)
+        .with_context(|| format!("Failed to write report {}", spec.path.display()))
+}
+
+/// Suite name for reports: the project directory's name
+pub fn suite_name(project_root: &Path) -> String {
+    project_root
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "migrate".to_string())
+}
+
+fn junit(suites: &[Suite]) -> String {
+    let all: Vec<&TestCase> = suites.iter().flat_map(|s| &s.cases).collect();
+    let (failures, skipped, time) = totals(all.iter().copied());
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"migrate\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+        all.len(),
+        failures,
+        skipped,
+        time.as_secs_f64()
+    );
+    for suite in suites {
+        junit_suite(&mut xml, &suite.name, &suite.cases);
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// Failed and skipped counts and total duration of `cases`
+fn totals<'a>(cases: impl Iterator<Item = &'a TestCase>) -> (usize, usize, Duration) {
+    cases.fold(
+        (0, 0, Duration::ZERO),
+        |(failures, skipped, time), case| match case.outcome {
+            CaseOutcome::Passed => (failures, skipped, time + case.duration),
+            CaseOutcome::Failed { .. } => (failures + 1, skipped, time + case.duration),
+            CaseOutcome::Skipped { .. } => (failures, skipped + 1, time + case.duration),
+        },
+    )
+}
+
+fn junit_suite(xml: &mut String, suite: &str, cases: &[TestCase]) {
+    let (failures, skipped, time) = totals(cases.iter());
+    let _ = writeln!(
+        xml,
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+        escape_xml(suite),
+        cases.len(),
+        failures,
+        skipped,
+        time.as_secs_f64()
+    );
+    for case in cases {
+        let _ = write!(
+            xml,
+            "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+            escape_xml(suite),
+            escape_xml(&case.id),
+            case.duration.as_secs_f64()
+        );
+        let stdout = case.lines(Stream::Stdout);
+        let stderr = case.lines(Stream::Stderr);
+        if case.outcome == CaseOutcome::Passed && case.output.is_empty() && case.messages.is_empty()
+        {
+            xml.push_str("/>\n");
+            continue;
+        }
+        xml.push_str(">\n");
+        if !case.messages.is_empty() {
+            xml.push_str("      <properties>\n");
+            for message in &case.messages {
+                let _ = writeln!(
+                    xml,
+                    "        <property name=\"{}\" value=\"{}\"/>",
+                    message.kind(),
+                    escape_xml(message.text().unwrap_or_default())
+                );
+            }
+            xml.push_str("      </properties>\n");
+        }
+        match &case.outcome {
+            CaseOutcome::Passed => {}
+            CaseOutcome::Failed { exit_code, message } => {
+                // The failure body is what a reader needs first: stderr, else all output
+                let body = if stderr.is_empty() {
+                    case.output.iter().map(|(_, l)| l.as_str()).collect()
+                } else {
+                    stderr.clone()
+                };
+                let _ = writeln!(
+                    xml,
+                    "      <failure message=\"{}\" type=\"exit code {}\">{}</failure>",
+                    escape_xml(message),
+                    exit_code,
+                    escape_xml(&body.join("\n"))
+                );
+            }
+            CaseOutcome::Skipped { reason } => {
+                let _ = writeln!(xml, "      <skipped message=\"{}\"/>", escape_xml(reason));
+            }
+        }
+        if !stdout.is_empty() {
+            let _ = writeln!(
+                xml,
+                "      <system-out>{}</system-out>",
+                escape_xml(&stdout.join("\n"))
+            );
+        }
+        if !stderr.is_empty() {
+            let _ = writeln!(
+                xml,
+                "      <system-err>{}</system-err>",
+                escape_xml(&stderr.join("\n"))
+            );
+        }
+        xml.push_str("    </testcase>\n");
+    }
+    xml.push_str("  </testsuite>\n");
+}
+
+fn markdown(suite: &str, dry_run: bool, cases: &[TestCase]) -> String {
+    let count = |f: fn(&CaseOutcome) -> bool| cases.iter().filter(|c| f(&c.outcome)).count();
+    let passed = count(|o| *o == CaseOutcome::Passed);
+    let failed = count(|o| matches!(o, CaseOutcome::Failed { .. }));
+    let skipped = count(|o| matches!(o, CaseOutcome::Skipped { .. }));
+
+    let mut md = format!(
+        "## Migrations: {}{}\n\n",
+        suite,
+        if dry_run { " (dry run)" } else { "" }
+    );
+    let _ = writeln!(
+        md,
+        "**{} passed, {} failed, {} skipped**\n",
+        passed, failed, skipped
+    );
+    if cases.is_empty() {
+        md.push_str("No pending migrations.\n");
+        return md;
+    }
+
+    md.push_str("| | Migration | Result | Duration |\n|---|---|---|---|\n");
+    for case in cases {
+        let (icon, result) = match &case.outcome {
+            CaseOutcome::Passed => ("✅", "passed".to_string()),
+            CaseOutcome::Failed { exit_code, .. } => {
+                ("❌", format!("failed (exit code {})", exit_code))
+            }
+            CaseOutcome::Skipped { reason } => ("⏭️", format!("skipped: {}", reason)),
+        };
+        let duration = match case.outcome {
+            CaseOutcome::Skipped { .. } => String::new(),
+            _ => format_duration(case.duration),
+        };
+        let _ = writeln!(
+            md,
+            "| {} | `{}` | {} | {} |",
+            icon,
+            case.id,
+            escape_table_cell(&result),
+            duration
+        );
+    }
+
+    let messages: Vec<_> = cases
+        .iter()
+        .flat_map(|c| c.messages.iter().map(move |m| (&c.id, m)))
+        .collect();
+    if !messages.is_empty() {
+        md.push_str("\n### Messages\n\n");
+        for (id, message) in messages {
+            let (icon, label) = match message {
+                Message::Warning { .. } => ("⚠️", "Warning"),
+                Message::FollowUp { .. } => ("🛠️", "Manual follow-up"),
+                _ => ("ℹ️", "Note"),
+            };
+            let _ = writeln!(
+                md,
+                "- {} **{}** (`{}`): {}",
+                icon,
+                label,
+                id,
+                message.text().unwrap_or_default()
+            );
+        }
+    }
+
+    for case in cases {
+        let CaseOutcome::Failed { message, .. } = &case.outcome else {
+            continue;
+        };
+        let _ = write!(
+            md,
+            "\n<details><summary><code>{}</code> failed</summary>\n\n{}\n\n",
+            case.id,
+            escape_html(message)
+        );
+        if !case.output.is_empty() {
+            let fence = code_fence(case.output.iter().map(|(_, line)| line.as_str()));
+            let _ = writeln!(md, "{}", fence);
+            for (_, line) in &case.output {
+                md.push_str(line);
+                md.push('\n');
+            }
+            let _ = writeln!(md, "{}\n", fence);
+        }
+        md.push_str("</details>\n");
+    }
+    md
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than tab, newline and carriage return are not allowed
+            // in XML 1.0
+            c if c.is_control() && !matches!(c, '\n' | '\t' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// A backtick fence longer than any run of backticks in `lines`, so the output can't close it
+fn code_fence<'a>(lines: impl Iterator<Item = &'a str>) -> String {
+    let longest = lines
+        .flat_map(|line| line.split(|c| c != '`'))
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn escape_table_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cases() -> Vec<TestCase> {
+        vec![
+            TestCase {
+                id: "1f700-init".to_string(),
+                outcome: CaseOutcome::Passed,
+                duration: Duration::from_millis(120),
+                output: vec![(Stream::Stdout, "created <config>".to_string())],
+                messages: vec![Message::FollowUp {
+                    message: "rotate the API key".to_string(),
+                }],
+            },
+            TestCase {
+                id: "1f710-broken".to_string(),
+                outcome: CaseOutcome::Failed {
+                    exit_code: 2,
+                    message: "Migration 1f710-broken failed with exit code 2".to_string(),
+                },
+                duration: Duration::from_millis(40),
+                output: vec![
+                    (Stream::Stdout, "starting".to_string()),
+                    (Stream::Stderr, "config.json: not found".to_string()),
+                ],
+                messages: Vec::new(),
+            },
+            TestCase {
+                id: "1f720-later".to_string(),
+                outcome: CaseOutcome::Skipped {
+                    reason: "not run after an earlier failure".to_string(),
+                },
+                duration: Duration::ZERO,
+                output: Vec::new(),
+                messages: Vec::new(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_parse_report_spec() {
+        assert_eq!(
+            parse_report_spec("junit=out/report.xml").unwrap(),
+            ReportSpec {
+                kind: ReportKind::Junit,
+                path: PathBuf::from("out/report.xml")
+            }
+        );
+        assert!(parse_report_spec("html=report.html").is_err());
+        assert!(parse_report_spec("report.xml").is_err());
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let xml = junit(&[Suite {
+            name: "app".to_string(),
+            cases: cases(),
+        }]);
+        assert!(xml.contains(
+            "<testsuite name=\"app\" tests=\"3\" failures=\"1\" skipped=\"1\" time=\"0.160\">"
+        ));
+        assert!(xml.contains("<system-out>created &lt;config&gt;</system-out>"));
+        assert!(xml.contains("type=\"exit code 2\">config.json: not found</failure>"));
+        assert!(xml.contains("<skipped message=\"not run after an earlier failure\"/>"));
+        assert!(xml.contains("<property name=\"follow-up\" value=\"rotate the API key\"/>"));
+    }
+
+    #[test]
+    fn test_markdown_report() {
+        let md = markdown("app", false, &cases());
+        assert!(md.contains("**1 passed, 1 failed, 1 skipped**"));
+        assert!(md.contains("| ✅ | `1f700-init` | passed | 120ms |"));
+        assert!(md.contains("| ❌ | `1f710-broken` | failed (exit code 2) | 40ms |"));
+        assert!(md.contains("<details><summary><code>1f710-broken</code> failed</summary>"));
+        assert!(md.contains("config.json: not found\n```"));
+        assert!(md.contains("- 🛠️ **Manual follow-up** (`1f700-init`): rotate the API key"));
+    }
+
+    #[test]
+    fn test_markdown_report_escapes_failure_details() {
+        let mut cases = cases();
+        cases[1].outcome = CaseOutcome::Failed {
+            exit_code: 1,
+            message: "expected <config> & got </details>".to_string(),
+        };
+        cases[1].output = vec![(Stream::Stdout, "```` fenced ``` text".to_string())];
+        let md = markdown("app", false, &cases);
+        assert!(md.contains("expected &lt;config&gt; &amp; got &lt;/details&gt;"));
+        assert!(
+            md.contains("\n`````\n```` fenced ``` text\n`````\n"),
+            "{}",
+            md
+        );
+    }
+
+    #[test]
+    fn test_escape_xml_keeps_carriage_returns() {
+        assert_eq!(escape_xml("a\r\nb\tc\u{1b}[0m"), "a\r\nb\tc[0m");
+    }
+}
@@ -3,12 +3,14 @@ use chrono::{DateTime, Utc};
 use std::fs::{self, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
+use std::time::Duration;
 
-use crate::{AppliedMigration, Migration};
+use crate::{AppliedMigration, MigrateError, Migration};
 
-const HISTORY_FILE: &str = "history";
-const LEGACY_HISTORY_FILE: &str = ".history";
-const LEGACY_BASELINE_FILE: &str = ".baseline";
+/// Name of the default history file in the migrations directory
+pub const HISTORY_FILE: &str = "history";
+pub(crate) const LEGACY_HISTORY_FILE: &str = ".history";
+pub(crate) const LEGACY_BASELINE_FILE: &str = ".baseline";
 
 /// A baseline assertion: migrations with version <= this are considered applied
 #[derive(Debug, Clone)]
@@ -40,18 +42,41 @@ pub fn read_history(migrations_dir: &Path) -> Result<HistoryState> {
         migrate_legacy_files(migrations_dir)?;
     }
 
+    let mut state = read_history_file(&history_path)?;
+
+    // Also check for legacy .baseline file that might not have been migrated
+    if state.baseline.is_none() && legacy_baseline_path.exists() {
+        if let Some(legacy_baseline) = read_legacy_baseline(&legacy_baseline_path)? {
+            // Write it to the new history file and delete the legacy file
+            append_baseline(migrations_dir, &legacy_baseline)?;
+            fs::remove_file(&legacy_baseline_path).ok();
+            state.baseline = Some(legacy_baseline);
+        }
+    }
+
+    Ok(state)
+}
+
+/// Read state from a history file at an arbitrary path. A missing file is an empty state.
+pub fn read_history_file(history_path: &Path) -> Result<HistoryState> {
     if !history_path.exists() {
         return Ok(HistoryState::default());
     }
 
-    let file = fs::File::open(&history_path)
+    let file = fs::File::open(history_path)
         .with_context(|| format!("Failed to open history file: {}", history_path.display()))?;
 
     let reader = BufReader::new(file);
     let mut applied = Vec::new();
     let mut baseline: Option<Baseline> = None;
 
-    for line in reader.lines() {
+    let corrupt = |line: usize, message: String| MigrateError::HistoryCorrupt {
+        path: history_path.to_path_buf(),
+        line,
+        message,
+    };
+
+    for (number, line) in reader.lines().enumerate() {
         let line = line.context("Failed to read line from history file")?;
         let line = line.trim();
 
@@ -59,50 +84,90 @@ pub fn read_history(migrations_dir: &Path) -> Result<HistoryState> {
             continue;
         }
 
+        // Audit fields of the migration on the line before: "#fields\tkey=value\t..."
+        if let Some(fields) = line.strip_prefix(FIELDS_PREFIX) {
+            let Some(record) = applied.last_mut() else {
+                return Err(corrupt(
+                    number + 1,
+                    "Fields line without a migration before it".to_string(),
+                )
+                .into());
+            };
+            for field in fields.split('\t') {
+                let Some((key, value)) = field.split_once('=') else {
+                    continue;
+                };
+                set_history_field(record, key, unescape_field(value))
+                    .map_err(|message| corrupt(number + 1, message))?;
+            }
+            continue;
+        }
+        // Other comments are ignored
+        if line.starts_with('#') {
+            continue;
+        }
+
         // Baseline format: "baseline: version timestamp [summary]"
         if let Some(rest) = line.strip_prefix("baseline: ") {
             let parts: Vec<&str> = rest.splitn(3, ' ').collect();
-            if parts.len() >= 2 {
-                let version = parts[0].to_string();
-                let created = DateTime::parse_from_rfc3339(parts[1])
-                    .with_context(|| format!("Invalid timestamp in baseline: {}", parts[1]))?
-                    .with_timezone(&Utc);
-                let summary = if parts.len() == 3 {
-                    Some(parts[2].to_string())
-                } else {
-                    None
-                };
-                baseline = Some(Baseline {
-                    version,
-                    created,
-                    summary,
-                });
+            if parts.len() < 2 {
+                return Err(corrupt(
+                    number + 1,
+                    format!("Baseline is missing its timestamp: {}", line),
+                )
+                .into());
             }
+            let version = parts[0].to_string();
+            let created = DateTime::parse_from_rfc3339(parts[1])
+                .map_err(|_| {
+                    corrupt(
+                        number + 1,
+                        format!("Invalid timestamp in baseline: {}", parts[1]),
+                    )
+                })?
+                .with_timezone(&Utc);
+            let summary = if parts.len() == 3 {
+                Some(parts[2].to_string())
+            } else {
+                None
+            };
+            baseline = Some(Baseline {
+                version,
+                created,
+                summary,
+            });
             continue;
         }
 
-        // Migration format: "id timestamp" (space-separated)
-        let parts: Vec<&str> = line.splitn(2, ' ').collect();
-        if parts.len() != 2 {
-            continue;
+        // Migration format: "id timestamp", optionally followed by space-separated
+        // `key=value` fields as written by earlier development versions
+        let parts: Vec<&str> = line.split(' ').collect();
+        if parts.len() < 2 {
+            return Err(corrupt(
+                number + 1,
+                format!("Migration is missing its timestamp: {}", line),
+            )
+            .into());
         }
 
-        let id = parts[0].to_string();
         let applied_at = DateTime::parse_from_rfc3339(parts[1])
-            .with_context(|| format!("Invalid timestamp in history file: {}", parts[1]))?
+            .map_err(|_| {
+                corrupt(
+                    number + 1,
+                    format!("Invalid timestamp in history file: {}", parts[1]),
+                )
+            })?
             .with_timezone(&Utc);
-
-        applied.push(AppliedMigration { id, applied_at });
-    }
-
-    // Also check for legacy .baseline file that might not have been migrated
-    if baseline.is_none() && legacy_baseline_path.exists() {
-        if let Some(legacy_baseline) = read_legacy_baseline(&legacy_baseline_path)? {
-            // Write it to the new history file and delete the legacy file
-            append_baseline(migrations_dir, &legacy_baseline)?;
-            fs::remove_file(&legacy_baseline_path).ok();
-            baseline = Some(legacy_baseline);
+        let mut record = AppliedMigration::new(parts[0], applied_at);
+        for field in &parts[2..] {
+            let Some((key, value)) = field.split_once('=') else {
+                continue;
+            };
+            set_history_field(&mut record, key, unescape_field(value))
+                .map_err(|message| corrupt(number + 1, message))?;
         }
+
+        applied.push(record);
     }
 
     Ok(HistoryState { applied, baseline })
@@ -234,38 +299,151 @@ fn format_baseline_line(baseline: &Baseline) -> String {
     }
 }
 
-/// Append a migration record to the history file.
-pub fn append_history(migrations_dir: &Path, id: &str, applied_at: DateTime<Utc>) -> Result<()> {
-    let history_path = migrations_dir.join(HISTORY_FILE);
+/// `outcome` of a history record for a migration that was not applicable
+const NOT_APPLICABLE_OUTCOME: &str = "not-applicable";
+
+/// Prefix of the history fields holding a migration's outputs, e.g. `out.db_url=...`
+const OUTPUT_FIELD_PREFIX: &str = "out.";
+
+/// Start of the line holding the audit fields of the migration on the line before
+const FIELDS_PREFIX: &str = "#fields\t";
+
+/// Format an applied migration for the history file: an `id timestamp` line, and if it has
+/// audit fields, a `#fields` line after it with tab-separated `key=value` fields. Values are
+/// escaped so the fields line contains no spaces, which versions of `migrate` that only
+/// know `id timestamp` lines skip.
+fn format_history_record(record: &AppliedMigration) -> String {
+    let mut line = format!("{} {}", record.id, record.applied_at.to_rfc3339());
+    let mut fields = Vec::new();
+    let audit = [
+        (
+            "duration_ms",
+            record.duration.map(|d| d.as_millis().to_string()),
+        ),
+        ("exit_code", record.exit_code.map(|c| c.to_string())),
+        ("version", record.migrate_version.clone()),
+        ("user", record.user.clone()),
+        ("host", record.host.clone()),
+        ("run", record.run_id.clone()),
+        ("commit", record.commit.clone()),
+        (
+            "outcome",
+            record
+                .not_applicable
+                .then(|| NOT_APPLICABLE_OUTCOME.to_string()),
+        ),
+    ];
+    for (key, value) in audit {
+        if let Some(value) = value {
+            fields.push(format!("{}={}", key, escape_field(&value)));
+        }
+    }
+    for (key, value) in &record.outputs {
+        fields.push(format!(
+            "{}{}={}",
+            OUTPUT_FIELD_PREFIX,
+            key,
+            escape_field(value)
+        ));
+    }
+    if !fields.is_empty() {
+        line.push('\n');
+        line.push_str(FIELDS_PREFIX);
+        line.push_str(&fields.join("\t"));
+    }
+    line
+}
 
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&history_path)
-        .with_context(|| format!("Failed to open history file: {}", history_path.display()))?;
+/// Apply a `key=value` field from a history line; unknown keys (from newer versions) are ignored
+fn set_history_field(
+    record: &mut AppliedMigration,
+    key: &str,
+    value: String,
+) -> std::result::Result<(), String> {
+    let invalid = |_| format!("Invalid {} in history file: {}", key, value);
+    match key {
+        "duration_ms" => {
+            record.duration = Some(Duration::from_millis(value.parse().map_err(invalid)?))
+        }
+        "exit_code" => record.exit_code = Some(value.parse().map_err(invalid)?),
+        "version" => record.migrate_version = Some(value),
+        "user" => record.user = Some(value),
+        "host" => record.host = Some(value),
+        "run" => record.run_id = Some(value),
+        "commit" => record.commit = Some(value),
+        "outcome" => record.not_applicable = value == NOT_APPLICABLE_OUTCOME,
+        _ => {
+            if let Some(output) = key.strip_prefix(OUTPUT_FIELD_PREFIX) {
+                record.outputs.insert(output.to_string(), value);
+            }
+        }
+    }
+    Ok(())
+}
 
-    writeln!(file, "{} {}", id, applied_at.to_rfc3339())
-        .context("Failed to write to history file")?;
+fn escape_field(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '%' => escaped.push_str("%25"),
+            ' ' => escaped.push_str("%20"),
+            '\t' => escaped.push_str("%09"),
+            '\n' => escaped.push_str("%0A"),
+            '\r' => escaped.push_str("%0D"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
 
-    Ok(())
+fn unescape_field(value: &str) -> String {
+    value
+        .replace("%20", " ")
+        .replace("%09", "\t")
+        .replace("%0A", "\n")
+        .replace("%0D", "\r")
+        .replace("%25", "%")
+}
+
+/// Append a migration record to the history file.
+pub fn append_history(migrations_dir: &Path, record: &AppliedMigration) -> Result<()> {
+    append_history_file(&migrations_dir.join(HISTORY_FILE), record)
 }
 
 /// Append a baseline record to the history file.
 pub fn append_baseline(migrations_dir: &Path, baseline: &Baseline) -> Result<()> {
-    let history_path = migrations_dir.join(HISTORY_FILE);
+    append_baseline_file(&migrations_dir.join(HISTORY_FILE), baseline)
+}
 
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&history_path)
-        .with_context(|| format!("Failed to open history file: {}", history_path.display()))?;
+/// Append a migration record to a history file at an arbitrary path.
+pub fn append_history_file(history_path: &Path, record: &AppliedMigration) -> Result<()> {
+    let mut file = open_for_append(history_path)?;
+    writeln!(file, "{}", format_history_record(record))
+        .context("Failed to write to history file")?;
 
+    Ok(())
+}
+
+/// Append a baseline record to a history file at an arbitrary path.
+pub fn append_baseline_file(history_path: &Path, baseline: &Baseline) -> Result<()> {
+    let mut file = open_for_append(history_path)?;
     writeln!(file, "{}", format_baseline_line(baseline))
         .context("Failed to write baseline to history file")?;
 
     Ok(())
 }
 
+fn open_for_append(history_path: &Path) -> Result<fs::File> {
+    if let Some(parent) = history_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_path)
+        .with_context(|| format!("Failed to open history file: {}", history_path.display()))
+}
+
 /// Get pending migrations (available but not yet applied).
 /// If a baseline is provided, skip migrations at or before the baseline version.
 pub fn get_pending<'a>(available: &'a [Migration], state: &HistoryState) -> Vec<&'a Migration> {
@@ -324,24 +502,24 @@ mod tests {
                 id: "1f700-first".to_string(),
                 version: "1f700".to_string(),
                 file_path: "1f700-first.sh".into(),
+                ..Default::default()
             },
             Migration {
                 id: "1f710-second".to_string(),
                 version: "1f710".to_string(),
                 file_path: "1f710-second.sh".into(),
+                ..Default::default()
             },
             Migration {
                 id: "1f720-third".to_string(),
                 version: "1f720".to_string(),
                 file_path: "1f720-third.sh".into(),
+                ..Default::default()
             },
         ];
 
         let state = HistoryState {
-            applied: vec![AppliedMigration {
-                id: "1f700-first".to_string(),
-                applied_at: Utc::now(),
-            }],
+            applied: vec![AppliedMigration::new("1f700-first", Utc::now())],
             baseline: None,
         };
 
@@ -358,16 +536,19 @@ mod tests {
                 id: "1f700-first".to_string(),
                 version: "1f700".to_string(),
                 file_path: "1f700-first.sh".into(),
+                ..Default::default()
             },
             Migration {
                 id: "1f710-second".to_string(),
                 version: "1f710".to_string(),
                 file_path: "1f710-second.sh".into(),
+                ..Default::default()
             },
             Migration {
                 id: "1f720-third".to_string(),
                 version: "1f720".to_string(),
                 file_path: "1f720-third.sh".into(),
+                ..Default::default()
             },
         ];
 
@@ -393,11 +574,13 @@ mod tests {
                 id: "1f700-first".to_string(),
                 version: "1f700".to_string(),
                 file_path: "1f700-first.sh".into(),
+                ..Default::default()
             },
             Migration {
                 id: "1f710-second".to_string(),
                 version: "1f710".to_string(),
                 file_path: "1f710-second.sh".into(),
+                ..Default::default()
             },
         ];
 
@@ -406,10 +589,7 @@ mod tests {
         assert_eq!(get_current_version(&available, &applied), None);
 
         // One applied migration
-        let applied = vec![AppliedMigration {
-            id: "1f700-first".to_string(),
-            applied_at: Utc::now(),
-        }];
+        let applied = vec![AppliedMigration::new("1f700-first", Utc::now())];
         assert_eq!(
             get_current_version(&available, &applied),
             Some("1f700".to_string())
@@ -417,14 +597,8 @@ mod tests {
 
         // Two applied migrations
         let applied = vec![
-            AppliedMigration {
-                id: "1f700-first".to_string(),
-                applied_at: Utc::now(),
-            },
-            AppliedMigration {
-                id: "1f710-second".to_string(),
-                applied_at: Utc::now(),
-            },
+            AppliedMigration::new("1f700-first", Utc::now()),
+            AppliedMigration::new("1f710-second", Utc::now()),
         ];
         assert_eq!(
             get_current_version(&available, &applied),
@@ -442,11 +616,13 @@ mod tests {
                 id: "1f700-first".to_string(),
                 version: "1f700".to_string(),
                 file_path: "1f700-first.sh".into(),
+                ..Default::default()
             },
             Migration {
                 id: "1f710-second".to_string(),
                 version: "1f710".to_string(),
                 file_path: "1f710-second.sh".into(),
+                ..Default::default()
             },
         ];
         assert_eq!(get_target_version(&available), Some("1f710".to_string()));
@@ -477,6 +653,123 @@ mod tests {
         assert!(!migrations_dir.join(".baseline").exists());
     }
 
+    #[test]
+    fn test_read_history_reports_corrupt_line() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("history");
+        fs::write(
+            &path,
+            "1f700-first 2024-06-15T14:30:00Z\n1f710-second yesterday\n",
+        )
+        .unwrap();
+
+        let error = read_history_file(&path).unwrap_err();
+        match error.downcast_ref::<MigrateError>() {
+            Some(MigrateError::HistoryCorrupt { line, .. }) => assert_eq!(*line, 2),
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        for content in ["1f700-first\n", "baseline: 1f700\n"] {
+            fs::write(&path, format!("\n{}", content)).unwrap();
+            let error = read_history_file(&path).unwrap_err();
+            match error.downcast_ref::<MigrateError>() {
+                Some(MigrateError::HistoryCorrupt { line, .. }) => assert_eq!(*line, 2),
+                other => panic!("unexpected error: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_history_lines_with_audit_fields() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("history");
+        let record = AppliedMigration {
+            duration: Some(Duration::from_millis(1520)),
+            exit_code: Some(0),
+            migrate_version: Some("0.5.0".to_string()),
+            user: Some("Jane Doe".to_string()),
+            host: Some("build-1".to_string()),
+            run_id: Some("20240615T143000Z-1f".to_string()),
+            commit: Some("9fceb02".to_string()),
+            ..AppliedMigration::new(
+                "1f710-second",
+                DateTime::parse_from_rfc3339("2024-06-15T14:30:00Z")
+                    .unwrap()
+                    .with_timezone(&Utc),
+            )
+        };
+        assert_eq!(
+            format_history_record(&record),
+            "1f710-second 2024-06-15T14:30:00+00:00\n#fields\tduration_ms=1520\texit_code=0\t\
+             version=0.5.0\tuser=Jane%20Doe\thost=build-1\trun=20240615T143000Z-1f\tcommit=9fceb02"
+        );
+
+        // Lines from older versions have no fields, fields on the migration's own line (from
+        // development versions) are read too, and unknown fields are ignored
+        fs::write(
+            &path,
+            format!(
+                "1f700-first 2024-06-15T14:00:00Z\n{}\n1f720-third 2024-06-15T15:00:00Z future=1\n",
+                format_history_record(&record)
+            ),
+        )
+        .unwrap();
+        let state = read_history_file(&path).unwrap();
+        assert_eq!(state.applied.len(), 3);
+        assert_eq!(state.applied[0].duration, None);
+        assert_eq!(state.applied[1], record);
+        assert_eq!(state.applied[2].id, "1f720-third");
+
+        let skipped = AppliedMigration {
+            not_applicable: true,
+            ..AppliedMigration::new("1f730-fourth", record.applied_at)
+        };
+        let line = format_history_record(&skipped);
+        assert_eq!(
+            line,
+            "1f730-fourth 2024-06-15T14:30:00+00:00\n#fields\toutcome=not-applicable"
+        );
+        append_history_file(&path, &skipped).unwrap();
+        assert!(read_history_file(&path).unwrap().applied[3].not_applicable);
+
+        let with_outputs = AppliedMigration {
+            outputs: [
+                ("db_url".to_string(), "postgres://db/app?a=b".to_string()),
+                ("notes".to_string(), "two\nlines".to_string()),
+            ]
+            .into(),
+            ..AppliedMigration::new("1f740-fifth", record.applied_at)
+        };
+        assert_eq!(
+            format_history_record(&with_outputs),
+            "1f740-fifth 2024-06-15T14:30:00+00:00\n\
+             #fields\tout.db_url=postgres://db/app?a=b\tout.notes=two%0Alines"
+        );
+        append_history_file(&path, &with_outputs).unwrap();
+        assert_eq!(read_history_file(&path).unwrap().applied[4], with_outputs);
+
+        // Versions that only know "id timestamp" lines skip lines without a space, so they
+        // still read every migration
+        let content = fs::read_to_string(&path).unwrap();
+        let old_reader: Vec<&str> = content
+            .lines()
+            .filter_map(|line| line.trim().split_once(' '))
+            .filter(|(id, _)| *id != "1f720-third")
+            .map(|(id, timestamp)| {
+                assert!(
+                    DateTime::parse_from_rfc3339(timestamp).is_ok(),
+                    "{}",
+                    timestamp
+                );
+                id
+            })
+            .collect();
+        assert_eq!(
+            old_reader,
+            ["1f700-first", "1f710-second", "1f730-fourth", "1f740-fifth"]
+        );
+    }
+
     #[test]
     fn test_format_baseline_line() {
         let baseline = Baseline {
@@ -0,0 +1,594 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::state::{
+    append_baseline_file, append_history_file, read_history, read_history_file, Baseline,
+    HistoryState, HISTORY_FILE,
+};
+use crate::{AppliedMigration, MigrateError, Outputs};
+
+/// Where applied migrations and baselines are persisted
+pub trait StateStore {
+    /// Read applied migrations (in application order) and the current baseline
+    fn read(&self) -> Result<HistoryState>;
+
+    /// Record that a migration was applied
+    fn record_applied(&self, record: &AppliedMigration) -> Result<()>;
+
+    /// Record a new baseline, replacing any previous one
+    fn record_baseline(&self, baseline: &Baseline) -> Result<()>;
+
+    /// Take an exclusive lock so concurrent runs don't apply the same migrations twice
+    fn lock(&self) -> Result<StateLock>;
+
+    /// Human-readable location of the state, for status output
+    fn location(&self) -> String;
+}
+
+/// An exclusive lock on a state store, released when dropped
+pub struct StateLock {
+    release: Option<Box<dyn FnOnce()>>,
+}
+
+impl StateLock {
+    /// A lock released by running `release`
+    pub fn new(release: impl FnOnce() + 'static) -> Self {
+        Self {
+            release: Some(Box::new(release)),
+        }
+    }
+
+    /// Lock `path` with an OS advisory lock, which the system releases if the process dies,
+    /// so a crashed run doesn't leave the state locked. The file is removed on release.
+    /// Fails if another run holds the lock.
+    pub fn file(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        loop {
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)
+                .with_context(|| format!("Failed to create lock {}", path.display()))?;
+            match file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => {
+                    return Err(MigrateError::LockHeld {
+                        path: path.to_path_buf(),
+                    }
+                    .into())
+                }
+                Err(TryLockError::Error(e)) => {
+                    return Err(e).with_context(|| format!("Failed to lock {}", path.display()))
+                }
+            }
+            // The run we waited for may have removed the file after we opened it; then
+            // lock the file now at `path` instead
+            if !is_same_file(&file, path) {
+                continue;
+            }
+            file.set_len(0).ok();
+            writeln!(file, "{}", std::process::id()).ok();
+
+            let path = path.to_path_buf();
+            return Ok(Self::new(move || {
+                // Remove before unlocking, so a run that opened the file first sees it is gone
+                fs::remove_file(path).ok();
+                drop(file);
+            }));
+        }
+    }
+}
+
+/// Whether `path` still names the open `file`
+#[cfg(unix)]
+fn is_same_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_same_file(_file: &File, path: &Path) -> bool {
+    path.exists()
+}
+
+impl Drop for StateLock {
+    fn drop(&mut self) {
+        if let Some(release) = self.release.take() {
+            release();
+        }
+    }
+}
+
+/// The plain-text `history` file (the default)
+pub struct HistoryFileStore {
+    path: PathBuf,
+    /// Migrations directory whose legacy `.history`/`.baseline` files are upgraded on read
+    migrations_dir: Option<PathBuf>,
+}
+
+impl HistoryFileStore {
+    /// The `history` file in the migrations directory
+    pub fn new(migrations_dir: impl Into<PathBuf>) -> Self {
+        let migrations_dir = migrations_dir.into();
+        Self {
+            path: migrations_dir.join(HISTORY_FILE),
+            migrations_dir: Some(migrations_dir),
+        }
+    }
+
+    /// A history file at an arbitrary path, e.g. in a directory outside the repository
+    pub fn at(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            migrations_dir: None,
+        }
+    }
+}
+
+impl StateStore for HistoryFileStore {
+    fn read(&self) -> Result<HistoryState> {
+        match &self.migrations_dir {
+            Some(dir) => read_history(dir),
+            None => read_history_file(&self.path),
+        }
+    }
+
+    fn record_applied(&self, record: &AppliedMigration) -> Result<()> {
+        append_history_file(&self.path, record)
+    }
+
+    fn record_baseline(&self, baseline: &Baseline) -> Result<()> {
+        append_baseline_file(&self.path, baseline)
+    }
+
+    fn lock(&self) -> Result<StateLock> {
+        StateLock::file(&lock_path(&self.path))
+    }
+
+    fn location(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+/// A single JSON object per line, convenient for other tools to consume
+pub struct JsonLinesStore {
+    path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum JsonRecord {
+    Applied {
+        id: String,
+        applied_at: DateTime<Utc>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        duration_ms: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        exit_code: Option<i32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        migrate_version: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        user: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        host: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        run_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        commit: Option<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        not_applicable: bool,
+        #[serde(default, skip_serializing_if = "Outputs::is_empty")]
+        outputs: Outputs,
+    },
+    Baseline {
+        version: String,
+        created: DateTime<Utc>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        summary: Option<String>,
+    },
+}
+
+impl JsonLinesStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn append(&self, record: &JsonRecord) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open state file: {}", self.path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(record)?)
+            .context("Failed to write to state file")
+    }
+}
+
+impl StateStore for JsonLinesStore {
+    fn read(&self) -> Result<HistoryState> {
+        if !self.path.exists() {
+            return Ok(HistoryState::default());
+        }
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read state file: {}", self.path.display()))?;
+
+        let mut state = HistoryState::default();
+        for (number, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: JsonRecord =
+                serde_json::from_str(line).map_err(|e| MigrateError::HistoryCorrupt {
+                    path: self.path.clone(),
+                    line: number + 1,
+                    message: format!("Invalid record: {}", e),
+                })?;
+            match record {
+                JsonRecord::Applied {
+                    id,
+                    applied_at,
+                    duration_ms,
+                    exit_code,
+                    migrate_version,
+                    user,
+                    host,
+                    run_id,
+                    commit,
+                    not_applicable,
+                    outputs,
+                } => state.applied.push(AppliedMigration {
+                    id,
+                    applied_at,
+                    duration: duration_ms.map(Duration::from_millis),
+                    exit_code,
+                    migrate_version,
+                    user,
+                    host,
+                    run_id,
+                    commit,
+                    not_applicable,
+                    outputs,
+                }),
+                JsonRecord::Baseline {
+                    version,
+                    created,
+                    summary,
+                } => {
+                    state.baseline = Some(Baseline {
+                        version,
+                        created,
+                        summary,
+                    })
+                }
+            }
+        }
+        Ok(state)
+    }
+
+    fn record_applied(&self, record: &AppliedMigration) -> Result<()> {
+        self.append(&JsonRecord::Applied {
+            id: record.id.clone(),
+            applied_at: record.applied_at,
+            duration_ms: record.duration.map(|d| d.as_millis() as u64),
+            exit_code: record.exit_code,
+            migrate_version: record.migrate_version.clone(),
+            user: record.user.clone(),
+            host: record.host.clone(),
+            run_id: record.run_id.clone(),
+            commit: record.commit.clone(),
+            not_applicable: record.not_applicable,
+            outputs: record.outputs.clone(),
+        })
+    }
+
+    fn record_baseline(&self, baseline: &Baseline) -> Result<()> {
+        self.append(&JsonRecord::Baseline {
+            version: baseline.version.clone(),
+            created: baseline.created,
+            summary: baseline.summary.clone(),
+        })
+    }
+
+    fn lock(&self) -> Result<StateLock> {
+        StateLock::file(&lock_path(&self.path))
+    }
+
+    fn location(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+/// Columns of the `applied` table beyond `id` and `applied_at`
+#[cfg(feature = "sqlite")]
+const AUDIT_COLUMNS: [(&str, &str); 9] = [
+    ("duration_ms", "INTEGER"),
+    ("exit_code", "INTEGER"),
+    ("migrate_version", "TEXT"),
+    ("user", "TEXT"),
+    ("host", "TEXT"),
+    ("run_id", "TEXT"),
+    ("git_commit", "TEXT"),
+    ("not_applicable", "INTEGER"),
+    ("outputs", "TEXT"),
+];
+
+/// A SQLite database with `applied` and `baselines` tables
+#[cfg(feature = "sqlite")]
+pub struct SqliteStore {
+    path: PathBuf,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn connect(&self) -> Result<rusqlite::Connection> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let conn = rusqlite::Connection::open(&self.path)
+            .with_context(|| format!("Failed to open state database: {}", self.path.display()))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS applied (id TEXT NOT NULL, applied_at TEXT NOT NULL);
+             CREATE TABLE IF NOT EXISTS baselines (version TEXT NOT NULL, created TEXT NOT NULL, summary TEXT);",
+        )?;
+        // Audit columns were added later; add them to databases created before that
+        let existing: Vec<String> = conn
+            .prepare("SELECT name FROM pragma_table_info('applied')")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        for (column, kind) in AUDIT_COLUMNS {
+            if !existing.iter().any(|c| c == column) {
+                conn.execute_batch(&format!(
+                    "ALTER TABLE applied ADD COLUMN {} {}",
+                    column, kind
+                ))?;
+            }
+        }
+        Ok(conn)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl StateStore for SqliteStore {
+    fn read(&self) -> Result<HistoryState> {
+        let conn = self.connect()?;
+        let parse = |raw: String| -> Result<DateTime<Utc>> {
+            Ok(DateTime::parse_from_rfc3339(&raw)
+                .with_context(|| format!("Invalid timestamp in state database: {}", raw))?
+                .with_timezone(&Utc))
+        };
+
+        let mut state = HistoryState::default();
+        let mut stmt = conn.prepare(
+            "SELECT id, applied_at, duration_ms, exit_code, migrate_version, user, host, run_id, git_commit,
+                    not_applicable, outputs
+             FROM applied ORDER BY rowid",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let duration_ms: Option<i64> = row.get(2)?;
+            let outputs: Option<String> = row.get(10)?;
+            state.applied.push(AppliedMigration {
+                id: row.get(0)?,
+                applied_at: parse(row.get(1)?)?,
+                duration: duration_ms.map(|ms| Duration::from_millis(ms as u64)),
+                exit_code: row.get(3)?,
+                migrate_version: row.get(4)?,
+                user: row.get(5)?,
+                host: row.get(6)?,
+                run_id: row.get(7)?,
+                commit: row.get(8)?,
+                not_applicable: row.get::<_, Option<bool>>(9)?.unwrap_or(false),
+                outputs: match outputs {
+                    Some(json) => serde_json::from_str(&json)
+                        .with_context(|| format!("Invalid outputs in state database: {}", json))?,
+                    None => Outputs::new(),
+                },
+            });
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT version, created, summary FROM baselines ORDER BY rowid DESC LIMIT 1",
+        )?;
+        let mut rows = stmt.query([])?;
+        if let Some(row) = rows.next()? {
+            state.baseline = Some(Baseline {
+                version: row.get(0)?,
+                created: parse(row.get(1)?)?,
+                summary: row.get(2)?,
+            });
+        }
+        Ok(state)
+    }
+
+    fn record_applied(&self, record: &AppliedMigration) -> Result<()> {
+        self.connect()?.execute(
+            "INSERT INTO applied (id, applied_at, duration_ms, exit_code, migrate_version, user, host, run_id, git_commit,
+                                  not_applicable, outputs)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            (
+                &record.id,
+                record.applied_at.to_rfc3339(),
+                record.duration.map(|d| d.as_millis() as i64),
+                record.exit_code,
+                &record.migrate_version,
+                &record.user,
+                &record.host,
+                &record.run_id,
+                &record.commit,
+                record.not_applicable,
+                (!record.outputs.is_empty())
+                    .then(|| serde_json::to_string(&record.outputs))
+                    .transpose()?,
+            ),
+        )?;
+        Ok(())
+    }
+
+    fn record_baseline(&self, baseline: &Baseline) -> Result<()> {
+        self.connect()?.execute(
+            "INSERT INTO baselines (version, created, summary) VALUES (?1, ?2, ?3)",
+            (
+                &baseline.version,
+                baseline.created.to_rfc3339(),
+                &baseline.summary,
+            ),
+        )?;
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<StateLock> {
+        StateLock::file(&lock_path(&self.path))
+    }
+
+    fn location(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+/// `state.jsonl` -> `state.jsonl.lock`
+fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn exercise(store: &dyn StateStore) {
+        assert!(store.read().unwrap().applied.is_empty());
+
+        let now = Utc::now();
+        store
+            .record_applied(&AppliedMigration::new("1f700-first", now))
+            .unwrap();
+        let second = AppliedMigration {
+            duration: Some(Duration::from_millis(1250)),
+            exit_code: Some(0),
+            migrate_version: Some("0.5.0".to_string()),
+            user: Some("ci bot".to_string()),
+            host: Some("build-1".to_string()),
+            run_id: Some("run-1".to_string()),
+            commit: Some("0123abc".to_string()),
+            not_applicable: true,
+            outputs: [("db_url".to_string(), "postgres://db/app".to_string())].into(),
+            ..AppliedMigration::new("1f710-second", now)
+        };
+        store.record_applied(&second).unwrap();
+        store
+            .record_baseline(&Baseline {
+                version: "1f700".to_string(),
+                created: now,
+                summary: Some("first".to_string()),
+            })
+            .unwrap();
+
+        let state = store.read().unwrap();
+        let ids: Vec<_> = state.applied.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, ["1f700-first", "1f710-second"]);
+        assert_eq!(state.applied[0].user, None);
+        assert!(!state.applied[0].not_applicable);
+        // Timestamps are stored at RFC 3339 precision, so compare the audit fields
+        assert_eq!(
+            AppliedMigration {
+                applied_at: second.applied_at,
+                ..state.applied[1].clone()
+            },
+            second
+        );
+        let baseline = state.baseline.unwrap();
+        assert_eq!(baseline.version, "1f700");
+        assert_eq!(baseline.summary.as_deref(), Some("first"));
+    }
+
+    #[test]
+    fn test_history_file_store() {
+        let dir = TempDir::new().unwrap();
+        exercise(&HistoryFileStore::new(dir.path()));
+    }
+
+    #[test]
+    fn test_json_lines_store() {
+        let dir = TempDir::new().unwrap();
+        exercise(&JsonLinesStore::new(dir.path().join("state.jsonl")));
+    }
+
+    #[test]
+    fn test_history_file_outside_migrations_dir() {
+        let dir = TempDir::new().unwrap();
+        exercise(&HistoryFileStore::at(
+            dir.path().join("nested/state/history"),
+        ));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store() {
+        let dir = TempDir::new().unwrap();
+        exercise(&SqliteStore::new(dir.path().join("state.db")));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store_upgrades_old_schema() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.db");
+        rusqlite::Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE applied (id TEXT NOT NULL, applied_at TEXT NOT NULL);
+                 INSERT INTO applied VALUES ('1f700-first', '2024-06-15T14:30:00+00:00');",
+            )
+            .unwrap();
+
+        let store = SqliteStore::new(&path);
+        assert_eq!(store.read().unwrap().applied[0].duration, None);
+        store
+            .record_applied(&AppliedMigration {
+                run_id: Some("run-2".to_string()),
+                ..AppliedMigration::new("1f710-second", Utc::now())
+            })
+            .unwrap();
+        let state = store.read().unwrap();
+        assert_eq!(state.applied[1].run_id.as_deref(), Some("run-2"));
+    }
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let dir = TempDir::new().unwrap();
+        let store = JsonLinesStore::new(dir.path().join("state.jsonl"));
+
+        let lock = store.lock().unwrap();
+        assert!(store.lock().is_err());
+        drop(lock);
+        assert!(!dir.path().join("state.jsonl.lock").exists());
+        assert!(store.lock().is_ok());
+
+        // A lock file left behind by a run that was killed doesn't hold the lock
+        fs::write(dir.path().join("state.jsonl.lock"), "4194304\n").unwrap();
+        let lock = store.lock().unwrap();
+        assert!(store.lock().is_err());
+        drop(lock);
+    }
+}
@@ -0,0 +1,723 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item, TableLike};
+
+/// Structured file formats that can be edited in-process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    /// Detect the format from a file extension
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Ok(Format::Json),
+            Some("yaml" | "yml") => Ok(Format::Yaml),
+            Some("toml") => Ok(Format::Toml),
+            _ => bail!(
+                "Cannot determine data format of {} (expected .json, .yaml, .yml or .toml)",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// An edit to a structured document.
+/// Keys are dotted paths (`settings.ports.0`) or JSON Pointers (`/settings/ports/0`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum DataEdit {
+    /// Set a value, creating intermediate objects as needed
+    Set { key: String, value: Value },
+    /// Delete a key (no-op if it doesn't exist)
+    Delete { key: String },
+    /// Deep-merge an object into the value at `key` (the document root by default)
+    Merge {
+        #[serde(default)]
+        key: String,
+        value: Value,
+    },
+    /// Move a value to a new key
+    Rename { from: String, to: String },
+    /// Append a value to an array, creating the array if needed
+    Append { key: String, value: Value },
+    /// Apply an RFC 6902 JSON Patch
+    Patch { patch: Value },
+    /// Apply an RFC 7386 JSON Merge Patch
+    MergePatch { patch: Value },
+}
+
+impl DataEdit {
+    /// One-line summary of the edit, used for progress and preview output
+    pub fn describe(&self) -> String {
+        match self {
+            DataEdit::Set { key, value } => format!("set {} = {}", key, value),
+            DataEdit::Delete { key } => format!("delete {}", key),
+            DataEdit::Merge { key, .. } if key.is_empty() => "merge into document".to_string(),
+            DataEdit::Merge { key, .. } => format!("merge into {}", key),
+            DataEdit::Rename { from, to } => format!("rename {} -> {}", from, to),
+            DataEdit::Append { key, value } => format!("append {} to {}", value, key),
+            DataEdit::Patch { .. } => "apply JSON patch".to_string(),
+            DataEdit::MergePatch { .. } => "apply merge patch".to_string(),
+        }
+    }
+
+    /// Apply the edit to a document value
+    pub fn apply(&self, doc: &mut Value) -> Result<()> {
+        match self {
+            DataEdit::Set { key, value } => set_path(doc, &parse_key(key), value.clone()),
+            DataEdit::Delete { key } => {
+                remove_path(doc, &parse_key(key));
+                Ok(())
+            }
+            DataEdit::Merge { key, value } => {
+                let segments = parse_key(key);
+                if get_path(doc, &segments).is_none() {
+                    set_path(doc, &segments, Value::Object(Map::new()))?;
+                }
+                let target = get_path_mut(doc, &segments).context("Merge target not found")?;
+                deep_merge(target, value);
+                Ok(())
+            }
+            DataEdit::Rename { from, to } => {
+                let from_segments = parse_key(from);
+                let to_segments = parse_key(to);
+                match remove_path(doc, &from_segments) {
+                    Some(value) => set_path(doc, &to_segments, value),
+                    // Already renamed
+                    None if get_path(doc, &to_segments).is_some() => Ok(()),
+                    None => bail!("Key not found: {}", from),
+                }
+            }
+            DataEdit::Append { key, value } => {
+                let segments = parse_key(key);
+                if get_path(doc, &segments).is_none() {
+                    set_path(doc, &segments, Value::Array(Vec::new()))?;
+                }
+                match get_path_mut(doc, &segments) {
+                    Some(Value::Array(items)) => {
+                        items.push(value.clone());
+                        Ok(())
+                    }
+                    _ => bail!("Cannot append to {}: not an array", key),
+                }
+            }
+            DataEdit::Patch { patch } => json_patch(doc, patch),
+            DataEdit::MergePatch { patch } => {
+                merge_patch(doc, patch);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A parsed structured document that remembers enough of its source to preserve formatting
+pub struct Document {
+    format: Format,
+    source: String,
+    value: Value,
+}
+
+impl Document {
+    /// Parse a document in the given format
+    pub fn parse(format: Format, source: &str) -> Result<Self> {
+        let value = match format {
+            Format::Json => serde_json::from_str(source).context("Invalid JSON")?,
+            Format::Yaml => serde_yaml_ng::from_str(source).context("Invalid YAML")?,
+            Format::Toml => toml::from_str(source).context("Invalid TOML")?,
+        };
+        Ok(Self {
+            format,
+            source: source.to_string(),
+            value,
+        })
+    }
+
+    /// The document contents as a JSON value
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Mutable access to the document contents
+    pub fn value_mut(&mut self) -> &mut Value {
+        &mut self.value
+    }
+
+    /// Render the (possibly edited) document back to text.
+    /// JSON keeps key order, indentation and trailing newline; TOML keeps comments and
+    /// formatting of untouched entries. YAML keeps key order but is re-serialized, so
+    /// documents with comments are refused rather than silently losing them.
+    pub fn render(&self) -> Result<String> {
+        match self.format {
+            Format::Json => render_json(&self.source, &self.value),
+            Format::Yaml if has_yaml_comments(&self.source) => bail!(
+                "YAML with comments can't be edited without losing them; use a script migration instead"
+            ),
+            Format::Yaml => serde_yaml_ng::to_string(&self.value).context("Failed to render YAML"),
+            Format::Toml => render_toml(&self.source, &self.value),
+        }
+    }
+}
+
+/// Apply edits to a structured file, writing it back only if the content changed.
+/// Returns whether the file changed (or would change, in dry-run mode).
+pub fn edit_file(path: &Path, edits: &[DataEdit], dry_run: bool) -> Result<bool> {
+    let format = Format::from_path(path)?;
+    let source =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut document = Document::parse(format, &source)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    let original = document.value().clone();
+    for edit in edits {
+        edit.apply(document.value_mut())
+            .with_context(|| format!("Failed to {} in {}", edit.describe(), path.display()))?;
+    }
+    if *document.value() == original {
+        return Ok(false);
+    }
+
+    // Render even in dry runs, so a preview reports documents that can't be rewritten
+    let rendered = document
+        .render()
+        .with_context(|| format!("Cannot edit {}", path.display()))?;
+    if !dry_run {
+        fs::write(path, rendered).with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(true)
+}
+
+/// Split a key into path segments. Keys starting with `/` are JSON Pointers;
+/// anything else is a dotted path. An empty key (or `.`) refers to the whole document.
+pub fn parse_key(key: &str) -> Vec<String> {
+    if key.is_empty() || key == "." {
+        return Vec::new();
+    }
+    if let Some(pointer) = key.strip_prefix('/') {
+        return pointer
+            .split('/')
+            .map(|s| s.replace("~1", "/").replace("~0", "~"))
+            .collect();
+    }
+    key.split('.').map(|s| s.to_string()).collect()
+}
+
+pub(crate) fn get_path<'a>(doc: &'a Value, segments: &[String]) -> Option<&'a Value> {
+    segments
+        .iter()
+        .try_fold(doc, |current, segment| match current {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => None,
+        })
+}
+
+fn get_path_mut<'a>(doc: &'a mut Value, segments: &[String]) -> Option<&'a mut Value> {
+    segments
+        .iter()
+        .try_fold(doc, |current, segment| match current {
+            Value::Object(map) => map.get_mut(segment),
+            Value::Array(items) => items.get_mut(segment.parse::<usize>().ok()?),
+            _ => None,
+        })
+}
+
+/// Set a value at a path, creating intermediate objects for missing segments.
+fn set_path(doc: &mut Value, segments: &[String], value: Value) -> Result<()> {
+    let Some((last, parents)) = segments.split_last() else {
+        *doc = value;
+        return Ok(());
+    };
+
+    let mut current = doc;
+    for segment in parents {
+        current = match current {
+            Value::Object(map) => map
+                .entry(segment.clone())
+                .or_insert_with(|| Value::Object(Map::new())),
+            Value::Array(items) => {
+                let index = parse_index(segment, items.len())?;
+                items
+                    .get_mut(index)
+                    .with_context(|| format!("Array index out of bounds: {}", segment))?
+            }
+            _ => bail!("Cannot descend into '{}': not an object or array", segment),
+        };
+    }
+
+    match current {
+        Value::Object(map) => {
+            map.insert(last.clone(), value);
+        }
+        Value::Array(items) => {
+            let index = parse_index(last, items.len())?;
+            if index == items.len() {
+                items.push(value);
+            } else if let Some(slot) = items.get_mut(index) {
+                *slot = value;
+            } else {
+                bail!("Array index out of bounds: {}", last);
+            }
+        }
+        _ => bail!("Cannot set '{}': parent is not an object or array", last),
+    }
+    Ok(())
+}
+
+/// Remove the value at a path, returning it if it existed.
+fn remove_path(doc: &mut Value, segments: &[String]) -> Option<Value> {
+    let (last, parents) = segments.split_last()?;
+    match get_path_mut(doc, parents)? {
+        Value::Object(map) => map.shift_remove(last),
+        Value::Array(items) => {
+            let index = last.parse::<usize>().ok()?;
+            (index < items.len()).then(|| items.remove(index))
+        }
+        _ => None,
+    }
+}
+
+/// Parse an array index; `-` refers to the position after the last element.
+fn parse_index(segment: &str, len: usize) -> Result<usize> {
+    if segment == "-" {
+        return Ok(len);
+    }
+    segment
+        .parse()
+        .with_context(|| format!("Invalid array index: {}", segment))
+}
+
+/// Recursively merge `patch` into `target`: objects are merged key by key, anything else replaces.
+fn deep_merge(target: &mut Value, patch: &Value) {
+    match (target, patch) {
+        (Value::Object(target_map), Value::Object(patch_map)) => {
+            for (key, value) in patch_map {
+                match target_map.get_mut(key) {
+                    Some(existing) => deep_merge(existing, value),
+                    None => {
+                        target_map.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch.clone(),
+    }
+}
+
+/// Apply an RFC 7386 JSON Merge Patch.
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch_map) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target_map) = target {
+        for (key, value) in patch_map {
+            if value.is_null() {
+                target_map.shift_remove(key);
+            } else {
+                merge_patch(target_map.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Apply an RFC 6902 JSON Patch. The document is left unchanged if any operation fails.
+fn json_patch(doc: &mut Value, patch: &Value) -> Result<()> {
+    let operations = patch
+        .as_array()
+        .context("JSON Patch must be an array of operations")?;
+
+    let mut result = doc.clone();
+    for (index, operation) in operations.iter().enumerate() {
+        apply_patch_operation(&mut result, operation)
+            .with_context(|| format!("JSON Patch operation {} failed", index))?;
+    }
+    *doc = result;
+    Ok(())
+}
+
+fn apply_patch_operation(doc: &mut Value, operation: &Value) -> Result<()> {
+    let pointer = |field: &str| -> Result<Vec<String>> {
+        let raw = operation[field]
+            .as_str()
+            .with_context(|| format!("Missing '{}'", field))?;
+        if !raw.is_empty() && !raw.starts_with('/') {
+            bail!("Invalid JSON Pointer: {}", raw);
+        }
+        Ok(parse_key(raw))
+    };
+    let value = || -> Result<Value> { operation.get("value").cloned().context("Missing 'value'") };
+
+    let op = operation["op"].as_str().context("Missing 'op'")?;
+    let path = pointer("path")?;
+    match op {
+        "add" => patch_add(doc, &path, value()?),
+        "remove" => {
+            if path.is_empty() {
+                bail!("Cannot remove the document root");
+            }
+            remove_path(doc, &path).context("Path not found")?;
+            Ok(())
+        }
+        "replace" => {
+            let target = get_path_mut(doc, &path).context("Path not found")?;
+            *target = value()?;
+            Ok(())
+        }
+        "move" => {
+            let from = pointer("from")?;
+            if path.starts_with(&from) && path.len() > from.len() {
+                bail!("Cannot move a value into one of its children");
+            }
+            let moved = remove_path(doc, &from).context("'from' path not found")?;
+            patch_add(doc, &path, moved)
+        }
+        "copy" => {
+            let from = pointer("from")?;
+            let copied = get_path(doc, &from)
+                .cloned()
+                .context("'from' path not found")?;
+            patch_add(doc, &path, copied)
+        }
+        "test" => {
+            if get_path(doc, &path) != Some(&value()?) {
+                bail!("Test failed: value does not match");
+            }
+            Ok(())
+        }
+        other => bail!("Unknown operation '{}'", other),
+    }
+}
+
+/// RFC 6902 "add": insert into arrays (shifting elements), set object members.
+fn patch_add(doc: &mut Value, path: &[String], value: Value) -> Result<()> {
+    let Some((last, parents)) = path.split_last() else {
+        *doc = value;
+        return Ok(());
+    };
+    match get_path_mut(doc, parents).context("Parent path not found")? {
+        Value::Object(map) => {
+            map.insert(last.clone(), value);
+        }
+        Value::Array(items) => {
+            let index = parse_index(last, items.len())?;
+            if index > items.len() {
+                bail!("Array index out of bounds: {}", last);
+            }
+            items.insert(index, value);
+        }
+        _ => bail!("Parent is not an object or array"),
+    }
+    Ok(())
+}
+
+/// Re-serialize JSON using the indentation and trailing newline of the original source.
+fn render_json(source: &str, value: &Value) -> Result<String> {
+    use serde::Serialize;
+
+    let mut rendered = if source.trim().contains('\n') {
+        let indent = source
+            .lines()
+            .skip(1)
+            .find(|line| !line.trim().is_empty())
+            .map(|line| &line[..line.len() - line.trim_start().len()])
+            .filter(|indent| !indent.is_empty())
+            .unwrap_or("  ");
+        let mut buffer = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+        let mut serializer = serde_json::Serializer::with_formatter(&mut buffer, formatter);
+        value.serialize(&mut serializer)?;
+        String::from_utf8(buffer)?
+    } else {
+        serde_json::to_string(value)?
+    };
+
+    if source.ends_with('\n') {
+        rendered.push('\n');
+    }
+    Ok(rendered)
+}
+
+/// Whether YAML source has a `#` comment: at the start of a line or after whitespace,
+/// outside quotes. Block scalars aren't tracked, so a `#` inside one also counts.
+fn has_yaml_comments(source: &str) -> bool {
+    source.lines().any(|line| {
+        let mut quote = None;
+        let mut previous = ' ';
+        for c in line.chars() {
+            match (quote, c) {
+                (None, '#') if previous.is_whitespace() => return true,
+                // Quotes only delimit a scalar at its start, not in `it's`
+                (None, '"' | '\'') if previous.is_whitespace() || "[{,".contains(previous) => {
+                    quote = Some(c)
+                }
+                (Some(open), _) if c == open => quote = None,
+                _ => {}
+            }
+            previous = c;
+        }
+        false
+    })
+}
+
+/// Update the original TOML document in place so comments and formatting of
+/// untouched entries survive the edit.
+fn render_toml(source: &str, value: &Value) -> Result<String> {
+    let mut document: DocumentMut = source.parse().context("Invalid TOML")?;
+    let original: Value = toml::from_str(source).context("Invalid TOML")?;
+
+    let (Value::Object(old), Value::Object(new)) = (&original, value) else {
+        bail!("A TOML document must be a table");
+    };
+    sync_table(document.as_table_mut(), old, new, false)?;
+    Ok(document.to_string())
+}
+
+fn sync_table(
+    table: &mut dyn TableLike,
+    old: &Map<String, Value>,
+    new: &Map<String, Value>,
+    inline: bool,
+) -> Result<()> {
+    for key in old.keys() {
+        if !new.contains_key(key) {
+            table.remove(key);
+        }
+    }
+
+    for (key, new_value) in new {
+        let old_value = old.get(key);
+        if old_value == Some(new_value) {
+            continue;
+        }
+
+        if let Some(item) = table.get_mut(key) {
+            if let (Some(Value::Object(old_map)), Value::Object(new_map)) = (old_value, new_value) {
+                let nested_inline = inline || item.is_inline_table();
+                if let Some(nested) = item.as_table_like_mut() {
+                    sync_table(nested, old_map, new_map, nested_inline)?;
+                    continue;
+                }
+            }
+
+            // Replace the value but keep any surrounding comments/whitespace
+            let decor = item.as_value().map(|v| v.decor().clone());
+            let mut replacement = to_toml_item(new_value, inline || item.is_value())?;
+            if let (Some(decor), Some(value)) = (decor, replacement.as_value_mut()) {
+                *value.decor_mut() = decor;
+            }
+            *item = replacement;
+        } else {
+            table.insert(key, to_toml_item(new_value, inline)?);
+        }
+    }
+    Ok(())
+}
+
+/// Convert a JSON value to a TOML item. Objects become tables unless `inline` is set.
+fn to_toml_item(value: &Value, inline: bool) -> Result<Item> {
+    match value {
+        Value::Object(map) if !inline => {
+            let mut table = toml_edit::Table::new();
+            for (key, value) in map {
+                table.insert(key, to_toml_item(value, false)?);
+            }
+            Ok(Item::Table(table))
+        }
+        Value::Array(items)
+            if !inline && !items.is_empty() && items.iter().all(Value::is_object) =>
+        {
+            let mut array = toml_edit::ArrayOfTables::new();
+            for item in items {
+                if let Item::Table(table) = to_toml_item(item, false)? {
+                    array.push(table);
+                }
+            }
+            Ok(Item::ArrayOfTables(array))
+        }
+        _ => Ok(Item::Value(to_toml_value(value)?)),
+    }
+}
+
+fn to_toml_value(value: &Value) -> Result<toml_edit::Value> {
+    Ok(match value {
+        Value::Null => bail!("TOML cannot represent null values"),
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into(),
+            None => n.as_f64().context("Unsupported number")?.into(),
+        },
+        Value::String(s) => s.as_str().into(),
+        Value::Array(items) => {
+            let mut array = toml_edit::Array::new();
+            for item in items {
+                array.push(to_toml_value(item)?);
+            }
+            toml_edit::Value::Array(array)
+        }
+        Value::Object(map) => {
+            let mut table = toml_edit::InlineTable::new();
+            for (key, value) in map {
+                table.insert(key, to_toml_value(value)?);
+            }
+            toml_edit::Value::InlineTable(table)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn edit(format: Format, source: &str, edits: &[DataEdit]) -> String {
+        let mut document = Document::parse(format, source).unwrap();
+        for edit in edits {
+            edit.apply(document.value_mut()).unwrap();
+        }
+        document.render().unwrap()
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("settings.debug"), vec!["settings", "debug"]);
+        assert_eq!(parse_key("/a~1b/0"), vec!["a/b", "0"]);
+        assert!(parse_key("").is_empty());
+    }
+
+    #[test]
+    fn test_json_edits_preserve_order_and_indent() {
+        let source = "{\n    \"name\": \"app\",\n    \"oldField\": 1,\n    \"settings\": {\n        \"debug\": false\n    }\n}\n";
+        let result = edit(
+            Format::Json,
+            source,
+            &[
+                DataEdit::Set {
+                    key: "settings.debug".to_string(),
+                    value: json!(true),
+                },
+                DataEdit::Delete {
+                    key: "oldField".to_string(),
+                },
+                DataEdit::Append {
+                    key: "features".to_string(),
+                    value: json!("auth"),
+                },
+            ],
+        );
+        assert_eq!(
+            result,
+            "{\n    \"name\": \"app\",\n    \"settings\": {\n        \"debug\": true\n    },\n    \"features\": [\n        \"auth\"\n    ]\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_rename_and_merge() {
+        let mut doc = json!({"a": {"b": 1}, "c": {"d": 1}});
+        DataEdit::Rename {
+            from: "a.b".to_string(),
+            to: "a.renamed".to_string(),
+        }
+        .apply(&mut doc)
+        .unwrap();
+        DataEdit::Merge {
+            key: "c".to_string(),
+            value: json!({"e": 2}),
+        }
+        .apply(&mut doc)
+        .unwrap();
+        assert_eq!(doc, json!({"a": {"renamed": 1}, "c": {"d": 1, "e": 2}}));
+    }
+
+    #[test]
+    fn test_json_patch() {
+        let mut doc = json!({"list": [1, 2], "a": {"b": "x"}});
+        let patch = json!([
+            {"op": "test", "path": "/a/b", "value": "x"},
+            {"op": "add", "path": "/list/1", "value": 9},
+            {"op": "add", "path": "/list/-", "value": 3},
+            {"op": "move", "from": "/a/b", "path": "/moved"},
+            {"op": "copy", "from": "/moved", "path": "/copied"},
+            {"op": "replace", "path": "/copied", "value": "y"},
+            {"op": "remove", "path": "/a"}
+        ]);
+        DataEdit::Patch { patch }.apply(&mut doc).unwrap();
+        assert_eq!(
+            doc,
+            json!({"list": [1, 9, 2, 3], "moved": "x", "copied": "y"})
+        );
+
+        // A failing test operation leaves the document unchanged
+        let before = doc.clone();
+        let patch = json!([
+            {"op": "remove", "path": "/moved"},
+            {"op": "test", "path": "/copied", "value": "nope"}
+        ]);
+        assert!(DataEdit::Patch { patch }.apply(&mut doc).is_err());
+        assert_eq!(doc, before);
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let mut doc = json!({"a": "b", "c": {"d": "e", "f": "g"}});
+        merge_patch(&mut doc, &json!({"a": "z", "c": {"f": null}}));
+        assert_eq!(doc, json!({"a": "z", "c": {"d": "e"}}));
+    }
+
+    #[test]
+    fn test_toml_edits_preserve_comments() {
+        let source = "# App config\n[server]\nport = 3000 # dev port\nhost = \"localhost\"\n\n[old]\nvalue = 1\n";
+        let result = edit(
+            Format::Toml,
+            source,
+            &[
+                DataEdit::Set {
+                    key: "server.port".to_string(),
+                    value: json!(8080),
+                },
+                DataEdit::Delete {
+                    key: "old".to_string(),
+                },
+                DataEdit::Set {
+                    key: "server.tls".to_string(),
+                    value: json!(true),
+                },
+            ],
+        );
+        assert_eq!(
+            result,
+            "# App config\n[server]\nport = 8080 # dev port\nhost = \"localhost\"\ntls = true\n"
+        );
+    }
+
+    #[test]
+    fn test_yaml_edit_keeps_key_order() {
+        let result = edit(
+            Format::Yaml,
+            "name: app\nversion: 1\nsettings:\n  debug: false\n",
+            &[DataEdit::Set {
+                key: "settings.debug".to_string(),
+                value: json!(true),
+            }],
+        );
+        assert_eq!(result, "name: app\nversion: 1\nsettings:\n  debug: true\n");
+    }
+
+    #[test]
+    fn test_yaml_with_comments_is_refused() {
+        let render = |source: &str| Document::parse(Format::Yaml, source).unwrap().render();
+        assert!(render("# App config\nname: app\n").is_err());
+        assert!(render("name: app # the app\n").is_err());
+        assert!(render("url: \"https://example.com/#top\"\ncolor: '#fff'\ntag: a#b\n").is_ok());
+        assert!(render("title: it's done # finally\n").is_err());
+    }
+}
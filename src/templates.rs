@@ -34,8 +34,21 @@ pub static TEMPLATES: &[Template] = &[
         extension: ".rb",
         content: include_str!("../templates/ruby.rb"),
     },
+    Template {
+        name: "toml",
+        extension: ".toml",
+        content: include_str!("../templates/declarative.toml"),
+    },
 ];
 
+/// Template for migrations generated by `migrate record`.
+/// Not listed in [`TEMPLATES`] because it depends on a recorded patch in the asset directory.
+pub static PATCH_TEMPLATE: Template = Template {
+    name: "patch",
+    extension: ".sh",
+    content: include_str!("../templates/patch.sh"),
+};
+
 /// Get a template by name
 pub fn get_template(name: &str) -> Option<&'static Template> {
     TEMPLATES.iter().find(|t| t.name == name)
@@ -0,0 +1,306 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+
+use crate::declarative::DeclarativeMigration;
+use crate::executor::{context_command, run, NOT_APPLICABLE_EXIT_CODE};
+use crate::structured::{get_path, parse_key, Document, Format};
+use crate::{ExecutionContext, ExecutionResult, Migration};
+
+/// Suffix of a migration's verify script, which sits next to it as `<id>.verify.sh`
+pub const VERIFY_SCRIPT_SUFFIX: &str = ".verify.sh";
+
+/// A postcondition of a declarative migration, from a `[[verify]]` table.
+/// Paths are relative to the project root.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "assert", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Assertion {
+    /// The file or directory exists
+    FileExists { path: String },
+    /// The file contains `text`
+    FileContains { path: String, text: String },
+    /// A key in a JSON, YAML or TOML file has this value
+    KeyEquals {
+        path: String,
+        key: String,
+        value: Value,
+    },
+}
+
+impl Assertion {
+    pub fn describe(&self) -> String {
+        match self {
+            Assertion::FileExists { path } => format!("{} exists", path),
+            Assertion::FileContains { path, text } => format!("{} contains '{}'", path, text),
+            Assertion::KeyEquals { path, key, value } => {
+                format!("{} in {} equals {}", key, path, value)
+            }
+        }
+    }
+
+    /// Why the assertion does not hold, or `None` if it does
+    fn failure(&self, ctx: &ExecutionContext) -> Result<Option<String>> {
+        let path = match self {
+            Assertion::FileExists { path }
+            | Assertion::FileContains { path, .. }
+            | Assertion::KeyEquals { path, .. } => path,
+        };
+        let full = ctx.path(path)?;
+        if !full.exists() {
+            return Ok(Some(format!("{} does not exist", path)));
+        }
+        let read = || fs::read_to_string(&full).with_context(|| format!("Failed to read {}", path));
+
+        Ok(match self {
+            Assertion::FileExists { .. } => None,
+            Assertion::FileContains { text, .. } => (!read()?.contains(text.as_str()))
+                .then(|| format!("{} does not contain '{}'", path, text)),
+            Assertion::KeyEquals { key, value, .. } => {
+                let document = Document::parse(Format::from_path(&full)?, &read()?)
+                    .with_context(|| format!("Failed to parse {}", path))?;
+                match get_path(document.value(), &parse_key(key)) {
+                    Some(actual) if actual == value => None,
+                    Some(actual) => Some(format!(
+                        "{} in {} is {}, expected {}",
+                        key, path, actual, value
+                    )),
+                    None => Some(format!("{} is not set in {}", key, path)),
+                }
+            }
+        })
+    }
+}
+
+/// Checks that a migration did its job: `[[verify]]` assertions, `Verify` header commands
+/// and a verify script, run in that order
+#[derive(Debug, Default)]
+pub struct Postconditions {
+    pub assertions: Vec<Assertion>,
+    pub commands: Vec<String>,
+    pub script: Option<PathBuf>,
+}
+
+impl Postconditions {
+    /// Collect a migration's postconditions
+    pub fn load(migration: &Migration) -> Result<Self> {
+        if migration.is_function() {
+            return Ok(Self::default());
+        }
+        let assertions = if migration.is_declarative() {
+            DeclarativeMigration::load(&migration.file_path)?.verify
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            assertions,
+            commands: migration.header.verify.clone(),
+            script: script_path(migration).filter(|script| script.is_file()),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assertions.is_empty() && self.commands.is_empty() && self.script.is_none()
+    }
+
+    /// Run every check, returning a message for each one that fails
+    pub fn check(&self, ctx: &ExecutionContext) -> Result<Vec<String>> {
+        let mut failures = Vec::new();
+        for assertion in &self.assertions {
+            ctx.log(format_args!("verify {}", assertion.describe()));
+            match assertion.failure(ctx) {
+                Ok(failure) => failures.extend(failure),
+                Err(e) => failures.push(format!("{}: {:#}", assertion.describe(), e)),
+            }
+        }
+        for command in &self.commands {
+            ctx.log(format_args!("verify {}", command));
+            let status = run(context_command("sh", ctx).arg("-c").arg(command), ctx, None)
+                .with_context(|| format!("Failed to run Verify command: {}", command))?;
+            if let Some(code) = failed(status) {
+                failures.push(format!("'{}' exited with code {}", command, code));
+            }
+        }
+        if let Some(script) = &self.script {
+            let name = script.file_name().unwrap_or_default().to_string_lossy();
+            ctx.log(format_args!("verify {}", name));
+            let status = run(&mut context_command(script, ctx), ctx, None)
+                .with_context(|| format!("Failed to run verify script: {}", script.display()))?;
+            if let Some(code) = failed(status) {
+                failures.push(format!("{} exited with code {}", name, code));
+            }
+        }
+        Ok(failures)
+    }
+}
+
+/// Where a migration's verify script would be; function migrations have none
+pub fn script_path(migration: &Migration) -> Option<PathBuf> {
+    (!migration.is_function()).then(|| {
+        migration
+            .file_path
+            .with_file_name(format!("{}{}", migration.id, VERIFY_SCRIPT_SUFFIX))
+    })
+}
+
+/// Check the postconditions of a migration that just ran, turning a successful result into
+/// a failure if any of them does not hold
+pub fn verify_result(
+    migration: &Migration,
+    ctx: &ExecutionContext,
+    result: ExecutionResult,
+) -> Result<ExecutionResult> {
+    if !result.success || result.exit_code == NOT_APPLICABLE_EXIT_CODE {
+        return Ok(result);
+    }
+    let failures = Postconditions::load(migration)?.check(ctx)?;
+    if failures.is_empty() {
+        return Ok(result);
+    }
+    Ok(ExecutionResult {
+        success: false,
+        exit_code: 1,
+        error: Some(format!(
+            "Migration {} failed verification: {}",
+            migration.id,
+            failures.join("; ")
+        )),
+        touched_files: result.touched_files,
+        outputs: result.outputs,
+        messages: result.messages,
+    })
+}
+
+/// The exit code of a command that did not succeed
+fn failed(status: Option<ExitStatus>) -> Option<i32> {
+    match status {
+        Some(status) if status.success() => None,
+        status => Some(status.and_then(|s| s.code()).unwrap_or(-1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::read_header;
+    use crate::MigrationKind;
+
+    fn migration(dir: &std::path::Path, name: &str, content: &str) -> Migration {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        Migration {
+            id: name.rsplit_once('.').unwrap().0.to_string(),
+            version: name[..5].to_string(),
+            header: read_header(&path),
+            kind: MigrationKind::from_path(&path),
+            file_path: path,
+        }
+    }
+
+    #[test]
+    fn test_declarative_assertions() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("package.json"), r#"{"type": "module"}"#).unwrap();
+        fs::write(dir.path().join("README.md"), "# Project\n").unwrap();
+        let migration = migration(
+            dir.path(),
+            "1f700-checks.toml",
+            r#"
+[[verify]]
+assert = "file-exists"
+path = "package.json"
+
+[[verify]]
+assert = "file-contains"
+path = "README.md"
+text = "Usage"
+
+[[verify]]
+assert = "key-equals"
+path = "package.json"
+key = "type"
+value = "commonjs"
+
+[[verify]]
+assert = "key-equals"
+path = "package.json"
+key = "engines.node"
+value = "20"
+
+[[verify]]
+assert = "file-exists"
+path = "tsconfig.json"
+"#,
+        );
+        let ctx = ExecutionContext::new(
+            dir.path().to_path_buf(),
+            dir.path().to_path_buf(),
+            migration.id.clone(),
+            false,
+            Default::default(),
+        )
+        .with_output(crate::OutputSink::new(|_, _| {}));
+
+        let postconditions = Postconditions::load(&migration).unwrap();
+        assert_eq!(postconditions.assertions.len(), 5);
+        assert_eq!(
+            postconditions.check(&ctx).unwrap(),
+            [
+                "README.md does not contain 'Usage'",
+                r#"type in package.json is "module", expected "commonjs""#,
+                "engines.node is not set in package.json",
+                "tsconfig.json does not exist",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_commands_and_script() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let migration = migration(
+            dir.path(),
+            "1f700-setup.sh",
+            "#!/bin/sh\n# Verify: test -f done\n# Verify: true\n",
+        );
+        let script = dir.path().join("1f700-setup.verify.sh");
+        fs::write(&script, "#!/bin/sh\nexit 3\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let ctx = ExecutionContext::new(
+            dir.path().to_path_buf(),
+            dir.path().to_path_buf(),
+            migration.id.clone(),
+            false,
+            Default::default(),
+        )
+        .with_output(crate::OutputSink::new(|_, _| {}));
+
+        let postconditions = Postconditions::load(&migration).unwrap();
+        assert_eq!(postconditions.script.as_ref(), Some(&script));
+        assert_eq!(
+            postconditions.check(&ctx).unwrap(),
+            [
+                "'test -f done' exited with code 1",
+                "1f700-setup.verify.sh exited with code 3",
+            ]
+        );
+
+        let passed = ExecutionResult {
+            success: true,
+            exit_code: 0,
+            error: None,
+            touched_files: Vec::new(),
+            outputs: Default::default(),
+            messages: Vec::new(),
+        };
+        let result = verify_result(&migration, &ctx, passed).unwrap();
+        assert!(!result.success);
+        assert!(result
+            .error
+            .unwrap()
+            .starts_with("Migration 1f700-setup failed verification: 'test -f done'"));
+    }
+}
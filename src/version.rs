@@ -1,4 +1,4 @@
-use chrono::{NaiveDate, Timelike, Utc};
+use chrono::{DateTime, Duration, NaiveDate, Timelike, Utc};
 
 /// Epoch for version calculation: 2020-01-01
 const EPOCH: (i32, u32, u32) = (2020, 1, 1);
@@ -71,6 +71,17 @@ pub fn parse_version(version: &str) -> Option<(u32, u32)> {
     Some((days, slot))
 }
 
+/// The time a version was generated, to the start of its 10-minute slot (UTC)
+pub fn version_time(version: &str) -> Option<DateTime<Utc>> {
+    let (days, slot) = parse_version(version)?;
+    if slot >= 144 {
+        return None;
+    }
+    let epoch = NaiveDate::from_ymd_opt(EPOCH.0, EPOCH.1, EPOCH.2)?.and_hms_opt(0, 0, 0)?;
+    let time = epoch + Duration::days(days as i64) + Duration::minutes(slot as i64 * 10);
+    Some(time.and_utc())
+}
+
 /// Check if a string is a valid version format
 pub fn is_valid_version(s: &str) -> bool {
     s.len() == 5 && s.chars().all(|c| c.is_ascii_alphanumeric())
@@ -118,6 +129,25 @@ mod tests {
         assert_eq!(parse_version("123456"), None); // Too long
     }
 
+    #[test]
+    fn test_version_time() {
+        let time = version_time("1f72f").unwrap();
+        assert_eq!(
+            time.format("%Y-%m-%d %H:%M").to_string(),
+            "2025-01-17 14:30"
+        );
+        assert_eq!(
+            version_time("00000").unwrap().to_rfc3339(),
+            "2020-01-01T00:00:00+00:00"
+        );
+        assert_eq!(version_time("0004z"), None); // Slot past the end of the day
+        assert_eq!(version_time("1f7-f"), None);
+
+        let now = Utc::now();
+        let generated = version_time(&generate_version()).unwrap();
+        assert!(generated <= now && now - generated < Duration::minutes(11));
+    }
+
     #[test]
     fn test_is_valid_version() {
         assert!(is_valid_version("1f72f"));
@@ -0,0 +1,165 @@
+use anyhow::{bail, Context, Result};
+use glob::{glob, Pattern};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+
+/// Directories never searched for workspaces
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "vendor"];
+
+/// Find workspace roots below `repo_root`: directories containing `migrations_dir`.
+///
+/// Members come from `[workspace] members` in the root `migrate.toml` when present;
+/// otherwise the tree is searched, skipping hidden directories and build/dependency folders.
+/// `filters` keeps only workspaces whose path relative to the root matches one of the globs.
+pub fn discover_workspaces(
+    repo_root: &Path,
+    migrations_dir: &Path,
+    filters: &[String],
+) -> Result<Vec<PathBuf>> {
+    if migrations_dir.is_absolute() {
+        bail!("Workspace mode needs a relative --migrations directory");
+    }
+
+    let config = Config::load(repo_root)?;
+    let mut workspaces = match &config.workspace {
+        Some(workspace) => {
+            let mut members = Vec::new();
+            for member in &workspace.members {
+                let pattern = repo_root.join(member);
+                let pattern = pattern.to_str().context("Invalid workspace pattern")?;
+                for entry in glob(pattern)
+                    .with_context(|| format!("Invalid workspace pattern: {}", member))?
+                {
+                    let path = entry?;
+                    if path.join(migrations_dir).is_dir() && !members.contains(&path) {
+                        members.push(path);
+                    }
+                }
+            }
+            let exclude = compile(&workspace.exclude)?;
+            members.retain(|path| !matches_any(&exclude, repo_root, path));
+            members
+        }
+        None => {
+            let mut found = Vec::new();
+            search(repo_root, migrations_dir, &mut found)?;
+            found
+        }
+    };
+
+    let filters = compile(filters)?;
+    if !filters.is_empty() {
+        workspaces.retain(|path| matches_any(&filters, repo_root, path));
+    }
+    workspaces.sort();
+    Ok(workspaces)
+}
+
+/// Display name of a workspace: its path relative to the repository root
+pub fn workspace_name(repo_root: &Path, workspace: &Path) -> String {
+    match workspace.strip_prefix(repo_root) {
+        Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+        Ok(relative) => relative.display().to_string(),
+        Err(_) => workspace.display().to_string(),
+    }
+}
+
+fn search(dir: &Path, migrations_dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    let own_migrations = dir.join(migrations_dir);
+    if own_migrations.is_dir() {
+        found.push(dir.to_path_buf());
+    }
+
+    let entries =
+        fs::read_dir(dir).with_context(|| format!("Failed to read directory {}", dir.display()))?;
+    let mut children: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref())
+        })
+        .map(|entry| entry.path())
+        .filter(|path| !own_migrations.starts_with(path))
+        .collect();
+    children.sort();
+
+    for child in children {
+        search(&child, migrations_dir, found)?;
+    }
+    Ok(())
+}
+
+fn compile(globs: &[String]) -> Result<Vec<Pattern>> {
+    globs
+        .iter()
+        .map(|g| Pattern::new(g).with_context(|| format!("Invalid workspace pattern: {}", g)))
+        .collect()
+}
+
+fn matches_any(patterns: &[Pattern], repo_root: &Path, workspace: &Path) -> bool {
+    let name = workspace_name(repo_root, workspace);
+    patterns.iter().any(|p| p.matches(&name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn names(root: &Path, workspaces: &[PathBuf]) -> Vec<String> {
+        workspaces.iter().map(|w| workspace_name(root, w)).collect()
+    }
+
+    #[test]
+    fn test_discovers_and_filters_workspaces() {
+        let dir = TempDir::new().unwrap();
+        for path in [
+            "migrations",
+            "packages/api/migrations",
+            "packages/web/migrations",
+            "packages/web/node_modules/dep/migrations",
+            ".cache/migrations",
+            "docs",
+        ] {
+            fs::create_dir_all(dir.path().join(path)).unwrap();
+        }
+
+        let all = discover_workspaces(dir.path(), Path::new("migrations"), &[]).unwrap();
+        assert_eq!(
+            names(dir.path(), &all),
+            [".", "packages/api", "packages/web"]
+        );
+
+        let filtered = discover_workspaces(
+            dir.path(),
+            Path::new("migrations"),
+            &["packages/w*".to_string()],
+        )
+        .unwrap();
+        assert_eq!(names(dir.path(), &filtered), ["packages/web"]);
+    }
+
+    #[test]
+    fn test_workspace_members_from_config() {
+        let dir = TempDir::new().unwrap();
+        for path in [
+            "packages/api/migrations",
+            "packages/old/migrations",
+            "tools/migrations",
+        ] {
+            fs::create_dir_all(dir.path().join(path)).unwrap();
+        }
+        fs::write(
+            dir.path().join("migrate.toml"),
+            "[workspace]\nmembers = [\"packages/*\"]\nexclude = [\"packages/old\"]\n",
+        )
+        .unwrap();
+
+        let workspaces = discover_workspaces(dir.path(), Path::new("migrations"), &[]).unwrap();
+        assert_eq!(names(dir.path(), &workspaces), ["packages/api"]);
+    }
+}
@@ -0,0 +1,33 @@
+#!/usr/bin/env bash
+set -euo pipefail
+# Description: {{DESCRIPTION}}
+
+cd "$MIGRATE_PROJECT_ROOT"
+
+# Recorded with `migrate record`: applies the captured patch from the asset directory.
+patch_file="$MIGRATE_MIGRATIONS_DIR/$MIGRATE_ID/changes.patch"
+
+# Inside a git repository, patch paths are resolved from the repository root,
+# so point git at the project root's location within it.
+apply_args=(--whitespace=nowarn)
+prefix="$(git rev-parse --show-prefix 2>/dev/null || true)"
+if [ -n "$prefix" ]; then
+    apply_args+=(--directory="$prefix")
+fi
+
+if git apply --check "${apply_args[@]}" "$patch_file" 2>/dev/null; then
+    if [ "$MIGRATE_DRY_RUN" = "true" ]; then
+        echo "Would apply recorded changes:"
+        git apply --stat "${apply_args[@]}" "$patch_file"
+        exit 0
+    fi
+    git apply "${apply_args[@]}" "$patch_file"
+    echo "Applied recorded changes from $MIGRATE_ID"
+elif git apply --reverse --check "${apply_args[@]}" "$patch_file" 2>/dev/null; then
+    echo "Recorded changes from $MIGRATE_ID are already present, nothing to do"
+else
+    echo "Recorded changes from $MIGRATE_ID do not apply cleanly to $MIGRATE_PROJECT_ROOT:" >&2
+    git apply --check "${apply_args[@]}" "$patch_file" >&2 || true
+    echo "Resolve the conflicts manually (e.g. git apply --3way $patch_file), then re-run migrate up." >&2
+    exit 1
+fi
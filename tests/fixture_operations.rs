@@ -926,3 +926,348 @@ VERIFY
     assert!(verify_content.contains("Read config: sample-project"));
     assert!(verify_content.contains("Features: auth, logging"));
 }
+
+// =============================================================================
+// Test: Declarative TOML migration with built-in steps
+// =============================================================================
+
+#[test]
+fn test_declarative_migration_runs_builtin_steps() {
+    let temp_dir = setup_fixture();
+    let asset_dir = temp_dir.path().join("migrations/00001-declarative");
+    fs::create_dir_all(&asset_dir).unwrap();
+    fs::write(asset_dir.join("prettierrc.json"), "{ \"semi\": false }\n").unwrap();
+
+    fs::write(
+        temp_dir.path().join("migrations/00001-declarative.toml"),
+        r#"
+description = "Tooling setup without external tools"
+
+[[steps]]
+action = "copy"
+from = "prettierrc.json"
+to = ".prettierrc.json"
+
+[[steps]]
+action = "replace"
+files = "src/**/*.ts"
+pattern = "deprecated(\\w+)"
+replacement = "legacy$1"
+
+[[steps]]
+action = "rename"
+from = "data/users.csv"
+to = "data/archive/users.csv"
+
+[[steps]]
+action = "delete"
+path = "README.md"
+
+[[steps]]
+action = "ensure-line"
+path = ".gitignore"
+line = "dist/"
+"#,
+    )
+    .unwrap();
+
+    let output = run_migrate(temp_dir.path());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        output.status.success(),
+        "Migration should succeed: stdout={}, stderr={}",
+        stdout,
+        stderr
+    );
+    assert!(stdout.contains("copy prettierrc.json -> .prettierrc.json"));
+
+    let root = temp_dir.path();
+    assert!(root.join(".prettierrc.json").exists());
+    let utils = fs::read_to_string(root.join("src/utils.ts")).unwrap();
+    assert!(utils.contains("legacyHelper"));
+    assert!(!utils.contains("deprecatedHelper"));
+    assert!(root.join("data/archive/users.csv").exists());
+    assert!(!root.join("data/users.csv").exists());
+    assert!(!root.join("README.md").exists());
+    assert_eq!(
+        fs::read_to_string(root.join(".gitignore")).unwrap(),
+        "dist/\n"
+    );
+
+    let history = fs::read_to_string(root.join("migrations/history")).unwrap();
+    assert!(history.contains("00001-declarative"));
+}
+
+#[test]
+fn test_declarative_migration_dry_run_previews_steps() {
+    let temp_dir = setup_fixture();
+    let original_utils = fs::read_to_string(temp_dir.path().join("src/utils.ts")).unwrap();
+
+    fs::write(
+        temp_dir.path().join("migrations/00001-declarative.toml"),
+        r#"
+[[steps]]
+action = "replace"
+files = "src/*.ts"
+pattern = "deprecatedHelper"
+replacement = "legacyHelper"
+
+[[steps]]
+action = "delete"
+path = "README.md"
+"#,
+    )
+    .unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args([
+            "--root",
+            temp_dir.path().to_str().unwrap(),
+            "up",
+            "--dry-run",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success(), "Dry run should succeed");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("would replace /deprecatedHelper/ in src/*.ts"));
+    assert!(stdout.contains("src/utils.ts"));
+    assert!(stdout.contains("would delete README.md"));
+
+    // Nothing was changed
+    assert!(temp_dir.path().join("README.md").exists());
+    let current_utils = fs::read_to_string(temp_dir.path().join("src/utils.ts")).unwrap();
+    assert_eq!(original_utils, current_utils);
+    assert!(!temp_dir.path().join("migrations/history").exists());
+}
+
+// =============================================================================
+// Test: Structured data edits without jq
+// =============================================================================
+
+#[test]
+fn test_declarative_migration_edits_json() {
+    let temp_dir = setup_fixture();
+
+    fs::write(
+        temp_dir.path().join("migrations/00001-update-config.toml"),
+        r#"
+[[steps]]
+action = "edit"
+path = "config.json"
+edits = [
+    { op = "set", key = "version", value = "2.0.0" },
+    { op = "set", key = "settings.debug", value = true },
+    { op = "rename", from = "settings.maxRetries", to = "settings.retries" },
+    { op = "append", key = "features", value = "metrics" },
+]
+"#,
+    )
+    .unwrap();
+
+    let output = run_migrate(temp_dir.path());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        output.status.success(),
+        "Migration should succeed: stdout={}, stderr={}",
+        stdout,
+        stderr
+    );
+
+    let content = fs::read_to_string(temp_dir.path().join("config.json")).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&content).expect("Should be valid JSON");
+    assert_eq!(json["version"], "2.0.0");
+    assert_eq!(json["settings"]["debug"], true);
+    assert_eq!(json["settings"]["retries"], 3);
+    assert!(json["settings"].get("maxRetries").is_none());
+    assert_eq!(json["features"][2], "metrics");
+
+    // Key order and two-space indentation are preserved
+    assert!(content.starts_with("{\n  \"name\": \"sample-project\",\n  \"version\": \"2.0.0\","));
+}
+
+#[test]
+fn test_declarative_migration_renders_templates() {
+    let temp_dir = setup_fixture();
+    let migrations = temp_dir.path().join("migrations");
+    let asset_dir = migrations.join("00001-add-service");
+    fs::create_dir_all(asset_dir.join("service")).unwrap();
+    fs::write(
+        asset_dir.join("service/README.md.tmpl"),
+        "# {{name}}\n{{#if private}}\nInternal package.\n{{/if}}\nPorts:\n{{#each ports}}\n- {{this}}\n{{/each}}\n",
+    )
+    .unwrap();
+    fs::write(asset_dir.join("service/logo.txt"), "{{not rendered}}").unwrap();
+
+    fs::write(
+        migrations.join("variables.toml"),
+        r#"
+name = { file = "config.json", key = "name" }
+private = false
+ports = [80, 443]
+"#,
+    )
+    .unwrap();
+    fs::write(
+        migrations.join("00001-add-service.toml"),
+        r#"
+[[steps]]
+action = "render"
+from = "service"
+to = "service"
+"#,
+    )
+    .unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["up", "--set", "private=true"])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Migration should succeed: stdout={}, stderr={}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("service/README.md")).unwrap(),
+        "# sample-project\nInternal package.\nPorts:\n- 80\n- 443\n"
+    );
+    // Files without the template extension are copied verbatim
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("service/logo.txt")).unwrap(),
+        "{{not rendered}}"
+    );
+}
+
+#[test]
+fn test_helper_edits_json_in_place() {
+    let temp_dir = setup_fixture();
+    let config = temp_dir.path().join("config.json");
+
+    let run_helper = |args: &[&str]| {
+        let output = Command::new(get_binary_path())
+            .arg("helper")
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to execute command");
+        assert!(
+            output.status.success(),
+            "helper {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    };
+
+    run_helper(&["set", "config.json", "settings.newFeature", "enabled"]);
+    run_helper(&["delete", "config.json", "settings.maxRetries"]);
+    run_helper(&[
+        "json-patch",
+        "config.json",
+        r#"[{"op": "add", "path": "/features/0", "value": "first"}]"#,
+    ]);
+    run_helper(&["merge-patch", "config.json", r#"{"version": null}"#]);
+
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&config).unwrap()).unwrap();
+    assert_eq!(json["settings"]["newFeature"], "enabled");
+    assert!(json["settings"].get("maxRetries").is_none());
+    assert_eq!(json["features"][0], "first");
+    assert!(json.get("version").is_none());
+}
+
+// =============================================================================
+// Test: Script migration using migrate helper commands
+// =============================================================================
+
+#[test]
+fn test_script_migration_uses_helpers() {
+    let temp_dir = setup_fixture();
+    let asset_dir = temp_dir.path().join("migrations/00001-use-helpers");
+    fs::create_dir_all(&asset_dir).unwrap();
+    fs::write(asset_dir.join("editorconfig"), "root = true\n").unwrap();
+    fs::write(
+        asset_dir.join("env.tmpl"),
+        "NAME={{ name }}\nPORT={{port}}\n",
+    )
+    .unwrap();
+
+    create_migration(
+        temp_dir.path(),
+        "00001-use-helpers.sh",
+        r#"#!/usr/bin/env bash
+set -euo pipefail
+cd "$MIGRATE_PROJECT_ROOT"
+
+"$MIGRATE_BIN" helper copy-asset editorconfig .editorconfig
+"$MIGRATE_BIN" helper ensure-line .gitignore "dist/"
+"$MIGRATE_BIN" helper replace "src/*.ts" "deprecatedHelper()" "legacyHelper()" --literal
+"$MIGRATE_BIN" helper json-set config.json settings.debug true
+"$MIGRATE_BIN" helper json-delete config.json settings.maxRetries
+"$MIGRATE_BIN" helper render-template env.tmpl .env --set name=sample --set port=8080
+"#,
+    );
+
+    let output = run_migrate(temp_dir.path());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        output.status.success(),
+        "Migration should succeed: stdout={}, stderr={}",
+        stdout,
+        stderr
+    );
+
+    let root = temp_dir.path();
+    assert_eq!(
+        fs::read_to_string(root.join(".editorconfig")).unwrap(),
+        "root = true\n"
+    );
+    assert_eq!(
+        fs::read_to_string(root.join(".gitignore")).unwrap(),
+        "dist/\n"
+    );
+    assert!(fs::read_to_string(root.join("src/utils.ts"))
+        .unwrap()
+        .contains("legacyHelper()"));
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(root.join("config.json")).unwrap()).unwrap();
+    assert_eq!(json["settings"]["debug"], true);
+    assert!(json["settings"].get("maxRetries").is_none());
+    assert_eq!(
+        fs::read_to_string(root.join(".env")).unwrap(),
+        "NAME=sample\nPORT=8080\n"
+    );
+
+    // Helpers report touched files back to `up`
+    assert!(stdout.contains("Files touched (5):"), "{}", stdout);
+    assert!(stdout.contains("config.json  (00001-use-helpers)"));
+}
+
+#[test]
+fn test_helper_honors_dry_run() {
+    let temp_dir = setup_fixture();
+    let original = fs::read_to_string(temp_dir.path().join("config.json")).unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["helper", "json-set", "config.json", "version", "\"9.9.9\""])
+        .env("MIGRATE_PROJECT_ROOT", temp_dir.path())
+        .env("MIGRATE_DRY_RUN", "true")
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("would edit config.json"), "{}", stdout);
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("config.json")).unwrap(),
+        original
+    );
+}
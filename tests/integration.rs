@@ -1,7 +1,7 @@
 use std::fs;
 use std::os::unix::fs::PermissionsExt;
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Command, Stdio};
 
 fn get_binary_path() -> PathBuf {
     let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -337,8 +337,8 @@ touch "$MIGRATE_PROJECT_ROOT/third.txt"
         .output()
         .expect("Failed to execute command");
 
-    // Should fail
-    assert!(!output.status.success());
+    // Should fail with the migration-failed exit code
+    assert_eq!(output.status.code(), Some(3));
 
     // First file should exist
     assert!(temp_dir.path().join("first.txt").exists());
@@ -352,6 +352,188 @@ touch "$MIGRATE_PROJECT_ROOT/third.txt"
     assert!(!history.contains("00002-fail"));
 }
 
+#[test]
+fn test_up_json_format() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+    let script = migrations_dir.join("00001-greet.sh");
+    fs::write(
+        &script,
+        "#!/usr/bin/env bash\necho hello\necho warning >&2\n",
+    )
+    .unwrap();
+    let mut perms = fs::metadata(&script).unwrap().permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(&script, perms).unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args([
+            "--root",
+            temp_dir.path().to_str().unwrap(),
+            "up",
+            "--format",
+            "json",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let events: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("each line is a JSON event"))
+        .collect();
+    let kinds: Vec<&str> = events
+        .iter()
+        .map(|e| e["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            "run-started",
+            "migration-started",
+            "output",
+            "output",
+            "migration-finished",
+            "run-finished"
+        ]
+    );
+    // stdout and stderr are read separately, so their lines may interleave either way
+    let lines: Vec<(&str, &str)> = events[2..4]
+        .iter()
+        .map(|e| (e["stream"].as_str().unwrap(), e["line"].as_str().unwrap()))
+        .collect();
+    assert!(lines.contains(&("stdout", "hello")), "{:?}", lines);
+    assert!(lines.contains(&("stderr", "warning")), "{:?}", lines);
+    assert_eq!(events[4]["status"], "applied");
+    assert!(events[4]["duration_ms"].is_u64());
+}
+
+#[test]
+fn test_up_writes_junit_and_markdown_reports() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+    for (name, body) in [
+        ("00001-ok.sh", "echo fine\n"),
+        ("00002-fail.sh", "echo 'config.json: missing' >&2\nexit 3\n"),
+        ("00003-later.sh", "\n"),
+    ] {
+        let path = migrations_dir.join(name);
+        fs::write(&path, format!("#!/usr/bin/env bash\n{}", body)).unwrap();
+        let mut perms = fs::metadata(&path).unwrap().permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(&path, perms).unwrap();
+    }
+
+    let junit = temp_dir.path().join("reports/migrate.xml");
+    let markdown = temp_dir.path().join("reports/migrate.md");
+    let output = Command::new(get_binary_path())
+        .args(["--root", temp_dir.path().to_str().unwrap(), "up"])
+        .arg(format!("--report=junit={}", junit.display()))
+        .arg(format!("--report=markdown={}", markdown.display()))
+        .output()
+        .expect("Failed to execute command");
+    assert_eq!(output.status.code(), Some(3));
+
+    let xml = fs::read_to_string(&junit).unwrap();
+    assert!(
+        xml.contains("tests=\"3\" failures=\"1\" skipped=\"1\""),
+        "{}",
+        xml
+    );
+    assert!(xml.contains(">config.json: missing</failure>"), "{}", xml);
+    assert!(xml.contains("<skipped message=\"not run after an earlier failure\"/>"));
+
+    let md = fs::read_to_string(&markdown).unwrap();
+    assert!(md.contains("**1 passed, 1 failed, 1 skipped**"), "{}", md);
+    assert!(md.contains("`00002-fail` | failed (exit code 3)"), "{}", md);
+}
+
+#[test]
+fn test_concurrent_output_is_attributed_to_its_migration() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+    for (name, body) in [
+        ("00001-a.sh", "echo A-start\nsleep 0.5\necho A-end\n"),
+        ("00002-b.sh", "sleep 0.2\necho B-out\nexit 2\n"),
+    ] {
+        let path = migrations_dir.join(name);
+        fs::write(&path, format!("#!/usr/bin/env bash\n{}", body)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    let junit = temp_dir.path().join("r.xml");
+    let output = Command::new(get_binary_path())
+        .args([
+            "--root",
+            temp_dir.path().to_str().unwrap(),
+            "up",
+            "--jobs",
+            "2",
+        ])
+        .arg(format!("--report=junit={}", junit.display()))
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+
+    let xml = fs::read_to_string(&junit).unwrap();
+    assert!(
+        xml.contains("<system-out>A-start\nA-end</system-out>"),
+        "{}",
+        xml
+    );
+    assert!(
+        xml.contains("type=\"exit code 2\">B-out</failure>"),
+        "{}",
+        xml
+    );
+    assert!(xml.contains("<system-out>B-out</system-out>"), "{}", xml);
+
+    // Lines printed while another migration's header is last are tagged with their ID
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("  [00002-b] B-out"), "{}", stdout);
+    assert!(stdout.contains("  [00001-a] A-end"), "{}", stdout);
+}
+
+#[test]
+fn test_exit_codes() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+    fs::write(
+        migrations_dir.join("00001-notes.toml"),
+        "[[steps]]\naction = \"write\"\npath = \"notes.txt\"\ncontent = \"hi\"\n",
+    )
+    .unwrap();
+
+    let run = |args: &[&str]| {
+        Command::new(get_binary_path())
+            .args(["--root", temp_dir.path().to_str().unwrap()])
+            .args(args)
+            .output()
+            .expect("Failed to execute command")
+            .status
+            .code()
+    };
+
+    assert_eq!(run(&["check"]), Some(4));
+    assert_eq!(run(&["baseline", "00001"]), Some(5));
+    assert_eq!(run(&["create", "x", "--template", "cobol"]), Some(8));
+
+    // Another run holds the lock
+    let lock = fs::File::create(migrations_dir.join("history.lock")).unwrap();
+    lock.lock().unwrap();
+    assert_eq!(run(&["up"]), Some(7));
+    drop(lock);
+    // A leftover lock file that nobody holds doesn't block the next run
+    assert_eq!(run(&["up"]), Some(0));
+
+    fs::write(migrations_dir.join("history"), "00001-notes not-a-date\n").unwrap();
+    assert_eq!(run(&["status"]), Some(6));
+}
+
 #[test]
 fn test_status_shows_applied_and_pending() {
     let temp_dir = create_temp_dir();
@@ -396,3 +578,1010 @@ fn test_status_shows_applied_and_pending() {
     assert!(stdout.contains("Pending (1)"));
     assert!(stdout.contains("00002-second"));
 }
+
+#[test]
+fn test_log_and_show() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir_all(migrations_dir.join("1f72f-first")).unwrap();
+    fs::write(
+        migrations_dir.join("1f72f-first.sh"),
+        "#!/usr/bin/env bash\n# Description: Add the first file\n",
+    )
+    .unwrap();
+    fs::write(migrations_dir.join("1f72f-first/config.json"), "{}").unwrap();
+    fs::write(
+        migrations_dir.join("1f730-second.sh"),
+        "#!/usr/bin/env bash\n",
+    )
+    .unwrap();
+    fs::write(
+        migrations_dir.join("history"),
+        "00001-removed 2024-01-01T00:00:00+00:00\n\
+         1f72f-first 2024-02-01T10:00:00+00:00 duration_ms=120 exit_code=0 user=dev host=box run=r1\n",
+    )
+    .unwrap();
+    let root = temp_dir.path().to_str().unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "log"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("(run not recorded)"), "{}", stdout);
+    assert!(
+        stdout.contains("2024-02-01 10:00:00  run r1  dev@box"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("+ 1f72f-first  10:00:00  (120ms)"),
+        "{}",
+        stdout
+    );
+
+    let output = Command::new(get_binary_path())
+        .args([
+            "--root",
+            root,
+            "log",
+            "--since",
+            "2024-01-15",
+            "--grep",
+            "first",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1f72f-first"));
+    assert!(!stdout.contains("00001-removed"));
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "show", "1f72f"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("State:       applied"), "{}", stdout);
+    assert!(
+        stdout.contains("Created:     2025-01-17 14:30 UTC"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Description: Add the first file"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("By:          dev@box"), "{}", stdout);
+    assert!(stdout.contains("    config.json"), "{}", stdout);
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "show", "1f730-second"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("State:       pending"));
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "show", "00001-removed"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("State:       missing on disk"));
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "show", "zzzzz-nope"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_migration_headers() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+    let write_script = |name: &str, content: &str| {
+        let path = migrations_dir.join(name);
+        fs::write(&path, content).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    };
+    write_script(
+        "00001-env.sh",
+        "#!/usr/bin/env bash\n# Description: Write greeting\n# Tags: setup\n# Env: GREETING=hello\n# Owner: me\n\necho \"$GREETING\" > greeting.txt\n",
+    );
+    write_script(
+        "00002-slow.sh",
+        "#!/usr/bin/env bash\n# Timeout: 200ms\n\nsleep 5\n",
+    );
+    let root = temp_dir.path().to_str().unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "status"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("- 00001-env  Write greeting  [setup]"),
+        "{}",
+        stdout
+    );
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "check"])
+        .output()
+        .expect("Failed to execute command");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("warning: 00001-env.sh:5: unknown header field 'Owner'"),
+        "{}",
+        stderr
+    );
+
+    let started = std::time::Instant::now();
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "up"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(started.elapsed() < std::time::Duration::from_secs(4));
+    assert!(stdout.contains("timed out after 200ms"), "{}", stdout);
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("greeting.txt")).unwrap(),
+        "hello\n"
+    );
+
+    write_script(
+        "00002-slow.sh",
+        "#!/usr/bin/env bash\n# Requires: definitely-not-a-command\n\ntrue\n",
+    );
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "up"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("requires 'definitely-not-a-command', which was not found on PATH"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_tag_filters() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+    for (name, tags) in [
+        ("00001-shared", ""),
+        ("00002-web", "# Tags: frontend\n"),
+        ("00003-ci", "# Tags: ci-only\n"),
+    ] {
+        let path = migrations_dir.join(format!("{}.sh", name));
+        fs::write(
+            &path,
+            format!("#!/usr/bin/env bash\n{}\ntouch {}.done\n", tags, name),
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let root = temp_dir.path().to_str().unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "status", "--tag", "frontend"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Pending (1):\n  - 00002-web  [frontend]"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("Filtered out by tags (2):"), "{}", stdout);
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "up", "--exclude-tag", "ci-only"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    assert!(temp_dir.path().join("00001-shared.done").exists());
+    assert!(temp_dir.path().join("00002-web.done").exists());
+    assert!(!temp_dir.path().join("00003-ci.done").exists());
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "check", "--exclude-tag", "ci-only"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "status"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Pending (1):\n  - 00003-ci  [ci-only]"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_not_applicable_migrations() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+    for (name, body) in [
+        (
+            "00001-react",
+            "# RunIf: file-exists package.json\n\ntouch react.done\n",
+        ),
+        ("00002-opt-out", "exit \"$MIGRATE_NOT_APPLICABLE\"\n"),
+        (
+            "00003-always",
+            "# RunIf: test -d migrations\n\ntouch always.done\n",
+        ),
+    ] {
+        let path = migrations_dir.join(format!("{}.sh", name));
+        fs::write(&path, format!("#!/usr/bin/env bash\n{}", body)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let root = temp_dir.path().to_str().unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "up"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(
+        stdout.contains("○ not applicable: RunIf 'file-exists package.json' does not hold"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("○ not applicable: exited with code 99"),
+        "{}",
+        stdout
+    );
+    assert!(!temp_dir.path().join("react.done").exists());
+    assert!(temp_dir.path().join("always.done").exists());
+
+    // Recorded, so creating package.json later doesn't make it pending
+    fs::write(temp_dir.path().join("package.json"), "{}").unwrap();
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "status"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Pending"), "{}", stdout);
+    assert!(stdout.contains("  ○ 00001-react  "), "{}", stdout);
+    assert!(stdout.contains("(not applicable)"), "{}", stdout);
+    assert!(stdout.contains("  + 00003-always  "), "{}", stdout);
+
+    let history = fs::read_to_string(migrations_dir.join("history")).unwrap();
+    assert!(history.contains("outcome=not-applicable"), "{}", history);
+}
+
+#[test]
+fn test_postcondition_verification() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+    for (name, body) in [
+        (
+            "00001-config.sh",
+            "#!/usr/bin/env bash\n# Verify: grep -q enabled config.txt\n\necho enabled > config.txt\n",
+        ),
+        ("00001-config.verify.sh", "#!/usr/bin/env bash\ntest -s config.txt\n"),
+    ] {
+        let path = migrations_dir.join(name);
+        fs::write(&path, body).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let root = temp_dir.path().to_str().unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "up"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("Applying 1 migration(s)"), "{}", stdout);
+    assert!(
+        stdout.contains("verify 00001-config.verify.sh"),
+        "{}",
+        stdout
+    );
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "verify", "--postconditions"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("1 verified, 0 failed"), "{}", stdout);
+
+    // Once the project drifts, verification fails with its own exit code
+    fs::write(temp_dir.path().join("config.txt"), "disabled\n").unwrap();
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "verify", "--postconditions"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(10), "{}", stdout);
+    assert!(
+        stdout.contains("'grep -q enabled config.txt' exited with code 1"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_migration_params() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+    let path = migrations_dir.join("00001-api.sh");
+    fs::write(
+        &path,
+        "#!/usr/bin/env bash\n# Param: api-host required New API hostname\n# Param: team=platform\n\necho \"$MIGRATE_PARAM_API_HOST $MIGRATE_PARAM_TEAM\" > api.txt\n",
+    )
+    .unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    let params_file = temp_dir.path().join("params.toml");
+    fs::write(
+        &params_file,
+        "api-host = \"old.example.com\"\nteam = \"web\"\n",
+    )
+    .unwrap();
+    let root = temp_dir.path().to_str().unwrap();
+
+    // Not a terminal, so nothing is prompted for
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "up"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to execute command");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(11), "{}", stderr);
+    assert!(
+        stderr.contains("00001-api needs api-host (New API hostname)"),
+        "{}",
+        stderr
+    );
+    assert!(!temp_dir.path().join("api.txt").exists());
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root, "up", "--params"])
+        .arg(&params_file)
+        .args(["--set", "api-host=api.example.com"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("api.txt")).unwrap(),
+        "api.example.com web\n"
+    );
+}
+
+#[test]
+fn test_migration_outputs() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+    let write = |name: &str, content: &str| {
+        let path = migrations_dir.join(name);
+        fs::write(&path, content).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    };
+    write(
+        "00001-create-db.sh",
+        "#!/usr/bin/env bash\necho \"db_url=postgres://db/app\" >> \"$MIGRATE_OUTPUT\"\n",
+    );
+    let root = temp_dir.path().to_str().unwrap();
+    let run = |args: &[&str]| {
+        let output = Command::new(get_binary_path())
+            .args(["--root", root])
+            .args(args)
+            .output()
+            .expect("Failed to execute command");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+    run(&["up"]);
+
+    // A later `up` still sees the outputs recorded in history
+    write(
+        "00002-configure.sh",
+        "#!/usr/bin/env bash\necho \"$MIGRATE_OUT_00001_CREATE_DB_DB_URL\" > db.txt\n",
+    );
+    run(&["up"]);
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("db.txt")).unwrap(),
+        "postgres://db/app\n"
+    );
+
+    let stdout = run(&["show", "00001-create-db"]);
+    assert!(
+        stdout.contains("Output:      db_url=postgres://db/app"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_migration_messages() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+    let path = migrations_dir.join("00001-upgrade.sh");
+    fs::write(
+        &path,
+        "#!/usr/bin/env bash\n\
+         echo '{\"type\": \"progress\", \"percent\": 40, \"message\": \"upgrading\"}' >&\"$MIGRATE_MESSAGE_FD\"\n\
+         echo '{\"type\": \"warning\", \"message\": \"no lockfile\"}' >&3\n\
+         echo '{\"type\": \"follow-up\", \"message\": \"rotate the API key\"}' >&3\n",
+    )
+    .unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    let report = temp_dir.path().join("report.md");
+
+    let output = Command::new(get_binary_path())
+        .args([
+            "--root",
+            temp_dir.path().to_str().unwrap(),
+            "up",
+            "--report",
+        ])
+        .arg(format!("markdown={}", report.display()))
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("  [ 40%] upgrading"), "{}", stdout);
+    assert!(stdout.contains("  warning: no lockfile"), "{}", stdout);
+    assert!(
+        stdout.contains("Manual follow-up needed (1):\n  rotate the API key  (00001-upgrade)"),
+        "{}",
+        stdout
+    );
+    let markdown = fs::read_to_string(&report).unwrap();
+    assert!(
+        markdown.contains("- ⚠️ **Warning** (`00001-upgrade`): no lockfile"),
+        "{}",
+        markdown
+    );
+}
+
+#[test]
+fn test_state_backend_from_config() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+
+    let first = migrations_dir.join("00001-first.sh");
+    fs::write(&first, "#!/usr/bin/env bash\necho first").unwrap();
+    let mut perms = fs::metadata(&first).unwrap().permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(&first, perms).unwrap();
+
+    fs::write(
+        temp_dir.path().join("migrate.toml"),
+        "[state]\nbackend = \"jsonl\"\npath = \"state/migrations.jsonl\"\n",
+    )
+    .unwrap();
+
+    let run = |command: &str| {
+        let output = Command::new(get_binary_path())
+            .args(["--root", temp_dir.path().to_str().unwrap(), command])
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    run("up");
+    assert!(!migrations_dir.join("history").exists());
+    let state = fs::read_to_string(temp_dir.path().join("state/migrations.jsonl")).unwrap();
+    let record: serde_json::Value = serde_json::from_str(state.lines().next().unwrap()).unwrap();
+    assert_eq!(record["type"], "applied");
+    assert_eq!(record["id"], "00001-first");
+
+    // The lock is released after the run
+    assert!(!temp_dir.path().join("state/migrations.jsonl.lock").exists());
+    assert!(run("status").contains("Applied (1)"));
+    assert!(run("up").contains("No pending migrations."));
+}
+
+#[test]
+fn test_shared_migrations_with_per_root_state() {
+    let shared = create_temp_dir();
+    let first = shared.path().join("00001-first.sh");
+    fs::write(&first, "#!/usr/bin/env bash\necho applied >> marker").unwrap();
+    let mut perms = fs::metadata(&first).unwrap().permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(&first, perms).unwrap();
+
+    let run = |root: &std::path::Path, args: &[&str]| {
+        let output = Command::new(get_binary_path())
+            .args(["--root", root.to_str().unwrap()])
+            .args(["--migrations", shared.path().to_str().unwrap()])
+            .args(["--state-location", "project"])
+            .args(args)
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let target_a = create_temp_dir();
+    let target_b = create_temp_dir();
+    run(target_a.path(), &["up"]);
+    run(target_b.path(), &["up"]);
+
+    // Each target root has its own state; nothing is written next to the migrations
+    assert!(!shared.path().join("history").exists());
+    for target in [&target_a, &target_b] {
+        assert_eq!(
+            fs::read_to_string(target.path().join("marker")).unwrap(),
+            "applied\n"
+        );
+        assert!(target.path().join(".migrate/history").exists());
+    }
+
+    let status = run(target_a.path(), &["status"]);
+    assert!(
+        status.contains(&format!(
+            "State: {}",
+            target_a.path().join(".migrate/history").display()
+        )),
+        "{}",
+        status
+    );
+
+    // An explicit state file overrides the location
+    let state_file = target_a.path().join("custom-state");
+    run(
+        target_a.path(),
+        &["--state", state_file.to_str().unwrap(), "up"],
+    );
+    assert!(fs::read_to_string(&state_file)
+        .unwrap()
+        .starts_with("00001-first "));
+}
+
+#[test]
+fn test_up_targets_runs_each_target_with_own_state() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+    let scripts = [
+        ("00001-first.sh", "echo first >> log"),
+        // Fails only for the target named "broken"
+        (
+            "00002-second.sh",
+            "[ \"$(basename \"$MIGRATE_PROJECT_ROOT\")\" != broken ]",
+        ),
+        ("00003-third.sh", "echo third >> log"),
+    ];
+    for (name, body) in scripts {
+        let path = migrations_dir.join(name);
+        fs::write(&path, format!("#!/usr/bin/env bash\n{}\n", body)).unwrap();
+        let mut perms = fs::metadata(&path).unwrap().permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(&path, perms).unwrap();
+    }
+    for target in ["apps/alpha", "apps/broken", "apps/gamma"] {
+        fs::create_dir_all(temp_dir.path().join(target)).unwrap();
+    }
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", temp_dir.path().to_str().unwrap()])
+        .args(["up", "--targets", "apps/*", "--parallel", "2"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(
+        !output.status.success(),
+        "A failing target should fail the run"
+    );
+    assert!(
+        stdout.contains("Target       00001-first  00002-second  00003-third"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("apps/alpha   ✓            ✓             ✓"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("apps/broken  ✓            ✗             ·"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("2 target(s) succeeded, 1 failed"),
+        "{}",
+        stdout
+    );
+
+    // Only the failing target stopped; state is kept per target
+    let log = |target: &str| fs::read_to_string(temp_dir.path().join(target).join("log")).unwrap();
+    assert_eq!(log("apps/gamma"), "first\nthird\n");
+    assert_eq!(log("apps/broken"), "first\n");
+    assert!(temp_dir.path().join("apps/gamma/.migrate/history").exists());
+    assert!(!migrations_dir.join("history").exists());
+}
+
+#[test]
+fn test_up_targets_marks_migrations_excluded_by_tag() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+    for (name, tags) in [("00001-web.sh", "web"), ("00002-ci.sh", "ci-only")] {
+        let path = migrations_dir.join(name);
+        fs::write(
+            &path,
+            format!("#!/usr/bin/env bash\n# Tags: {}\ntrue\n", tags),
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    fs::create_dir_all(temp_dir.path().join("apps/alpha")).unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", temp_dir.path().to_str().unwrap()])
+        .args(["up", "--targets", "apps/*", "--exclude-tag", "ci-only"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("apps/alpha  ✓          /"), "{}", stdout);
+    assert!(
+        stdout.contains(
+            "○ not applicable  · skipped after failure  - already applied  / excluded by tag"
+        ),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_up_targets_refuse_shared_state() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+    let path = migrations_dir.join("00001-init.sh");
+    fs::write(&path, "#!/usr/bin/env bash\ntouch initialized\n").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    for target in ["apps/alpha", "apps/beta"] {
+        fs::create_dir_all(temp_dir.path().join(target)).unwrap();
+    }
+    let run = |args: &[&str]| {
+        Command::new(get_binary_path())
+            .args(["--root", temp_dir.path().to_str().unwrap()])
+            .args(args)
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    let output = run(&[
+        "--state-location",
+        "migrations",
+        "up",
+        "--targets",
+        "apps/*",
+    ]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--state-location migrations cannot be combined with --targets"),
+        "{}",
+        stderr
+    );
+    assert!(!temp_dir.path().join("apps/alpha/initialized").exists());
+
+    // A target whose own config keeps state next to the shared migrations fails alone
+    fs::write(temp_dir.path().join("apps/beta/migrate.toml"), "").unwrap();
+    let output = run(&["up", "--targets", "apps/*"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success());
+    assert!(
+        stdout.contains("1 target(s) succeeded, 1 failed"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("keeps state in the shared migrations directory"),
+        "{}",
+        stdout
+    );
+    assert!(temp_dir.path().join("apps/alpha/initialized").exists());
+    assert!(!temp_dir.path().join("apps/beta/initialized").exists());
+    assert!(!migrations_dir.join("history").exists());
+}
+
+#[test]
+fn test_up_targets_keep_outputs_apart() {
+    let temp_dir = create_temp_dir();
+    let migrations_dir = temp_dir.path().join("migrations");
+    fs::create_dir(&migrations_dir).unwrap();
+    let scripts = [
+        (
+            "00001-name.sh",
+            // Overlap the targets' runs so a shared output file would mix them up
+            "name=$(basename \"$MIGRATE_PROJECT_ROOT\")\necho \"name=$name\" >> \"$MIGRATE_OUTPUT\"\nsleep 0.3\necho \"path=$name\" >> \"$MIGRATE_OUTPUT\"",
+        ),
+        (
+            "00002-read.sh",
+            "echo \"$MIGRATE_OUT_00001_NAME_NAME $MIGRATE_OUT_00001_NAME_PATH\" > seen",
+        ),
+    ];
+    for (name, body) in scripts {
+        let path = migrations_dir.join(name);
+        fs::write(&path, format!("#!/usr/bin/env bash\n{}\n", body)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let targets = ["apps/alpha", "apps/beta", "apps/gamma"];
+    for target in targets {
+        fs::create_dir_all(temp_dir.path().join(target)).unwrap();
+    }
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", temp_dir.path().to_str().unwrap()])
+        .args(["up", "--targets", "apps/*", "--parallel", "3"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    for target in targets {
+        let name = target.trim_start_matches("apps/");
+        let root = temp_dir.path().join(target);
+        assert_eq!(
+            fs::read_to_string(root.join("seen")).unwrap(),
+            format!("{} {}\n", name, name)
+        );
+        let history = fs::read_to_string(root.join(".migrate/history")).unwrap();
+        assert!(
+            history.contains(&format!("\tout.name={}\tout.path={}\n", name, name)),
+            "{}",
+            history
+        );
+    }
+}
+
+#[test]
+fn test_workspaces_run_in_each_package() {
+    let temp_dir = create_temp_dir();
+    for package in ["packages/api", "packages/web"] {
+        let migrations_dir = temp_dir.path().join(package).join("migrations");
+        fs::create_dir_all(&migrations_dir).unwrap();
+        let script = migrations_dir.join("00001-init.sh");
+        fs::write(&script, "#!/usr/bin/env bash\ntouch initialized\n").unwrap();
+        let mut perms = fs::metadata(&script).unwrap().permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(&script, perms).unwrap();
+    }
+
+    let run = |args: &[&str]| {
+        Command::new(get_binary_path())
+            .args(["--root", temp_dir.path().to_str().unwrap()])
+            .args(args)
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    // Pending migrations fail the aggregated check, with check's own exit code
+    let output = run(&["--workspaces", "check"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stdout.contains("=== packages/api ==="), "{}", stdout);
+    assert!(stdout.contains("=== packages/web ==="), "{}", stdout);
+    assert!(stdout.contains("2 of 2 workspace(s) failed"), "{}", stdout);
+
+    // A filter limits the run to matching workspaces
+    let output = run(&["--workspace", "packages/api", "up"]);
+    assert!(output.status.success());
+    assert!(temp_dir.path().join("packages/api/initialized").exists());
+    assert!(!temp_dir.path().join("packages/web/initialized").exists());
+
+    let output = run(&["--workspaces", "check"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("1 of 2 workspace(s) failed: packages/web"),
+        "{}",
+        stdout
+    );
+
+    // JSON output stays one event per line, with workspaces as events
+    let output = run(&["--workspaces", "up", "--format", "json"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let events: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).expect(line))
+        .collect();
+    assert_eq!(events[0]["event"], "workspace-started");
+    assert_eq!(events[0]["name"], "packages/api");
+    assert_eq!(
+        events.last().unwrap(),
+        &serde_json::json!({"event": "workspaces-finished", "total": 2, "failed": []})
+    );
+}
+
+#[test]
+fn test_workspaces_share_one_report() {
+    let temp_dir = create_temp_dir();
+    for (package, body) in [("packages/api", "exit 3"), ("packages/web", "true")] {
+        let migrations_dir = temp_dir.path().join(package).join("migrations");
+        fs::create_dir_all(&migrations_dir).unwrap();
+        let script = migrations_dir.join("00001-init.sh");
+        fs::write(&script, format!("#!/usr/bin/env bash\n{}\n", body)).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let junit = temp_dir.path().join("out.xml");
+
+    let output = Command::new(get_binary_path())
+        .args([
+            "--root",
+            temp_dir.path().to_str().unwrap(),
+            "--workspaces",
+            "up",
+        ])
+        .arg(format!("--report=junit={}", junit.display()))
+        .output()
+        .expect("Failed to execute command");
+    assert_eq!(output.status.code(), Some(3));
+
+    // The failure in the first workspace survives the second workspace's run
+    let xml = fs::read_to_string(&junit).unwrap();
+    assert!(
+        xml.contains("<testsuites name=\"migrate\" tests=\"2\" failures=\"1\""),
+        "{}",
+        xml
+    );
+    assert!(
+        xml.contains("<testsuite name=\"api\" tests=\"1\" failures=\"1\""),
+        "{}",
+        xml
+    );
+    assert!(
+        xml.contains("<testsuite name=\"web\" tests=\"1\" failures=\"0\""),
+        "{}",
+        xml
+    );
+}
+
+/// Run git in the given directory with a fixed identity, panicking on failure.
+fn git(dir: &std::path::Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .expect("Failed to run git");
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+/// Create a git repo with one commit, then a second commit that edits config.json.
+fn setup_recording_repo() -> tempfile::TempDir {
+    let temp_dir = create_temp_dir();
+    let root = temp_dir.path();
+    git(root, &["init", "-q"]);
+    fs::write(root.join("config.json"), "{\n  \"port\": 3000\n}\n").unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "-q", "-m", "initial"]);
+    fs::write(root.join("config.json"), "{\n  \"port\": 8080\n}\n").unwrap();
+    fs::write(root.join("NOTES.md"), "notes\n").unwrap();
+    git(root, &["add", "."]);
+    git(root, &["commit", "-q", "-m", "change port"]);
+    temp_dir
+}
+
+#[test]
+fn test_record_from_git_creates_patch_migration() {
+    let temp_dir = setup_recording_repo();
+    let root = temp_dir.path();
+
+    let output = Command::new(get_binary_path())
+        .args([
+            "--root",
+            root.to_str().unwrap(),
+            "record",
+            "change-port",
+            "--from-git",
+            "HEAD",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "record should succeed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Recorded 2 changed file(s)"), "{}", stdout);
+
+    let migration = fs::read_dir(root.join("migrations"))
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .find(|e| e.file_name().to_string_lossy().ends_with("-change-port.sh"))
+        .expect("Migration file should be created");
+    let id = migration
+        .file_name()
+        .to_string_lossy()
+        .trim_end_matches(".sh")
+        .to_string();
+    let patch =
+        fs::read_to_string(root.join("migrations").join(&id).join("changes.patch")).unwrap();
+    assert!(patch.contains("+  \"port\": 8080"));
+
+    // Roll the checkout back and apply the recorded migration
+    git(root, &["reset", "-q", "--hard", "HEAD~1"]);
+    assert!(!root.join("NOTES.md").exists());
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root.to_str().unwrap(), "up"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "up should succeed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let config = fs::read_to_string(root.join("config.json")).unwrap();
+    assert!(config.contains("8080"));
+    assert!(root.join("NOTES.md").exists());
+}
+
+#[test]
+fn test_recorded_migration_fails_when_patch_does_not_apply() {
+    let temp_dir = setup_recording_repo();
+    let root = temp_dir.path();
+
+    let output = Command::new(get_binary_path())
+        .args([
+            "--root",
+            root.to_str().unwrap(),
+            "record",
+            "change-port",
+            "--from-git",
+            "HEAD",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+
+    // Diverge from the recorded starting point
+    git(root, &["reset", "-q", "--hard", "HEAD~1"]);
+    fs::write(root.join("config.json"), "{\n  \"port\": 4000\n}\n").unwrap();
+
+    let output = Command::new(get_binary_path())
+        .args(["--root", root.to_str().unwrap(), "up"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("do not apply cleanly"), "{}", stderr);
+
+    // Nothing was changed and nothing was recorded
+    let config = fs::read_to_string(root.join("config.json")).unwrap();
+    assert!(config.contains("4000"));
+    assert!(!root.join("migrations/history").exists());
+}